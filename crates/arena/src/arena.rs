@@ -1,25 +1,33 @@
 use std::iter::{self, FusedIterator};
-use std::{ops, slice};
+use std::{fmt, ops, slice};
 
+use crate::provenance::ArenaId;
 use crate::{Index, IndexRange, IndexRangeIterator};
 
 pub struct Arena<T> {
     data: Vec<T>,
+    id: ArenaId,
 }
 
 impl<T> Arena<T> {
     #[inline]
     pub fn new() -> Self {
-        Arena { data: Vec::new() }
+        Arena::with_capacity(0)
     }
 
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
         Arena {
             data: Vec::with_capacity(capacity),
+            id: ArenaId::fresh(),
         }
     }
 
+    #[inline]
+    pub(crate) fn id(&self) -> ArenaId {
+        self.id
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.data.len()
@@ -42,7 +50,9 @@ impl<T> Arena<T> {
 
     #[inline]
     pub fn next_index(&self) -> Index<T> {
-        Index::from_usize(self.data.len()).unwrap()
+        let index = Index::<T>::from_usize(self.data.len()).unwrap();
+
+        Index::tagged(index.inner(), self.id)
     }
 
     #[inline]
@@ -65,10 +75,37 @@ impl<T> Arena<T> {
         IndexRange { start, end }
     }
 
+    /// Records the current length, to be restored by [`Arena::rollback_to`].
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            len: self.next_index(),
+        }
+    }
+
+    /// Removes every value pushed since `snapshot` was taken.
+    #[track_caller]
+    pub fn rollback_to(&mut self, snapshot: Snapshot<T>) {
+        self.id.check(snapshot.len.arena());
+
+        let len = snapshot.len.index();
+        assert!(len <= self.data.len(), "snapshot is newer than the arena");
+
+        self.data.truncate(len);
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn get(&self, index: Index<T>) -> Option<&T> {
+        self.id.check(index.arena());
+        self.data.get(index.index())
+    }
+
     #[inline]
     pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             iter: self.data.iter().enumerate(),
+            id: self.id,
         }
     }
 
@@ -76,15 +113,13 @@ impl<T> Arena<T> {
     pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
             iter: self.data.iter_mut().enumerate(),
+            id: self.id,
         }
     }
 
     #[inline]
     pub fn keys(&self) -> IndexRangeIterator<T> {
-        IndexRangeIterator::new(
-            Index::new_unchecked(0),
-            Index::from_usize_unchecked(self.data.len()),
-        )
+        IndexRangeIterator::new(Index::tagged(0, self.id), self.next_index())
     }
 
     #[inline]
@@ -98,6 +133,34 @@ impl<T> Arena<T> {
     }
 }
 
+/// The length of an [`Arena`] at some point.
+pub struct Snapshot<T> {
+    len: Index<T>,
+}
+
+impl<T> Clone for Snapshot<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Snapshot<T> {}
+
+impl<T> Snapshot<T> {
+    /// The length of the arena when the snapshot was taken.
+    #[inline]
+    pub(crate) fn len(self) -> usize {
+        self.len.index()
+    }
+}
+
+impl<T> fmt::Debug for Snapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Snapshot").field(&self.len.index()).finish()
+    }
+}
+
 impl<T> Default for Arena<T> {
     #[inline]
     fn default() -> Self {
@@ -109,14 +172,18 @@ impl<T> ops::Index<Index<T>> for Arena<T> {
     type Output = T;
 
     #[inline]
+    #[track_caller]
     fn index(&self, index: Index<T>) -> &T {
+        index.check(self.id);
         &self.data[index.index()]
     }
 }
 
 impl<T> ops::IndexMut<Index<T>> for Arena<T> {
     #[inline]
+    #[track_caller]
     fn index_mut(&mut self, index: Index<T>) -> &mut T {
+        index.check(self.id);
         &mut self.data[index.index()]
     }
 }
@@ -125,20 +192,27 @@ impl<T> ops::Index<IndexRange<T>> for Arena<T> {
     type Output = [T];
 
     #[inline]
+    #[track_caller]
     fn index(&self, index: IndexRange<T>) -> &[T] {
+        index.start.check(self.id);
+        index.end.check(self.id);
         &self.data[index.start.index()..index.end.index()]
     }
 }
 
 impl<T> ops::IndexMut<IndexRange<T>> for Arena<T> {
     #[inline]
+    #[track_caller]
     fn index_mut(&mut self, index: IndexRange<T>) -> &mut [T] {
+        index.start.check(self.id);
+        index.end.check(self.id);
         &mut self.data[index.start.index()..index.end.index()]
     }
 }
 
 pub struct Iter<'a, T: 'a> {
     iter: iter::Enumerate<slice::Iter<'a, T>>,
+    id: ArenaId,
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
@@ -148,7 +222,7 @@ impl<'a, T> Iterator for Iter<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter
             .next()
-            .map(|(i, v)| (Index::from_usize_unchecked(i), v))
+            .map(|(i, v)| (Index::from_usize_unchecked(i, self.id), v))
     }
 
     #[inline]
@@ -162,7 +236,7 @@ impl<T> DoubleEndedIterator for Iter<'_, T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.iter
             .next_back()
-            .map(|(i, v)| (Index::from_usize_unchecked(i), v))
+            .map(|(i, v)| (Index::from_usize_unchecked(i, self.id), v))
     }
 }
 
@@ -180,6 +254,7 @@ impl<'a, T> FusedIterator for Iter<'a, T> where
 
 pub struct IterMut<'a, T: 'a> {
     iter: iter::Enumerate<slice::IterMut<'a, T>>,
+    id: ArenaId,
 }
 
 impl<'a, T> Iterator for IterMut<'a, T> {
@@ -189,7 +264,7 @@ impl<'a, T> Iterator for IterMut<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter
             .next()
-            .map(|(i, v)| (Index::from_usize_unchecked(i), v))
+            .map(|(i, v)| (Index::from_usize_unchecked(i, self.id), v))
     }
 
     #[inline]
@@ -203,7 +278,7 @@ impl<T> DoubleEndedIterator for IterMut<'_, T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.iter
             .next_back()
-            .map(|(i, v)| (Index::from_usize_unchecked(i), v))
+            .map(|(i, v)| (Index::from_usize_unchecked(i, self.id), v))
     }
 }
 
@@ -0,0 +1,275 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::{fmt, slice};
+
+use crate::provenance::ArenaId;
+use crate::{Arena, Index};
+
+type Word = u64;
+
+const WORD_BITS: usize = Word::BITS as usize;
+
+/// A set of indices into an [`Arena`], such as the nodes visited so far.
+pub struct IndexBitSet<T> {
+    words: Vec<Word>,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> IndexBitSet<T> {
+    #[inline]
+    pub fn new() -> Self {
+        IndexBitSet {
+            words: Vec::new(),
+            arena: ArenaId::NONE,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates an empty set with room for every index of `arena`.
+    pub fn for_arena(arena: &Arena<T>) -> Self {
+        IndexBitSet {
+            words: vec![0; arena.next_index().index().div_ceil(WORD_BITS)],
+            arena: arena.id(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn contains(&self, index: Index<T>) -> bool {
+        let (word, mask) = self.locate(index);
+
+        self.words.get(word).is_some_and(|&word| word & mask != 0)
+    }
+
+    /// Adds `index` to the set, returning `false` if it was already present.
+    #[track_caller]
+    pub fn insert(&mut self, index: Index<T>) -> bool {
+        let (word, mask) = self.locate(index);
+        self.arena.bind(index.arena());
+
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        let word = &mut self.words[word];
+        let inserted = *word & mask == 0;
+        *word |= mask;
+
+        inserted
+    }
+
+    /// Removes `index` from the set, returning `false` if it was not present.
+    #[track_caller]
+    pub fn remove(&mut self, index: Index<T>) -> bool {
+        let (word, mask) = self.locate(index);
+
+        let Some(word) = self.words.get_mut(word) else {
+            return false;
+        };
+
+        let removed = *word & mask != 0;
+        *word &= !mask;
+
+        removed
+    }
+
+    /// Iterates over the indices in the set in ascending order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            words: self.words.iter(),
+            word: 0,
+            base: 0,
+            arena: self.arena,
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn locate(&self, index: Index<T>) -> (usize, Word) {
+        index.check(self.arena);
+
+        let index = index.index();
+
+        (index / WORD_BITS, 1 << (index % WORD_BITS))
+    }
+}
+
+impl<T> Default for IndexBitSet<T> {
+    #[inline]
+    fn default() -> Self {
+        IndexBitSet::new()
+    }
+}
+
+impl<T> Clone for IndexBitSet<T> {
+    fn clone(&self) -> Self {
+        IndexBitSet {
+            words: self.words.clone(),
+            arena: self.arena,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for IndexBitSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<Index<T>> for IndexBitSet<T> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Index<T>>,
+    {
+        let mut set = IndexBitSet::new();
+        set.extend(iter);
+
+        set
+    }
+}
+
+impl<T> Extend<Index<T>> for IndexBitSet<T> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Index<T>>,
+    {
+        for index in iter {
+            self.insert(index);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IndexBitSet<T> {
+    type Item = Index<T>;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a, T> {
+    words: slice::Iter<'a, Word>,
+    word: Word,
+    base: usize,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = Index<T>;
+
+    fn next(&mut self) -> Option<Index<T>> {
+        while self.word == 0 {
+            self.word = *self.words.next()?;
+            self.base += WORD_BITS;
+        }
+
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+
+        // `base` is one word ahead, so that it never starts out negative.
+        let index = self.base - WORD_BITS + bit;
+
+        Some(Index::from_usize_unchecked(index, self.arena))
+    }
+}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexBitSet;
+    use crate::{Arena, Index};
+
+    fn index(i: u32) -> Index<()> {
+        Index::new(i).unwrap()
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut set = IndexBitSet::new();
+
+        assert!(set.insert(index(3)));
+        assert!(!set.insert(index(3)));
+        assert!(set.insert(index(70)));
+        assert_eq!(set.len(), 2);
+
+        assert!(set.contains(index(70)));
+        assert!(!set.contains(index(6)));
+        assert!(!set.contains(index(1000)));
+
+        assert!(set.remove(index(3)));
+        assert!(!set.remove(index(3)));
+        assert!(!set.remove(index(1000)));
+        assert_eq!(set.len(), 1);
+
+        set.clear();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let indices = [0, 1, 63, 64, 127, 128, 300];
+        let set: IndexBitSet<()> =
+            indices.iter().rev().map(|&i| index(i)).collect();
+
+        assert!(set.iter().map(Index::index).eq(indices.map(|i| i as usize)));
+        assert_eq!(set.len(), indices.len());
+
+        let mut set = set;
+        set.remove(index(0));
+        set.remove(index(64));
+        set.remove(index(300));
+
+        // Trailing and inner words that are left empty are skipped.
+        assert!(set.iter().map(Index::index).eq([1, 63, 127, 128]));
+        assert!(IndexBitSet::<()>::new().iter().next().is_none());
+    }
+
+    #[test]
+    fn for_arena() {
+        let mut arena = Arena::new();
+        let indices: Vec<_> = (0..65).map(|_| arena.push(())).collect();
+
+        let mut set = IndexBitSet::for_arena(&arena);
+        assert!(set.is_empty());
+
+        set.insert(indices[64]);
+        set.insert(indices[0]);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [indices[0], indices[64]]);
+    }
+
+    #[test]
+    #[cfg(feature = "provenance")]
+    #[should_panic = "used with arena"]
+    fn collected_set_checks_provenance() {
+        let mut a = Arena::new();
+        let mut b = Arena::new();
+
+        let set: IndexBitSet<()> = [a.push(())].into_iter().collect();
+        set.contains(b.push(()));
+    }
+}
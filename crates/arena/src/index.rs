@@ -5,11 +5,15 @@ use std::marker::PhantomData;
 use std::ops::Range;
 use std::{fmt, slice};
 
+use crate::provenance::ArenaId;
+
 pub(crate) type IndexInner = u32;
 
-#[repr(transparent)]
+#[cfg_attr(not(feature = "provenance"), repr(transparent))]
+#[cfg_attr(feature = "provenance", repr(C))]
 pub struct Index<T> {
     index: IndexInner,
+    arena: ArenaId,
     phantom: PhantomData<fn() -> T>,
 }
 
@@ -27,8 +31,14 @@ impl<T> Index<T> {
 
     #[inline]
     pub(crate) const fn new_unchecked(index: u32) -> Self {
+        Index::tagged(index, ArenaId::NONE)
+    }
+
+    #[inline]
+    pub(crate) const fn tagged(index: u32, arena: ArenaId) -> Self {
         Index {
             index,
+            arena,
             phantom: PhantomData,
         }
     }
@@ -39,8 +49,8 @@ impl<T> Index<T> {
     }
 
     #[inline]
-    pub(crate) fn from_usize_unchecked(index: usize) -> Self {
-        Index::new_unchecked(index as IndexInner)
+    pub(crate) fn from_usize_unchecked(index: usize, arena: ArenaId) -> Self {
+        Index::tagged(index as IndexInner, arena)
     }
 
     #[inline]
@@ -52,6 +62,29 @@ impl<T> Index<T> {
     pub(crate) fn inner(self) -> IndexInner {
         self.index
     }
+
+    #[inline]
+    pub(crate) fn arena(self) -> ArenaId {
+        self.arena
+    }
+
+    /// Forgets the type of value this index refers to.
+    #[inline]
+    pub(crate) fn erase(self) -> Index<()> {
+        Index::tagged(self.index, self.arena)
+    }
+
+    /// Panics if this index may not be used with the arena `arena`.
+    #[inline(always)]
+    #[track_caller]
+    pub(crate) fn check(self, arena: ArenaId) {
+        #[cfg(feature = "provenance")]
+        if self.index == IndexInner::MAX {
+            panic!("used `Index::INVALID` to index an arena");
+        }
+
+        arena.check(self.arena);
+    }
 }
 
 impl<T> Clone for Index<T> {
@@ -160,6 +193,7 @@ impl<T> IntoIterator for IndexRange<T> {
 
 pub struct IndexRangeIterator<T> {
     range: Range<IndexInner>,
+    arena: ArenaId,
     phantom: PhantomData<fn() -> T>,
 }
 
@@ -168,6 +202,7 @@ impl<T> IndexRangeIterator<T> {
     pub(crate) fn new(start: Index<T>, end: Index<T>) -> Self {
         IndexRangeIterator {
             range: start.index..end.index,
+            arena: start.arena,
             phantom: PhantomData,
         }
     }
@@ -178,7 +213,7 @@ impl<T> Iterator for IndexRangeIterator<T> {
 
     #[inline]
     fn next(&mut self) -> Option<Index<T>> {
-        Some(Index::new_unchecked(self.range.next()?))
+        Some(Index::tagged(self.range.next()?, self.arena))
     }
 
     #[inline]
@@ -190,7 +225,7 @@ impl<T> Iterator for IndexRangeIterator<T> {
 impl<T> DoubleEndedIterator for IndexRangeIterator<T> {
     #[inline]
     fn next_back(&mut self) -> Option<Index<T>> {
-        Some(Index::new_unchecked(self.range.next_back()?))
+        Some(Index::tagged(self.range.next_back()?, self.arena))
     }
 }
 
@@ -206,42 +241,35 @@ impl<T> FusedIterator for IndexRangeIterator<T> where
 {
 }
 
-pub trait InnerSlice {
-    fn as_index<T>(&self) -> &[Index<T>];
-
-    fn as_mut_index<T>(&mut self) -> &mut [Index<T>];
+/// Reinterprets indices to values of one type as indices to another.
+#[inline]
+pub(crate) fn cast_slice<T, U>(slice: &[Index<T>]) -> &[Index<U>] {
+    // `Index<T>` only refers to `T` through `PhantomData`.
+    unsafe { slice::from_raw_parts(slice.as_ptr().cast(), slice.len()) }
 }
 
-impl InnerSlice for [IndexInner] {
-    #[inline]
-    fn as_index<T>(&self) -> &[Index<T>] {
-        unsafe { slice::from_raw_parts(self.as_ptr().cast(), self.len()) }
-    }
-
-    #[inline]
-    fn as_mut_index<T>(&mut self) -> &mut [Index<T>] {
-        unsafe {
-            slice::from_raw_parts_mut(self.as_mut_ptr().cast(), self.len())
-        }
-    }
+#[inline]
+pub(crate) fn cast_slice_mut<T, U>(slice: &mut [Index<T>]) -> &mut [Index<U>] {
+    unsafe { slice::from_raw_parts_mut(slice.as_mut_ptr().cast(), slice.len()) }
 }
 
-pub trait IndexSlice {
-    fn as_inner(&self) -> &[IndexInner];
+#[cfg(all(test, feature = "provenance"))]
+mod tests {
+    use crate::{Arena, ArenaMap, Index};
 
-    fn _as_mut_inner(&mut self) -> &mut [IndexInner];
-}
+    #[test]
+    #[should_panic = "used `Index::INVALID`"]
+    fn invalid() {
+        let mut arena = Arena::new();
+        arena.push('a');
 
-impl<T> IndexSlice for [Index<T>] {
-    #[inline]
-    fn as_inner(&self) -> &[IndexInner] {
-        unsafe { slice::from_raw_parts(self.as_ptr().cast(), self.len()) }
+        let _ = arena[Index::INVALID];
     }
 
-    #[inline]
-    fn _as_mut_inner(&mut self) -> &mut [IndexInner] {
-        unsafe {
-            slice::from_raw_parts_mut(self.as_mut_ptr().cast(), self.len())
-        }
+    #[test]
+    #[should_panic = "used `Index::INVALID`"]
+    fn invalid_in_map() {
+        let mut map = ArenaMap::<(), ()>::new();
+        map.insert(Index::INVALID, ());
     }
 }
@@ -1,8 +1,9 @@
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::ops;
+use std::{ops, slice};
 
-use crate::{Arena, Index};
+use crate::{Arena, ArenaSnapshot, Index};
 
 pub struct Interned<T> {
     arena: Arena<T>,
@@ -16,6 +17,28 @@ impl<T> Interned<T> {
             index: HashMap::new(),
         }
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    #[inline]
+    pub fn values(&self) -> slice::Iter<'_, T> {
+        self.arena.values()
+    }
+
+    /// Records the number of interned values, to be restored by
+    /// [`Interned::rollback_to`].
+    #[inline]
+    pub fn snapshot(&self) -> ArenaSnapshot<T> {
+        self.arena.snapshot()
+    }
 }
 
 impl<T> Interned<T>
@@ -28,6 +51,27 @@ where
             .entry(value)
             .or_insert_with_key(|value| self.arena.push(value.clone()))
     }
+
+    /// Returns the index of `value` if it has already been interned.
+    #[inline]
+    pub fn get<Q>(&self, value: &Q) -> Option<Index<T>>
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.index.get(value).copied()
+    }
+
+    /// Forgets every value interned since `snapshot` was taken, in time
+    /// proportional to their number.
+    #[track_caller]
+    pub fn rollback_to(&mut self, snapshot: ArenaSnapshot<T>) {
+        for value in self.arena.values().skip(snapshot.len()) {
+            self.index.remove(value);
+        }
+
+        self.arena.rollback_to(snapshot);
+    }
 }
 
 impl<T> Default for Interned<T> {
@@ -45,3 +89,24 @@ impl<T> ops::Index<Index<T>> for Interned<T> {
         &self.arena[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Interned;
+
+    #[test]
+    fn rollback() {
+        let mut interned = Interned::new();
+        let a = interned.intern("a");
+        let snapshot = interned.snapshot();
+
+        interned.intern("b");
+        interned.intern("a");
+        interned.rollback_to(snapshot);
+
+        assert_eq!(interned.len(), 1);
+        assert_eq!(interned.get("a"), Some(a));
+        assert_eq!(interned.get("b"), None);
+        assert_eq!(interned.intern("b").index(), 1);
+    }
+}
@@ -1,11 +1,18 @@
 mod arena;
+mod bit_set;
 mod index;
 mod interned;
 mod list;
+mod map;
 mod option;
+mod provenance;
+mod symbol;
 
-pub use arena::Arena;
+pub use arena::{Arena, Snapshot as ArenaSnapshot};
+pub use bit_set::IndexBitSet;
 pub use index::{Index, IndexRange, IndexRangeIterator};
 pub use interned::Interned;
-pub use list::{IndexArena, List};
+pub use list::{IndexArena, IndexArenaSnapshot, List, ListBuilder};
+pub use map::{ArenaMap, Entry, SparseArenaMap};
 pub use option::NonMaxIndex;
+pub use symbol::{DisplaySymbol, Symbol, Symbols};
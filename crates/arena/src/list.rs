@@ -1,9 +1,10 @@
+use std::fmt;
 use std::marker::PhantomData;
 use std::ops::{self, Range};
-use std::{fmt, iter};
 
-use crate::index::{Index, IndexInner, IndexSlice, InnerSlice};
+use crate::index::{self, Index, IndexInner};
 use crate::option::NonMaxIndex;
+use crate::provenance::ArenaId;
 
 pub struct List<T> {
     start: NonMaxIndex<IndexInner>,
@@ -22,16 +23,28 @@ impl<T> List<T> {
         }
     }
 
-    fn from_range(start: usize, end: usize) -> Self {
+    fn from_range(start: usize, end: usize, arena: ArenaId) -> Self {
+        let index = |i| {
+            let i = IndexInner::try_from(i).ok()?;
+
+            NonMaxIndex::new(Index::tagged(i, arena))
+        };
+
         List {
-            start: Index::from_usize(start).and_then(NonMaxIndex::new).unwrap(),
-            end: Index::from_usize(end).and_then(NonMaxIndex::new).unwrap(),
+            start: index(start).unwrap(),
+            end: index(end).unwrap(),
             phantom: PhantomData,
         }
     }
 
     #[inline]
-    fn index(self) -> Range<usize> {
+    fn arena(self) -> ArenaId {
+        self.start.get().arena()
+    }
+
+    /// Returns the positions of the list's elements in its [`IndexArena`].
+    #[inline]
+    pub fn range(self) -> Range<usize> {
         self.start.get().index()..self.end.get().index()
     }
 
@@ -71,24 +84,40 @@ impl<T> fmt::Debug for List<T> {
     }
 }
 
-#[derive(Default)]
 pub struct IndexArena {
-    data: Vec<IndexInner>,
+    data: Vec<Index<()>>,
+    scratch: Vec<Index<()>>,
+    open: usize,
+    id: ArenaId,
 }
 
 impl IndexArena {
     #[inline]
     pub fn new() -> Self {
-        Default::default()
+        IndexArena::with_capacity(0)
     }
 
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
         IndexArena {
             data: Vec::with_capacity(capacity),
+            scratch: Vec::new(),
+            open: 0,
+            id: ArenaId::fresh(),
         }
     }
 
+    /// Returns the total number of elements of every list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     #[inline]
     pub fn reserve(&mut self, additional: usize) {
         self.data.reserve(additional);
@@ -99,11 +128,90 @@ impl IndexArena {
         self.data.reserve_exact(additional);
     }
 
+    /// Returns the list of the elements at `range`, or `None` if it is out of
+    /// bounds.
+    pub fn list<T>(&self, range: Range<usize>) -> Option<List<T>> {
+        if range.start > range.end || range.end > self.data.len() {
+            return None;
+        }
+
+        Some(List::from_range(range.start, range.end, self.id))
+    }
+
+    /// Records the current length, to be restored by
+    /// [`IndexArena::rollback_to`].
+    #[inline]
+    pub fn snapshot(&self) -> IndexArenaSnapshot {
+        IndexArenaSnapshot {
+            len: self.data.len(),
+            scratch: self.scratch.len(),
+            open: self.open,
+        }
+    }
+
+    /// Removes every list added since `snapshot` was taken, and abandons the
+    /// lists started since then.
+    pub fn rollback_to(&mut self, snapshot: IndexArenaSnapshot) {
+        assert!(
+            snapshot.len <= self.data.len()
+                && snapshot.scratch <= self.scratch.len()
+                && snapshot.open <= self.open,
+            "snapshot is newer than the arena",
+        );
+
+        self.data.truncate(snapshot.len);
+        self.scratch.truncate(snapshot.scratch);
+        self.open = snapshot.open;
+    }
+
+    /// Starts a list whose elements are added one at a time with
+    /// [`IndexArena::push`]. Other lists may be built in the meantime, as long
+    /// as they are finished first.
+    pub fn start_list<T>(&mut self) -> ListBuilder<T> {
+        self.open += 1;
+
+        ListBuilder {
+            start: self.scratch.len(),
+            depth: self.open,
+            arena: self.id,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Appends `index` to the innermost list being built.
+    #[track_caller]
+    pub fn push<T>(&mut self, list: &ListBuilder<T>, index: Index<T>) {
+        self.check_innermost(list);
+        self.scratch.push(index.erase());
+    }
+
+    /// Adds the elements pushed to `list` as a contiguous list.
+    #[track_caller]
+    pub fn finish_list<T>(&mut self, list: ListBuilder<T>) -> List<T> {
+        self.check_innermost(&list);
+        self.open -= 1;
+
+        let start = self.data.len();
+        self.data.extend(self.scratch.drain(list.start..));
+
+        List::from_range(start, self.data.len(), self.id)
+    }
+
+    #[track_caller]
+    fn check_innermost<T>(&self, list: &ListBuilder<T>) {
+        self.id.check(list.arena);
+
+        assert_eq!(
+            list.depth, self.open,
+            "list used while a nested list is being built",
+        );
+    }
+
     pub fn extend_from_slice<T>(&mut self, slice: &[Index<T>]) -> List<T> {
         let start = self.data.len();
-        self.data.extend_from_slice(slice.as_inner());
+        self.data.extend_from_slice(index::cast_slice(slice));
 
-        List::from_range(start, self.data.len())
+        List::from_range(start, self.data.len(), self.id)
     }
 
     pub fn extend<I, T>(&mut self, iter: I) -> List<T>
@@ -111,23 +219,41 @@ impl IndexArena {
         I: IntoIterator<Item = Index<T>>,
     {
         let start = self.data.len();
-        self.data.extend(iter.into_iter().map(Index::inner));
+        self.data.extend(iter.into_iter().map(Index::erase));
 
-        List::from_range(start, self.data.len())
+        List::from_range(start, self.data.len(), self.id)
     }
+}
 
-    pub fn extend_zeroed<T>(&mut self, len: usize) -> List<T> {
-        let start = self.data.len();
-        self.data.extend(iter::repeat_n(0, len));
-
-        List::from_range(start, self.data.len())
+impl Default for IndexArena {
+    #[inline]
+    fn default() -> Self {
+        IndexArena::new()
     }
+}
 
-    pub fn extend_invalid<T>(&mut self, len: usize) -> List<T> {
-        let start = self.data.len();
-        self.data.extend(iter::repeat_n(IndexInner::MAX, len));
+/// The length of an [`IndexArena`] at some point.
+#[derive(Clone, Copy, Debug)]
+pub struct IndexArenaSnapshot {
+    len: usize,
+    scratch: usize,
+    open: usize,
+}
+
+/// A list being built by [`IndexArena::start_list`].
+pub struct ListBuilder<T> {
+    start: usize,
+    depth: usize,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
 
-        List::from_range(start, self.data.len())
+impl<T> fmt::Debug for ListBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ListBuilder")
+            .field("start", &self.start)
+            .field("depth", &self.depth)
+            .finish()
     }
 }
 
@@ -135,14 +261,18 @@ impl<T> ops::Index<List<T>> for IndexArena {
     type Output = [Index<T>];
 
     #[inline]
+    #[track_caller]
     fn index(&self, index: List<T>) -> &[Index<T>] {
-        self.data[index.index()].as_index()
+        self.id.check(index.arena());
+        index::cast_slice(&self.data[index.range()])
     }
 }
 
 impl<T> ops::IndexMut<List<T>> for IndexArena {
     #[inline]
+    #[track_caller]
     fn index_mut(&mut self, index: List<T>) -> &mut [Index<T>] {
-        self.data[index.index()].as_mut_index()
+        self.id.check(index.arena());
+        index::cast_slice_mut(&mut self.data[index.range()])
     }
 }
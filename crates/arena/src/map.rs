@@ -0,0 +1,606 @@
+use std::collections::btree_map::{self, BTreeMap};
+use std::iter::{self, FusedIterator};
+use std::marker::PhantomData;
+use std::{fmt, ops, slice};
+
+use crate::provenance::ArenaId;
+use crate::{Arena, Index};
+
+/// Per-node data for the values of an [`Arena`], stored densely by index.
+pub struct ArenaMap<T, V> {
+    data: Vec<Option<V>>,
+    len: usize,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, V> ArenaMap<T, V> {
+    #[inline]
+    pub fn new() -> Self {
+        ArenaMap {
+            data: Vec::new(),
+            len: 0,
+            arena: ArenaId::NONE,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates an empty map with room for every index of `arena`.
+    pub fn for_arena(arena: &Arena<T>) -> Self {
+        let mut map = ArenaMap::new();
+        map.data.resize_with(arena.next_index().index(), || None);
+        map.arena = arena.id();
+
+        map
+    }
+
+    /// Returns the number of indices with a value.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn contains_key(&self, index: Index<T>) -> bool {
+        self.get(index).is_some()
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn get(&self, index: Index<T>) -> Option<&V> {
+        self.arena.check(index.arena());
+        self.data.get(index.index())?.as_ref()
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn get_mut(&mut self, index: Index<T>) -> Option<&mut V> {
+        self.arena.check(index.arena());
+        self.data.get_mut(index.index())?.as_mut()
+    }
+
+    #[track_caller]
+    pub fn insert(&mut self, index: Index<T>, value: V) -> Option<V> {
+        let old = self.slot(index).replace(value);
+
+        if old.is_none() {
+            self.len += 1;
+        }
+
+        old
+    }
+
+    #[track_caller]
+    pub fn remove(&mut self, index: Index<T>) -> Option<V> {
+        self.arena.check(index.arena());
+
+        let old = self.data.get_mut(index.index())?.take();
+
+        if old.is_some() {
+            self.len -= 1;
+        }
+
+        old
+    }
+
+    #[track_caller]
+    pub fn entry(&mut self, index: Index<T>) -> Entry<'_, T, V> {
+        self.slot(index);
+
+        Entry { map: self, index }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T, V> {
+        Iter {
+            iter: self.data.iter().enumerate(),
+            arena: self.arena,
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, V> {
+        IterMut {
+            iter: self.data.iter_mut().enumerate(),
+            arena: self.arena,
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = Index<T>> + '_ {
+        self.iter().map(|(index, _)| index)
+    }
+
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.data.iter().flatten()
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.data.iter_mut().flatten()
+    }
+
+    #[track_caller]
+    fn slot(&mut self, index: Index<T>) -> &mut Option<V> {
+        index.check(self.arena);
+        self.arena.bind(index.arena());
+
+        let i = index.index();
+
+        if i >= self.data.len() {
+            self.data.resize_with(i + 1, || None);
+        }
+
+        &mut self.data[i]
+    }
+}
+
+impl<T, V> Default for ArenaMap<T, V> {
+    #[inline]
+    fn default() -> Self {
+        ArenaMap::new()
+    }
+}
+
+impl<T, V: Clone> Clone for ArenaMap<T, V> {
+    fn clone(&self) -> Self {
+        ArenaMap {
+            data: self.data.clone(),
+            len: self.len,
+            arena: self.arena,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, V: fmt::Debug> fmt::Debug for ArenaMap<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, V> ops::Index<Index<T>> for ArenaMap<T, V> {
+    type Output = V;
+
+    #[inline]
+    fn index(&self, index: Index<T>) -> &V {
+        self.get(index).expect("no value for index")
+    }
+}
+
+impl<T, V> ops::IndexMut<Index<T>> for ArenaMap<T, V> {
+    #[inline]
+    fn index_mut(&mut self, index: Index<T>) -> &mut V {
+        self.get_mut(index).expect("no value for index")
+    }
+}
+
+impl<T, V> FromIterator<(Index<T>, V)> for ArenaMap<T, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (Index<T>, V)>,
+    {
+        let mut map = ArenaMap::new();
+        map.extend(iter);
+
+        map
+    }
+}
+
+impl<T, V> Extend<(Index<T>, V)> for ArenaMap<T, V> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Index<T>, V)>,
+    {
+        for (index, value) in iter {
+            self.insert(index, value);
+        }
+    }
+}
+
+/// A slot of an [`ArenaMap`] that may or may not hold a value.
+pub struct Entry<'a, T, V> {
+    map: &'a mut ArenaMap<T, V>,
+    index: Index<T>,
+}
+
+impl<'a, T, V> Entry<'a, T, V> {
+    #[inline]
+    pub fn key(&self) -> Index<T> {
+        self.index
+    }
+
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Some(value) = self.map.get_mut(self.index) {
+            f(value);
+        }
+
+        self
+    }
+
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        let slot = &mut self.map.data[self.index.index()];
+
+        if slot.is_none() {
+            self.map.len += 1;
+        }
+
+        slot.get_or_insert_with(default)
+    }
+
+    #[inline]
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+pub struct Iter<'a, T, V: 'a> {
+    iter: iter::Enumerate<slice::Iter<'a, Option<V>>>,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, V> Iterator for Iter<'a, T, V> {
+    type Item = (Index<T>, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|(i, value)| {
+            Some((Index::from_usize_unchecked(i, self.arena), value.as_ref()?))
+        })
+    }
+}
+
+impl<T, V> DoubleEndedIterator for Iter<'_, T, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.by_ref().rev().find_map(|(i, value)| {
+            Some((Index::from_usize_unchecked(i, self.arena), value.as_ref()?))
+        })
+    }
+}
+
+impl<T, V> FusedIterator for Iter<'_, T, V> {}
+
+pub struct IterMut<'a, T, V: 'a> {
+    iter: iter::Enumerate<slice::IterMut<'a, Option<V>>>,
+    arena: ArenaId,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, V> Iterator for IterMut<'a, T, V> {
+    type Item = (Index<T>, &'a mut V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|(i, value)| {
+            Some((Index::from_usize_unchecked(i, self.arena), value.as_mut()?))
+        })
+    }
+}
+
+impl<T, V> DoubleEndedIterator for IterMut<'_, T, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.by_ref().rev().find_map(|(i, value)| {
+            Some((Index::from_usize_unchecked(i, self.arena), value.as_mut()?))
+        })
+    }
+}
+
+impl<T, V> FusedIterator for IterMut<'_, T, V> {}
+
+/// Per-node data for a few values of an [`Arena`], iterated in index order.
+pub struct SparseArenaMap<T, V> {
+    data: BTreeMap<Index<T>, V>,
+}
+
+impl<T, V> SparseArenaMap<T, V> {
+    #[inline]
+    pub fn new() -> Self {
+        SparseArenaMap {
+            data: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    #[inline]
+    pub fn contains_key(&self, index: Index<T>) -> bool {
+        self.data.contains_key(&index)
+    }
+
+    #[inline]
+    pub fn get(&self, index: Index<T>) -> Option<&V> {
+        self.data.get(&index)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: Index<T>) -> Option<&mut V> {
+        self.data.get_mut(&index)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, index: Index<T>, value: V) -> Option<V> {
+        self.data.insert(index, value)
+    }
+
+    #[inline]
+    pub fn remove(&mut self, index: Index<T>) -> Option<V> {
+        self.data.remove(&index)
+    }
+
+    #[inline]
+    pub fn entry(
+        &mut self,
+        index: Index<T>,
+    ) -> btree_map::Entry<'_, Index<T>, V> {
+        self.data.entry(index)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Index<T>, &V)> {
+        self.data.iter().map(|(&index, value)| (index, value))
+    }
+
+    #[inline]
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = (Index<T>, &mut V)> {
+        self.data.iter_mut().map(|(&index, value)| (index, value))
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = Index<T>> + '_ {
+        self.data.keys().copied()
+    }
+
+    #[inline]
+    pub fn values(&self) -> btree_map::Values<'_, Index<T>, V> {
+        self.data.values()
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> btree_map::ValuesMut<'_, Index<T>, V> {
+        self.data.values_mut()
+    }
+
+    /// Moves the values into a dense [`ArenaMap`].
+    pub fn into_dense(self) -> ArenaMap<T, V> {
+        self.data.into_iter().collect()
+    }
+}
+
+impl<T, V> Default for SparseArenaMap<T, V> {
+    #[inline]
+    fn default() -> Self {
+        SparseArenaMap::new()
+    }
+}
+
+impl<T, V: Clone> Clone for SparseArenaMap<T, V> {
+    fn clone(&self) -> Self {
+        SparseArenaMap {
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T, V: fmt::Debug> fmt::Debug for SparseArenaMap<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, V> ops::Index<Index<T>> for SparseArenaMap<T, V> {
+    type Output = V;
+
+    #[inline]
+    fn index(&self, index: Index<T>) -> &V {
+        self.get(index).expect("no value for index")
+    }
+}
+
+impl<T, V> ops::IndexMut<Index<T>> for SparseArenaMap<T, V> {
+    #[inline]
+    fn index_mut(&mut self, index: Index<T>) -> &mut V {
+        self.get_mut(index).expect("no value for index")
+    }
+}
+
+impl<T, V> FromIterator<(Index<T>, V)> for SparseArenaMap<T, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (Index<T>, V)>,
+    {
+        SparseArenaMap {
+            data: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T, V> Extend<(Index<T>, V)> for SparseArenaMap<T, V> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Index<T>, V)>,
+    {
+        self.data.extend(iter);
+    }
+}
+
+impl<T, V> From<ArenaMap<T, V>> for SparseArenaMap<T, V> {
+    fn from(value: ArenaMap<T, V>) -> Self {
+        let arena = value.arena;
+
+        value
+            .data
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, value)| {
+                Some((Index::from_usize_unchecked(i, arena), value?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArenaMap, SparseArenaMap};
+    use crate::{Arena, Index};
+
+    fn index(i: u32) -> Index<()> {
+        Index::new(i).unwrap()
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut map = ArenaMap::new();
+
+        assert_eq!(map.insert(index(3), 'a'), None);
+        assert_eq!(map.insert(index(1), 'b'), None);
+        assert_eq!(map.insert(index(3), 'c'), Some('a'));
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.remove(index(3)), Some('c'));
+        assert_eq!(map.remove(index(3)), None);
+        assert_eq!(map.remove(index(10)), None);
+        assert_eq!(map.len(), 1);
+
+        assert!(!map.contains_key(index(0)));
+        assert_eq!(map[index(1)], 'b');
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(index(1)), None);
+    }
+
+    #[test]
+    fn entry() {
+        let mut map = ArenaMap::new();
+
+        *map.entry(index(2)).or_insert(0) += 1;
+        *map.entry(index(2)).or_insert(0) += 1;
+        map.entry(index(4))
+            .and_modify(|value| *value = 10)
+            .or_default();
+        map.entry(index(2)).and_modify(|value| *value *= 10);
+
+        assert_eq!(map.entry(index(5)).key(), index(5));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[index(2)], 20);
+        assert_eq!(map[index(4)], 0);
+    }
+
+    #[test]
+    fn iteration_order() {
+        let map: ArenaMap<(), char> =
+            [(index(5), 'a'), (index(0), 'b'), (index(2), 'c')]
+                .into_iter()
+                .collect();
+
+        let forward: Vec<_> =
+            map.iter().map(|(index, &c)| (index.index(), c)).collect();
+        let backward: Vec<_> = map
+            .iter()
+            .rev()
+            .map(|(index, &c)| (index.index(), c))
+            .collect();
+
+        assert_eq!(forward, [(0, 'b'), (2, 'c'), (5, 'a')]);
+        assert_eq!(backward, [(5, 'a'), (2, 'c'), (0, 'b')]);
+        assert!(map.values().eq(&['b', 'c', 'a']));
+
+        let sparse = SparseArenaMap::from(map);
+        assert!(sparse.keys().map(Index::index).eq([0, 2, 5]));
+
+        let dense = sparse.into_dense();
+        assert_eq!(dense.len(), 3);
+        assert!(dense.keys().map(Index::index).eq([0, 2, 5]));
+    }
+
+    #[test]
+    fn sparse() {
+        let mut map = SparseArenaMap::new();
+
+        assert_eq!(map.insert(index(9), 'a'), None);
+        assert_eq!(map.insert(index(4), 'b'), None);
+        assert_eq!(map.insert(index(9), 'c'), Some('a'));
+        *map.entry(index(6)).or_insert('d') = 'e';
+
+        assert_eq!(map.len(), 3);
+        assert!(map.values().eq(&['b', 'e', 'c']));
+        assert_eq!(map.iter().next_back(), Some((index(9), &'c')));
+
+        assert_eq!(map.remove(index(4)), Some('b'));
+        assert_eq!(map.remove(index(4)), None);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn bound_to_first_arena() {
+        let mut arena = Arena::new();
+        let a = arena.push(());
+        let b = arena.push(());
+
+        let mut map = ArenaMap::new();
+        map.insert(b, 'b');
+        map.insert(a, 'a');
+
+        // Indices from iteration carry the arena along.
+        let keys: Vec<_> = map.keys().collect();
+        assert_eq!(keys, [a, b]);
+        assert_eq!(map[keys[1]], 'b');
+    }
+
+    #[test]
+    #[cfg(feature = "provenance")]
+    #[should_panic = "used with arena"]
+    fn collected_map_checks_provenance() {
+        let mut a = Arena::new();
+        let mut b = Arena::new();
+
+        let map: ArenaMap<(), ()> = [(a.push(()), ())].into_iter().collect();
+        map.get(b.push(()));
+    }
+}
@@ -4,9 +4,11 @@ use std::num::NonZero;
 use std::{fmt, ops};
 
 use crate::index::{Index, IndexInner};
+use crate::provenance::ArenaId;
 
 pub struct NonMaxIndex<T> {
     inner: NonZero<IndexInner>,
+    arena: ArenaId,
     phantom: PhantomData<fn() -> T>,
 }
 
@@ -15,6 +17,7 @@ impl<T> NonMaxIndex<T> {
     pub fn new(index: Index<T>) -> Option<Self> {
         Some(NonMaxIndex {
             inner: NonZero::new(!index.inner())?,
+            arena: index.arena(),
             phantom: PhantomData,
         })
     }
@@ -23,13 +26,14 @@ impl<T> NonMaxIndex<T> {
     pub const fn zero() -> Self {
         NonMaxIndex {
             inner: unsafe { NonZero::new_unchecked(!0) },
+            arena: ArenaId::NONE,
             phantom: PhantomData,
         }
     }
 
     #[inline]
     pub fn get(self) -> Index<T> {
-        Index::new_unchecked(!self.inner.get())
+        Index::tagged(!self.inner.get(), self.arena)
     }
 }
 
@@ -0,0 +1,110 @@
+use std::fmt;
+
+/// Identifies the arena an index was created by. Without the `provenance`
+/// feature this is zero-sized and every check compiles to nothing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ArenaId(#[cfg(feature = "provenance")] u32);
+
+impl ArenaId {
+    /// The id of indices that were not created by an arena, which may be used
+    /// with any of them.
+    #[cfg(feature = "provenance")]
+    pub const NONE: ArenaId = ArenaId(0);
+
+    #[cfg(not(feature = "provenance"))]
+    pub const NONE: ArenaId = ArenaId();
+
+    #[cfg(feature = "provenance")]
+    pub fn fresh() -> ArenaId {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        static NEXT: AtomicU32 = AtomicU32::new(1);
+
+        ArenaId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    #[cfg(not(feature = "provenance"))]
+    #[inline(always)]
+    pub fn fresh() -> ArenaId {
+        ArenaId()
+    }
+
+    /// Panics if an index or list created by `other` is used with the arena
+    /// `self`.
+    #[inline(always)]
+    #[track_caller]
+    pub fn check(self, other: ArenaId) {
+        #[cfg(feature = "provenance")]
+        if self != ArenaId::NONE && other != ArenaId::NONE && self != other {
+            panic!(
+                "index created by arena #{} used with arena #{}",
+                other.0, self.0,
+            );
+        }
+
+        #[cfg(not(feature = "provenance"))]
+        let _ = other;
+    }
+
+    /// Checks `other` like [`ArenaId::check`], then takes it as the id of a
+    /// map or set that was created without an arena, so that later indices
+    /// are checked against the arena of the first one.
+    #[inline(always)]
+    #[track_caller]
+    pub fn bind(&mut self, other: ArenaId) {
+        self.check(other);
+
+        if *self == ArenaId::NONE {
+            *self = other;
+        }
+    }
+}
+
+impl fmt::Debug for ArenaId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "provenance")]
+        return write!(f, "#{}", self.0);
+
+        #[cfg(not(feature = "provenance"))]
+        f.write_str("#?")
+    }
+}
+
+#[cfg(all(test, feature = "provenance"))]
+mod tests {
+    use crate::{Arena, Index, IndexRange};
+
+    #[test]
+    fn untagged_indices() {
+        let mut a = Arena::new();
+        a.push('a');
+
+        assert_eq!(a[Index::new(0).unwrap()], 'a');
+    }
+
+    #[test]
+    #[should_panic = "used with arena"]
+    fn cross_arena() {
+        let mut a = Arena::new();
+        let mut b = Arena::new();
+        a.push('a');
+
+        let index = b.push('b');
+        let _ = a[index];
+    }
+
+    #[test]
+    #[should_panic = "used with arena"]
+    fn cross_arena_range() {
+        let mut a = Arena::new();
+        let mut b = Arena::new();
+        a.push('a');
+
+        let start = b.push('b');
+        let range = IndexRange {
+            start,
+            end: b.next_index(),
+        };
+        let _ = &a[range];
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::{Arc, PoisonError, RwLock};
+use std::{fmt, ops};
+
+use crate::{Index, Interned};
+
+/// An interned string, resolved through the [`Symbols`] of its session.
+///
+/// Symbols are numbered in the order they were first interned, so their
+/// ordering only depends on the session's own input.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(Index<Box<str>>);
+
+impl Symbol {
+    /// Returns an adapter that formats the symbol as its string in
+    /// `symbols`.
+    #[inline]
+    pub fn display(self, symbols: &Symbols) -> DisplaySymbol<'_> {
+        DisplaySymbol {
+            symbol: self,
+            symbols,
+        }
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({})", self.0.index())
+    }
+}
+
+pub struct DisplaySymbol<'a> {
+    symbol: Symbol,
+    symbols: &'a Symbols,
+}
+
+impl fmt::Display for DisplaySymbol<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.symbols[self.symbol], f)
+    }
+}
+
+/// The symbol table of a compilation session. Clones share the same table,
+/// which may be used from several threads.
+#[derive(Clone, Default)]
+pub struct Symbols {
+    interned: Arc<RwLock<Interned<Box<str>>>>,
+}
+
+impl Symbols {
+    #[inline]
+    pub fn new() -> Self {
+        Symbols::default()
+    }
+
+    pub fn intern(&self, name: &str) -> Symbol {
+        let interned =
+            self.interned.read().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(index) = interned.get(name) {
+            return Symbol(index);
+        }
+
+        drop(interned);
+
+        let mut interned = self
+            .interned
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        Symbol(interned.intern(name.into()))
+    }
+
+    #[track_caller]
+    pub fn get(&self, symbol: Symbol) -> &str {
+        let interned =
+            self.interned.read().unwrap_or_else(PoisonError::into_inner);
+        let name: *const str = &*interned[symbol.0];
+
+        // SAFETY: interned strings are never removed or modified, and they are
+        // boxed, so they stay in place while the table grows and live as long
+        // as any clone of `self`.
+        unsafe { &*name }
+    }
+}
+
+impl ops::Index<Symbol> for Symbols {
+    type Output = str;
+
+    #[inline]
+    #[track_caller]
+    fn index(&self, symbol: Symbol) -> &str {
+        self.get(symbol)
+    }
+}
+
+impl fmt::Debug for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Symbols").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbols;
+
+    #[test]
+    fn display() {
+        let symbols = Symbols::new();
+        let symbol = symbols.intern("width");
+
+        assert_eq!(symbol.display(&symbols).to_string(), "width");
+        assert_eq!(format!("`{:>7}`", symbol.display(&symbols)), "`  width`");
+    }
+}
@@ -1,8 +1,5 @@
-use std::ops::Range;
-
-use symbol_table::GlobalSymbol;
-
-pub type Symbol = GlobalSymbol;
+pub use adpl_arena::{DisplaySymbol, Symbol, Symbols};
+pub use adpl_util::{FileId, Span};
 
 #[derive(Debug)]
 pub struct File {
@@ -11,7 +8,16 @@ pub struct File {
 
 #[derive(Debug)]
 pub struct Item {
+    pub attrs: Vec<Attribute>,
     pub kind: ItemKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Attribute {
+    pub name: Id,
+    pub args: Option<Vec<Id>>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -126,7 +132,7 @@ pub struct UnaryOp {
     pub span: Span,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum UnaryKind {
     Neg,
     Not,
@@ -138,7 +144,7 @@ pub struct BinaryOp {
     pub span: Span,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BinaryKind {
     Add,
     Sub,
@@ -168,30 +174,3 @@ pub struct Constructor {
     pub generics: Vec<Expression>,
     pub fields: Vec<Assignment>,
 }
-
-#[derive(Clone, Copy, Debug)]
-pub struct Span(usize, usize);
-
-impl Span {
-    #[inline]
-    pub fn new(start: usize, end: usize) -> Span {
-        Span(start, end)
-    }
-}
-
-impl From<Range<usize>> for Span {
-    #[inline]
-    fn from(value: Range<usize>) -> Self {
-        Span::new(value.start, value.end)
-    }
-}
-
-impl From<Span> for Range<usize> {
-    #[inline]
-    fn from(value: Span) -> Self {
-        Range {
-            start: value.0,
-            end: value.1,
-        }
-    }
-}
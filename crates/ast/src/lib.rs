@@ -1,3 +1,5 @@
 mod ast;
+mod visit;
 
 pub use ast::*;
+pub use visit::*;
@@ -0,0 +1,460 @@
+use adpl_util::with_sufficient_stack;
+
+use crate::{
+    Assignment, Attribute, Block, Call, Constructor, Definition, ExprKind,
+    Expression, Field, File, Id, Item, ItemKind, Literal, Parameter, Record,
+    Signature, Statement, StmtKind, Type,
+};
+
+/// A pass over a syntax tree that only reads it. Every method defaults to
+/// visiting the children of its node, in source order, with the matching
+/// `walk_*` function.
+pub trait Visit<'ast>: Sized {
+    fn visit_file(&mut self, file: &'ast File) {
+        walk_file(self, file);
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item);
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast Attribute) {
+        walk_attribute(self, attr);
+    }
+
+    fn visit_record(&mut self, record: &'ast Record) {
+        walk_record(self, record);
+    }
+
+    fn visit_field(&mut self, field: &'ast Field) {
+        walk_field(self, field);
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_definition(&mut self, def: &'ast Definition) {
+        walk_definition(self, def);
+    }
+
+    fn visit_signature(&mut self, sig: &'ast Signature) {
+        walk_signature(self, sig);
+    }
+
+    fn visit_parameter(&mut self, param: &'ast Parameter) {
+        walk_parameter(self, param);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_assignment(&mut self, assign: &'ast Assignment) {
+        walk_assignment(self, assign);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_call(&mut self, call: &'ast Call) {
+        walk_call(self, call);
+    }
+
+    fn visit_constructor(&mut self, cons: &'ast Constructor) {
+        walk_constructor(self, cons);
+    }
+
+    fn visit_id(&mut self, _id: &'ast Id) {}
+
+    fn visit_literal(&mut self, _lit: &'ast Literal) {}
+}
+
+pub fn walk_file<'ast, V: Visit<'ast>>(visitor: &mut V, file: &'ast File) {
+    for item in &file.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<'ast, V: Visit<'ast>>(visitor: &mut V, item: &'ast Item) {
+    for attr in &item.attrs {
+        visitor.visit_attribute(attr);
+    }
+
+    match &item.kind {
+        ItemKind::Record(record) => visitor.visit_record(record),
+        ItemKind::Def(def) => visitor.visit_definition(def),
+    }
+}
+
+pub fn walk_attribute<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    attr: &'ast Attribute,
+) {
+    visitor.visit_id(&attr.name);
+
+    for arg in attr.args.iter().flatten() {
+        visitor.visit_id(arg);
+    }
+}
+
+pub fn walk_record<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    record: &'ast Record,
+) {
+    visitor.visit_id(&record.name);
+
+    for param in &record.params {
+        visitor.visit_id(param);
+    }
+
+    for field in &record.fields {
+        visitor.visit_field(field);
+    }
+}
+
+pub fn walk_field<'ast, V: Visit<'ast>>(visitor: &mut V, field: &'ast Field) {
+    visitor.visit_id(&field.name);
+    visitor.visit_type(&field.ty);
+}
+
+pub fn walk_type<'ast, V: Visit<'ast>>(visitor: &mut V, ty: &'ast Type) {
+    visitor.visit_id(&ty.name);
+
+    for arg in &ty.args {
+        visitor.visit_expression(arg);
+    }
+}
+
+pub fn walk_definition<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    def: &'ast Definition,
+) {
+    visitor.visit_id(&def.name);
+
+    for generic in &def.generics {
+        visitor.visit_id(generic);
+    }
+
+    visitor.visit_signature(&def.sig);
+
+    for expr in [&def.requires, &def.implements].into_iter().flatten() {
+        visitor.visit_expression(expr);
+    }
+
+    if let Some(body) = &def.body {
+        visitor.visit_block(body);
+    }
+}
+
+pub fn walk_signature<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    sig: &'ast Signature,
+) {
+    for param in &sig.inputs {
+        visitor.visit_parameter(param);
+    }
+
+    visitor.visit_type(&sig.output);
+}
+
+pub fn walk_parameter<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    param: &'ast Parameter,
+) {
+    visitor.visit_id(&param.name);
+    visitor.visit_type(&param.ty);
+}
+
+pub fn walk_block<'ast, V: Visit<'ast>>(visitor: &mut V, block: &'ast Block) {
+    for stmt in &block.0 {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    stmt: &'ast Statement,
+) {
+    with_sufficient_stack(|| match &stmt.kind {
+        StmtKind::Assign(assign) => visitor.visit_assignment(assign),
+        StmtKind::Return(expr) => visitor.visit_expression(expr),
+        StmtKind::Unsafe(block) => visitor.visit_block(block),
+    })
+}
+
+pub fn walk_assignment<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    assign: &'ast Assignment,
+) {
+    visitor.visit_id(&assign.lhs);
+    visitor.visit_expression(&assign.rhs);
+}
+
+pub fn walk_expression<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    expr: &'ast Expression,
+) {
+    with_sufficient_stack(|| match &expr.kind {
+        ExprKind::Id(id) => visitor.visit_id(id),
+        ExprKind::Lit(lit) => visitor.visit_literal(lit),
+        ExprKind::Field(base, name) => {
+            visitor.visit_expression(base);
+            visitor.visit_id(name);
+        }
+        ExprKind::Unary(_, operand) => visitor.visit_expression(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        ExprKind::Call(call) => visitor.visit_call(call),
+        ExprKind::Record(cons) => visitor.visit_constructor(cons),
+    })
+}
+
+pub fn walk_call<'ast, V: Visit<'ast>>(visitor: &mut V, call: &'ast Call) {
+    visitor.visit_id(&call.name);
+
+    for expr in call.generics.iter().chain(&call.args) {
+        visitor.visit_expression(expr);
+    }
+}
+
+pub fn walk_constructor<'ast, V: Visit<'ast>>(
+    visitor: &mut V,
+    cons: &'ast Constructor,
+) {
+    visitor.visit_id(&cons.name);
+
+    for expr in &cons.generics {
+        visitor.visit_expression(expr);
+    }
+
+    for field in &cons.fields {
+        visitor.visit_assignment(field);
+    }
+}
+
+/// A pass over a syntax tree that may change it in place. The walks read the
+/// children of a node after the visitor's method has been called, so a
+/// method that replaces its node before walking it visits the new children.
+pub trait VisitMut: Sized {
+    fn visit_file(&mut self, file: &mut File) {
+        walk_file_mut(self, file);
+    }
+
+    fn visit_item(&mut self, item: &mut Item) {
+        walk_item_mut(self, item);
+    }
+
+    fn visit_attribute(&mut self, attr: &mut Attribute) {
+        walk_attribute_mut(self, attr);
+    }
+
+    fn visit_record(&mut self, record: &mut Record) {
+        walk_record_mut(self, record);
+    }
+
+    fn visit_field(&mut self, field: &mut Field) {
+        walk_field_mut(self, field);
+    }
+
+    fn visit_type(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty);
+    }
+
+    fn visit_definition(&mut self, def: &mut Definition) {
+        walk_definition_mut(self, def);
+    }
+
+    fn visit_signature(&mut self, sig: &mut Signature) {
+        walk_signature_mut(self, sig);
+    }
+
+    fn visit_parameter(&mut self, param: &mut Parameter) {
+        walk_parameter_mut(self, param);
+    }
+
+    fn visit_block(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_assignment(&mut self, assign: &mut Assignment) {
+        walk_assignment_mut(self, assign);
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression) {
+        walk_expression_mut(self, expr);
+    }
+
+    fn visit_call(&mut self, call: &mut Call) {
+        walk_call_mut(self, call);
+    }
+
+    fn visit_constructor(&mut self, cons: &mut Constructor) {
+        walk_constructor_mut(self, cons);
+    }
+
+    fn visit_id(&mut self, _id: &mut Id) {}
+
+    fn visit_literal(&mut self, _lit: &mut Literal) {}
+}
+
+pub fn walk_file_mut<V: VisitMut>(visitor: &mut V, file: &mut File) {
+    for item in &mut file.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitMut>(visitor: &mut V, item: &mut Item) {
+    for attr in &mut item.attrs {
+        visitor.visit_attribute(attr);
+    }
+
+    match &mut item.kind {
+        ItemKind::Record(record) => visitor.visit_record(record),
+        ItemKind::Def(def) => visitor.visit_definition(def),
+    }
+}
+
+pub fn walk_attribute_mut<V: VisitMut>(visitor: &mut V, attr: &mut Attribute) {
+    visitor.visit_id(&mut attr.name);
+
+    for arg in attr.args.iter_mut().flatten() {
+        visitor.visit_id(arg);
+    }
+}
+
+pub fn walk_record_mut<V: VisitMut>(visitor: &mut V, record: &mut Record) {
+    visitor.visit_id(&mut record.name);
+
+    for param in &mut record.params {
+        visitor.visit_id(param);
+    }
+
+    for field in &mut record.fields {
+        visitor.visit_field(field);
+    }
+}
+
+pub fn walk_field_mut<V: VisitMut>(visitor: &mut V, field: &mut Field) {
+    visitor.visit_id(&mut field.name);
+    visitor.visit_type(&mut field.ty);
+}
+
+pub fn walk_type_mut<V: VisitMut>(visitor: &mut V, ty: &mut Type) {
+    visitor.visit_id(&mut ty.name);
+
+    for arg in &mut ty.args {
+        visitor.visit_expression(arg);
+    }
+}
+
+pub fn walk_definition_mut<V: VisitMut>(visitor: &mut V, def: &mut Definition) {
+    visitor.visit_id(&mut def.name);
+
+    for generic in &mut def.generics {
+        visitor.visit_id(generic);
+    }
+
+    visitor.visit_signature(&mut def.sig);
+
+    for expr in [&mut def.requires, &mut def.implements]
+        .into_iter()
+        .flatten()
+    {
+        visitor.visit_expression(expr);
+    }
+
+    if let Some(body) = &mut def.body {
+        visitor.visit_block(body);
+    }
+}
+
+pub fn walk_signature_mut<V: VisitMut>(visitor: &mut V, sig: &mut Signature) {
+    for param in &mut sig.inputs {
+        visitor.visit_parameter(param);
+    }
+
+    visitor.visit_type(&mut sig.output);
+}
+
+pub fn walk_parameter_mut<V: VisitMut>(visitor: &mut V, param: &mut Parameter) {
+    visitor.visit_id(&mut param.name);
+    visitor.visit_type(&mut param.ty);
+}
+
+pub fn walk_block_mut<V: VisitMut>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.0 {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitMut>(visitor: &mut V, stmt: &mut Statement) {
+    with_sufficient_stack(|| match &mut stmt.kind {
+        StmtKind::Assign(assign) => visitor.visit_assignment(assign),
+        StmtKind::Return(expr) => visitor.visit_expression(expr),
+        StmtKind::Unsafe(block) => visitor.visit_block(block),
+    })
+}
+
+pub fn walk_assignment_mut<V: VisitMut>(
+    visitor: &mut V,
+    assign: &mut Assignment,
+) {
+    visitor.visit_id(&mut assign.lhs);
+    visitor.visit_expression(&mut assign.rhs);
+}
+
+pub fn walk_expression_mut<V: VisitMut>(
+    visitor: &mut V,
+    expr: &mut Expression,
+) {
+    with_sufficient_stack(|| match &mut expr.kind {
+        ExprKind::Id(id) => visitor.visit_id(id),
+        ExprKind::Lit(lit) => visitor.visit_literal(lit),
+        ExprKind::Field(base, name) => {
+            visitor.visit_expression(base);
+            visitor.visit_id(name);
+        }
+        ExprKind::Unary(_, operand) => visitor.visit_expression(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        ExprKind::Call(call) => visitor.visit_call(call),
+        ExprKind::Record(cons) => visitor.visit_constructor(cons),
+    })
+}
+
+pub fn walk_call_mut<V: VisitMut>(visitor: &mut V, call: &mut Call) {
+    visitor.visit_id(&mut call.name);
+
+    for expr in call.generics.iter_mut().chain(&mut call.args) {
+        visitor.visit_expression(expr);
+    }
+}
+
+pub fn walk_constructor_mut<V: VisitMut>(
+    visitor: &mut V,
+    cons: &mut Constructor,
+) {
+    visitor.visit_id(&mut cons.name);
+
+    for expr in &mut cons.generics {
+        visitor.visit_expression(expr);
+    }
+
+    for field in &mut cons.fields {
+        visitor.visit_assignment(field);
+    }
+}
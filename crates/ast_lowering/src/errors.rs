@@ -1,22 +1,27 @@
+use std::ops::Range;
+
 use adpl_ast as ast;
-use adpl_util::Diagnostic;
+use adpl_util::{Applicability, Diagnostic, codes, lints};
 
 pub struct ReusedParameter<'a> {
+    pub symbols: &'a ast::Symbols,
     pub second: &'a ast::Id,
 }
 
 impl From<ReusedParameter<'_>> for Diagnostic {
     fn from(value: ReusedParameter) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0002)
             .with_message(format!(
                 "reuse of parameter name `{}`",
-                value.second.symbol,
+                &value.symbols[value.second.symbol],
             ))
             .with_primary(value.second.span, "parameter name already used")
     }
 }
 
 pub struct ShadowedGeneric<'a> {
+    pub symbols: &'a ast::Symbols,
     pub first: &'a ast::Id,
     pub second: &'a ast::Id,
 }
@@ -24,9 +29,10 @@ pub struct ShadowedGeneric<'a> {
 impl From<ShadowedGeneric<'_>> for Diagnostic {
     fn from(value: ShadowedGeneric) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0003)
             .with_message(format!(
                 "parameter `{}` shadows a generic parameter",
-                value.second.symbol,
+                &value.symbols[value.second.symbol],
             ))
             .with_secondary(value.first.span, "name first used here")
             .with_primary(value.second.span, "shadows a generic parameter")
@@ -34,6 +40,7 @@ impl From<ShadowedGeneric<'_>> for Diagnostic {
 }
 
 pub struct RedeclaredField<'a> {
+    pub symbols: &'a ast::Symbols,
     pub first: &'a ast::Id,
     pub second: &'a ast::Id,
 }
@@ -41,9 +48,10 @@ pub struct RedeclaredField<'a> {
 impl From<RedeclaredField<'_>> for Diagnostic {
     fn from(value: RedeclaredField) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0004)
             .with_message(format!(
                 "reuse of field name `{}`",
-                value.first.symbol,
+                &value.symbols[value.first.symbol],
             ))
             .with_secondary(value.first.span, "field first declared here")
             .with_primary(value.second.span, "field name already used")
@@ -51,6 +59,7 @@ impl From<RedeclaredField<'_>> for Diagnostic {
 }
 
 pub struct RedefinedName<'a> {
+    pub symbols: &'a ast::Symbols,
     pub first: &'a ast::Id,
     pub second: &'a ast::Id,
 }
@@ -58,9 +67,10 @@ pub struct RedefinedName<'a> {
 impl From<RedefinedName<'_>> for Diagnostic {
     fn from(value: RedefinedName) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0005)
             .with_message(format!(
                 "redefinition of name `{}`",
-                value.first.symbol,
+                &value.symbols[value.first.symbol],
             ))
             .with_secondary(value.first.span, "name first defined here")
             .with_primary(value.second.span, "name already defined")
@@ -68,30 +78,62 @@ impl From<RedefinedName<'_>> for Diagnostic {
 }
 
 pub struct UndefinedName<'a> {
+    pub symbols: &'a ast::Symbols,
     pub name: &'a ast::Id,
+    pub similar: Option<&'a str>,
 }
 
 impl From<UndefinedName<'_>> for Diagnostic {
     fn from(value: UndefinedName) -> Self {
-        Diagnostic::error()
-            .with_message(format!("undefined name `{}`", value.name.symbol))
-            .with_primary(value.name.span, "undefined name")
+        let diagnostic = Diagnostic::error()
+            .with_code(codes::E0006)
+            .with_message(format!(
+                "undefined name `{}`",
+                &value.symbols[value.name.symbol]
+            ))
+            .with_primary(value.name.span, "undefined name");
+
+        match value.similar {
+            Some(similar) => diagnostic.with_suggestion(
+                value.name.span,
+                similar,
+                format!("a variable with a similar name exists: `{}`", similar),
+                Applicability::MaybeIncorrect,
+            ),
+            None => diagnostic,
+        }
     }
 }
 
 pub struct KindNotFound<'a> {
+    pub symbols: &'a ast::Symbols,
     pub name: &'a ast::Id,
     pub kind: &'a str,
+    pub similar: Option<&'a str>,
 }
 
 impl From<KindNotFound<'_>> for Diagnostic {
     fn from(value: KindNotFound) -> Self {
-        Diagnostic::error()
+        let diagnostic = Diagnostic::error()
+            .with_code(codes::E0007)
             .with_message(format!(
                 "no {} with name `{}`",
-                value.kind, value.name.symbol,
+                value.kind, &value.symbols[value.name.symbol],
             ))
-            .with_primary(value.name.span, format!("{} not found", value.kind))
+            .with_primary(value.name.span, format!("{} not found", value.kind));
+
+        match value.similar {
+            Some(similar) => diagnostic.with_suggestion(
+                value.name.span,
+                similar,
+                format!(
+                    "a {} with a similar name exists: `{}`",
+                    value.kind, similar,
+                ),
+                Applicability::MaybeIncorrect,
+            ),
+            None => diagnostic,
+        }
     }
 }
 
@@ -103,6 +145,7 @@ pub struct UnexpectedItem<'a> {
 impl From<UnexpectedItem<'_>> for Diagnostic {
     fn from(value: UnexpectedItem) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0008)
             .with_message(format!("expected value, found {}", value.kind))
             .with_primary(value.name.span, "expected value")
     }
@@ -118,6 +161,7 @@ pub struct UnexpectedKind<'a> {
 impl From<UnexpectedKind<'_>> for Diagnostic {
     fn from(value: UnexpectedKind) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0009)
             .with_message(format!(
                 "expected {}, found {}",
                 value.expected, value.found,
@@ -126,10 +170,49 @@ impl From<UnexpectedKind<'_>> for Diagnostic {
     }
 }
 
+pub struct CalledStruct<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub name: &'a ast::Id,
+    pub fields: Vec<&'a str>,
+    pub generics: usize,
+    pub span: ast::Span,
+}
+
+impl From<CalledStruct<'_>> for Diagnostic {
+    fn from(value: CalledStruct) -> Self {
+        let mut replacement = value.symbols[value.name.symbol].to_string();
+
+        if value.generics > 0 {
+            let generics = vec!["_"; value.generics];
+            replacement.push_str(&format!("[{}]", generics.join(", ")));
+        }
+
+        let fields = value
+            .fields
+            .iter()
+            .map(|field| format!("{} = _", field))
+            .collect::<Vec<_>>();
+
+        replacement.push_str(&format!(" {{ {} }}", fields.join(", ")));
+
+        Diagnostic::error()
+            .with_code(codes::E0009)
+            .with_message("expected function, found struct")
+            .with_primary(value.name.span, "struct not callable")
+            .with_suggestion(
+                value.span,
+                replacement,
+                "use a struct initializer instead",
+                Applicability::HasPlaceholders,
+            )
+    }
+}
+
 pub struct ArityMismatch<'a> {
+    pub symbols: &'a ast::Symbols,
     pub callee: &'a ast::Id,
+    pub args: &'a [ast::Expression],
     pub expected: usize,
-    pub found: usize,
     pub what: &'a str,
 }
 
@@ -142,58 +225,251 @@ impl From<ArityMismatch<'_>> for Diagnostic {
             if value.expected == 1 { "" } else { "s" },
         );
 
-        Diagnostic::error()
+        let diagnostic = Diagnostic::error()
+            .with_code(codes::E0010)
             .with_message(format!(
                 "`{}` takes {}, found {}",
-                value.callee.symbol, expected, value.found,
+                &value.symbols[value.callee.symbol],
+                expected,
+                value.args.len(),
             ))
-            .with_primary(value.callee.span, format!("expected {}", expected))
+            .with_primary(value.callee.span, format!("expected {}", expected));
+
+        let Some(last) = value.args.last() else {
+            return diagnostic;
+        };
+
+        if value.args.len() <= value.expected {
+            return diagnostic;
+        }
+
+        // Removing everything after the last expected argument also removes
+        // the separating comma, and a trailing comma after the last argument
+        // stays valid.
+        let start = match value.expected {
+            0 => Range::from(value.args[0].span).start,
+            n => Range::from(value.args[n - 1].span).end,
+        };
+        let span =
+            ast::Span::new(last.span.file(), start, Range::from(last.span).end);
+        let extra = value.args.len() - value.expected;
+
+        diagnostic.with_suggestion(
+            span,
+            "",
+            format!(
+                "remove the extra {}{}",
+                value.what,
+                if extra == 1 { "" } else { "s" },
+            ),
+            Applicability::MachineApplicable,
+        )
     }
 }
 
 pub struct UnexpectedField<'a> {
+    pub symbols: &'a ast::Symbols,
     pub ty: &'a ast::Id,
     pub field: &'a ast::Id,
+    pub similar: Option<&'a str>,
 }
 
 impl From<UnexpectedField<'_>> for Diagnostic {
     fn from(value: UnexpectedField) -> Self {
-        Diagnostic::error()
+        let diagnostic = Diagnostic::error()
+            .with_code(codes::E0011)
             .with_message(format!(
                 "`{}` is not a field of `{}`",
-                value.field.symbol, value.ty.symbol,
+                &value.symbols[value.field.symbol],
+                &value.symbols[value.ty.symbol],
             ))
-            .with_primary(value.field.span, "no such field")
+            .with_primary(value.field.span, "no such field");
+
+        match value.similar {
+            Some(similar) => diagnostic.with_suggestion(
+                value.field.span,
+                similar,
+                format!("a field with a similar name exists: `{}`", similar),
+                Applicability::MaybeIncorrect,
+            ),
+            None => diagnostic,
+        }
     }
 }
 
 pub struct DuplicateField<'a> {
+    pub symbols: &'a ast::Symbols,
     pub second: &'a ast::Id,
 }
 
 impl From<DuplicateField<'_>> for Diagnostic {
     fn from(value: DuplicateField) -> Self {
         Diagnostic::error()
+            .with_code(codes::E0012)
             .with_message(format!(
                 "field `{}` specified more than once",
-                value.second.symbol,
+                &value.symbols[value.second.symbol],
             ))
             .with_primary(value.second.span, "field already initialized")
     }
 }
 
 pub struct MissingField<'a> {
+    pub symbols: &'a ast::Symbols,
     pub ty: &'a ast::Id,
     pub field: &'a ast::Id,
+    pub inits: &'a [ast::Assignment],
+    pub span: ast::Span,
 }
 
 impl From<MissingField<'_>> for Diagnostic {
     fn from(value: MissingField) -> Self {
+        let field = &value.symbols[value.field.symbol];
+
+        // Insert after the last initializer, or just before the closing brace
+        // of an empty initializer.
+        let (at, replacement) = match value.inits.last() {
+            Some(init) => {
+                (Range::from(init.rhs.span).end, format!(", {} = _", field))
+            }
+            None => {
+                (Range::from(value.span).end - 1, format!("{} = _ ", field))
+            }
+        };
+
         Diagnostic::error()
+            .with_code(codes::E0013)
             .with_message(format!(
                 "missing field `{}` in initializer for `{}`",
-                value.field.symbol, value.ty.symbol,
+                field, &value.symbols[value.ty.symbol],
             ))
             .with_primary(value.ty.span, "incomplete initializer")
+            .with_suggestion(
+                ast::Span::new(value.span.file(), at, at),
+                replacement,
+                format!("initialize the missing field `{}`", field),
+                Applicability::HasPlaceholders,
+            )
+    }
+}
+
+pub struct NotConst {
+    pub span: ast::Span,
+}
+
+impl From<NotConst> for Diagnostic {
+    fn from(value: NotConst) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0017)
+            .with_message("expected a compile-time constant")
+            .with_primary(value.span, "not a constant")
+    }
+}
+
+pub struct UnknownAttribute<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub attr: &'a ast::Attribute,
+}
+
+impl From<UnknownAttribute<'_>> for Diagnostic {
+    fn from(value: UnknownAttribute) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0014)
+            .with_message(format!(
+                "unknown attribute `{}`",
+                &value.symbols[value.attr.name.symbol],
+            ))
+            .with_primary(value.attr.span, "attribute not recognized")
+    }
+}
+
+pub struct UnexpectedAttribute<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub attr: &'a ast::Attribute,
+    pub item: &'a str,
+}
+
+impl From<UnexpectedAttribute<'_>> for Diagnostic {
+    fn from(value: UnexpectedAttribute) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0015)
+            .with_message(format!(
+                "attribute `{}` cannot be applied to a {}",
+                &value.symbols[value.attr.name.symbol], value.item,
+            ))
+            .with_primary(value.attr.span, "not allowed here")
+    }
+}
+
+pub struct ConflictingAttributes<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub first: &'a ast::Attribute,
+    pub second: &'a ast::Attribute,
+}
+
+impl From<ConflictingAttributes<'_>> for Diagnostic {
+    fn from(value: ConflictingAttributes) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0016)
+            .with_message(format!(
+                "attribute `{}` conflicts with `{}`",
+                &value.symbols[value.second.name.symbol],
+                &value.symbols[value.first.name.symbol],
+            ))
+            .with_secondary(value.first.span, "first attribute here")
+            .with_primary(value.second.span, "conflicting attribute")
+    }
+}
+
+pub struct InlineRequirement<'a> {
+    pub attr: &'a ast::Attribute,
+    pub requires: ast::Span,
+}
+
+impl From<InlineRequirement<'_>> for Diagnostic {
+    fn from(value: InlineRequirement) -> Self {
+        Diagnostic::warning()
+            .with_message(
+                "`#[inline]` has no effect on a definition with a `where` clause",
+            )
+            .with_primary(value.attr.span, "ignored")
+            .with_secondary(value.requires, "checked on entry to the definition")
+            .with_note("inlining a call would drop the check of the requirement")
+    }
+}
+
+pub struct MalformedAttribute<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub attr: &'a ast::Attribute,
+    pub expected: &'a str,
+}
+
+impl From<MalformedAttribute<'_>> for Diagnostic {
+    fn from(value: MalformedAttribute) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0025)
+            .with_message(format!(
+                "malformed `{}` attribute",
+                &value.symbols[value.attr.name.symbol],
+            ))
+            .with_primary(value.attr.span, "malformed attribute")
+            .with_note(format!("expected `{}`", value.expected))
+    }
+}
+
+pub struct UnknownLint<'a> {
+    pub symbols: &'a ast::Symbols,
+    pub name: &'a ast::Id,
+}
+
+impl From<UnknownLint<'_>> for Diagnostic {
+    fn from(value: UnknownLint) -> Self {
+        Diagnostic::warning()
+            .with_lint(&lints::UNKNOWN_LINTS)
+            .with_message(format!(
+                "unknown lint `{}`",
+                &value.symbols[value.name.symbol]
+            ))
+            .with_primary(value.name.span, "lint not recognized")
     }
 }
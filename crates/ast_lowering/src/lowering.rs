@@ -3,18 +3,21 @@ use std::iter;
 
 use adpl_ast as ast;
 use adpl_hir as hir;
-use adpl_util::{Reporter, with_sufficient_stack};
+use adpl_util::lints::{self, LintScope};
+use adpl_util::{Reporter, find_best_match, ice, with_sufficient_stack};
 
 use crate::errors;
 
 pub fn lower_ast(
     file: &ast::File,
+    symbols: &ast::Symbols,
     reporter: &mut Reporter,
 ) -> Option<hir::Context> {
-    let mut ctx = hir::Context::new();
+    let mut ctx = hir::Context::new(symbols.clone());
 
     let mut lowering = LoweringContext {
         ctx: &mut ctx,
+        symbols,
         reporter,
         globals: HashMap::new(),
         scopes: Vec::new(),
@@ -32,6 +35,7 @@ type Result<T> = std::result::Result<T, LoweringError>;
 
 struct LoweringContext<'a, 'src> {
     ctx: &'a mut hir::Context,
+    symbols: &'a ast::Symbols,
     reporter: &'a mut Reporter<'src>,
     globals: HashMap<ast::Symbol, Global>,
     scopes: Vec<HashMap<ast::Symbol, hir::Index<hir::Local>>>,
@@ -40,12 +44,32 @@ struct LoweringContext<'a, 'src> {
 impl LoweringContext<'_, '_> {
     fn lower_file(&mut self, file: &ast::File) -> Result<()> {
         for item in &file.items {
+            let attrs = self.lower_lint_levels(item)?;
+
             match &item.kind {
                 ast::ItemKind::Record(record) => {
-                    self.lower_record(record)?;
+                    if let Some(attr) = attrs.first() {
+                        self.reporter.emit(errors::UnexpectedAttribute {
+                            symbols: self.symbols,
+                            attr,
+                            item: "struct",
+                        });
+
+                        return Err(LoweringError);
+                    }
+
+                    ice::item(
+                        &self.symbols[record.name.symbol],
+                        record.name.span,
+                        || self.lower_record(record, item.span),
+                    )?;
                 }
                 ast::ItemKind::Def(def) => {
-                    self.lower_definition(def)?;
+                    ice::item(
+                        &self.symbols[def.name.symbol],
+                        def.name.span,
+                        || self.lower_definition(def, &attrs, item.span),
+                    )?;
                 }
             }
         }
@@ -56,6 +80,7 @@ impl LoweringContext<'_, '_> {
     fn lower_record(
         &mut self,
         record: &ast::Record,
+        span: ast::Span,
     ) -> Result<hir::Index<hir::Record>> {
         let params =
             self.ctx.locals.extend(record.params.iter().enumerate().map(
@@ -69,8 +94,10 @@ impl LoweringContext<'_, '_> {
 
         for (param, local) in iter::zip(&record.params, params) {
             if scope.insert(param.symbol, local).is_some() {
-                self.reporter
-                    .emit(errors::ReusedParameter { second: param });
+                self.reporter.emit(errors::ReusedParameter {
+                    symbols: self.symbols,
+                    second: param,
+                });
 
                 return Err(LoweringError);
             }
@@ -85,6 +112,7 @@ impl LoweringContext<'_, '_> {
             for prev in &record.fields[..i] {
                 if prev.name.symbol == field.name.symbol {
                     self.reporter.emit(errors::RedeclaredField {
+                        symbols: self.symbols,
                         first: &prev.name,
                         second: &field.name,
                     });
@@ -101,6 +129,7 @@ impl LoweringContext<'_, '_> {
             name: record.name,
             params,
             fields: hir::IndexRange { start, end },
+            span,
         });
 
         self.add_global(&record.name, Global::Record(index))?;
@@ -121,64 +150,108 @@ impl LoweringContext<'_, '_> {
         }))
     }
 
-    fn lower_type(&mut self, ty: &ast::Type) -> Result<hir::Index<hir::Type>> {
-        let decl = self
-            .globals
-            .get(&ty.name.symbol)
-            .ok_or_else(|| {
-                self.reporter.emit(errors::KindNotFound {
+    fn lower_type(&mut self, ty: &ast::Type) -> Result<hir::TypeRef> {
+        let kind = match self.globals.get(&ty.name.symbol) {
+            Some(Global::Record(record)) => hir::TypeKind::Record(*record),
+            Some(Global::Def(_)) => {
+                self.reporter.emit(errors::UnexpectedKind {
                     name: &ty.name,
-                    kind: "type",
+                    expected: "type",
+                    found: "function",
+                    label: "not a type",
                 });
 
-                LoweringError
-            })
-            .and_then(|&name| match name {
-                Global::Record(record) => Ok(record),
-                Global::Def(_) => {
-                    self.reporter.emit(errors::UnexpectedKind {
+                return Err(LoweringError);
+            }
+            None => match &self.symbols[ty.name.symbol] {
+                "uint" => hir::TypeKind::UInt,
+                _ => {
+                    let records = self
+                        .globals
+                        .iter()
+                        .filter(|(_, global)| {
+                            matches!(global, Global::Record(_))
+                        })
+                        .map(|(&symbol, _)| &self.symbols[symbol]);
+
+                    self.reporter.emit(errors::KindNotFound {
+                        symbols: self.symbols,
                         name: &ty.name,
-                        expected: "type",
-                        found: "function",
-                        label: "not a type",
+                        kind: "type",
+                        similar: find_best_match(
+                            records.chain(["uint"]),
+                            &self.symbols[ty.name.symbol],
+                        ),
                     });
 
-                    Err(LoweringError)
+                    return Err(LoweringError);
                 }
-            })?;
+            },
+        };
 
-        let args = self.ctx.lists.extend_zeroed(ty.args.len());
+        // The argument expressions are only needed until they are folded.
+        let snapshot = self.ctx.snapshot();
+        let mut args = Vec::with_capacity(ty.args.len());
 
-        for (i, arg) in ty.args.iter().enumerate() {
-            self.ctx[args][i] = self.lower_expression(arg)?;
+        for arg in &ty.args {
+            let expr = self.lower_expression(arg)?;
+            let arg = self.ctx.normalize_const(expr).map_err(|error| {
+                self.reporter.emit(errors::NotConst { span: error.span });
+
+                LoweringError
+            })?;
+
+            args.push(arg);
         }
 
-        let declared_param_count = self.ctx[decl].params.len();
+        self.ctx.rollback_to(snapshot);
+
+        let declared_param_count = match kind {
+            hir::TypeKind::UInt => 1,
+            hir::TypeKind::Record(record) => self.ctx[record].params.len(),
+        };
         let supplied_param_count = ty.args.len();
 
         if declared_param_count != supplied_param_count {
             self.reporter.emit(errors::ArityMismatch {
+                symbols: self.symbols,
                 callee: &ty.name,
+                args: &ty.args,
                 expected: declared_param_count,
-                found: supplied_param_count,
                 what: "generic argument",
             });
 
             return Err(LoweringError);
         }
 
-        Ok(self.ctx.add(hir::Type {
-            name: ty.name,
-            decl,
-            args,
+        let index = self.ctx.types.intern(hir::Type {
+            kind,
+            args: args.into(),
+        });
+
+        Ok(hir::TypeRef {
+            ty: index,
             span: ty.span,
-        }))
+        })
     }
 
     fn lower_definition(
         &mut self,
         def: &ast::Definition,
+        attrs: &[&ast::Attribute],
+        span: ast::Span,
     ) -> Result<hir::Index<hir::Definition>> {
+        let (inline, attr) = self.lower_inline(attrs)?;
+
+        if let (hir::Inline::Always, Some(attr), Some(requires)) =
+            (inline, attr, &def.requires)
+        {
+            self.reporter.emit(errors::InlineRequirement {
+                attr,
+                requires: requires.span,
+            });
+        }
+
         let generics =
             self.ctx.locals.extend(def.generics.iter().enumerate().map(
                 |(i, param)| hir::Local {
@@ -202,8 +275,10 @@ impl LoweringContext<'_, '_> {
 
         for (param, local) in iter::zip(&def.generics, generics) {
             if scope.insert(param.symbol, local).is_some() {
-                self.reporter
-                    .emit(errors::ReusedParameter { second: param });
+                self.reporter.emit(errors::ReusedParameter {
+                    symbols: self.symbols,
+                    second: param,
+                });
 
                 return Err(LoweringError);
             }
@@ -227,12 +302,14 @@ impl LoweringContext<'_, '_> {
                 match prev.kind {
                     hir::LocalKind::GenericParam(_) => {
                         self.reporter.emit(errors::ShadowedGeneric {
+                            symbols: self.symbols,
                             first: &prev.name,
                             second: &param.name,
                         });
                     }
                     hir::LocalKind::Param(_) => {
                         self.reporter.emit(errors::ReusedParameter {
+                            symbols: self.symbols,
                             second: &param.name,
                         });
                     }
@@ -275,6 +352,7 @@ impl LoweringContext<'_, '_> {
 
         let index = self.ctx.add(hir::Definition {
             safety: def.safety,
+            inline,
             name: def.name,
             generics,
             requires,
@@ -282,6 +360,7 @@ impl LoweringContext<'_, '_> {
             inputs: hir::IndexRange { start, end },
             output,
             body,
+            span,
         });
 
         self.add_global(&def.name, Global::Def(index))?;
@@ -289,6 +368,111 @@ impl LoweringContext<'_, '_> {
         Ok(index)
     }
 
+    /// Applies the lint level attributes of `item` and returns the others.
+    fn lower_lint_levels<'item>(
+        &mut self,
+        item: &'item ast::Item,
+    ) -> Result<Vec<&'item ast::Attribute>> {
+        let mut attrs = Vec::new();
+        let mut unknown = Vec::new();
+
+        for attr in &item.attrs {
+            let Ok(level) =
+                self.symbols[attr.name.symbol].parse::<lints::Level>()
+            else {
+                attrs.push(attr);
+                continue;
+            };
+
+            let Some(args) = attr.args.as_ref().filter(|args| !args.is_empty())
+            else {
+                self.reporter.emit(errors::MalformedAttribute {
+                    symbols: self.symbols,
+                    attr,
+                    expected: &format!("#[{}(lint, ...)]", level),
+                });
+
+                return Err(LoweringError);
+            };
+
+            for name in args {
+                match lints::find(&self.symbols[name.symbol]) {
+                    Some(lint) => self.reporter.push_lint_scope(LintScope {
+                        span: item.span,
+                        lint,
+                        level,
+                        attr: attr.span,
+                    }),
+                    None => unknown.push(name),
+                }
+            }
+        }
+
+        // Reported once every level of the item is known, so that
+        // `#[allow(unknown_lints)]` applies wherever it is written.
+        for name in unknown {
+            self.reporter.emit(errors::UnknownLint {
+                symbols: self.symbols,
+                name,
+            });
+        }
+
+        Ok(attrs)
+    }
+
+    /// Returns how calls to a definition are inlined and the attribute that
+    /// says so, if any.
+    fn lower_inline<'item>(
+        &mut self,
+        attrs: &[&'item ast::Attribute],
+    ) -> Result<(hir::Inline, Option<&'item ast::Attribute>)> {
+        let mut inline = None;
+
+        for &attr in attrs {
+            let value = match &self.symbols[attr.name.symbol] {
+                "inline" => hir::Inline::Always,
+                "noinline" => hir::Inline::Never,
+                _ => {
+                    self.reporter.emit(errors::UnknownAttribute {
+                        symbols: self.symbols,
+                        attr,
+                    });
+
+                    return Err(LoweringError);
+                }
+            };
+
+            if attr.args.is_some() {
+                self.reporter.emit(errors::MalformedAttribute {
+                    symbols: self.symbols,
+                    attr,
+                    expected: &format!(
+                        "#[{}]",
+                        &self.symbols[attr.name.symbol]
+                    ),
+                });
+
+                return Err(LoweringError);
+            }
+
+            if let Some((first, _)) = inline {
+                self.reporter.emit(errors::ConflictingAttributes {
+                    symbols: self.symbols,
+                    first,
+                    second: attr,
+                });
+
+                return Err(LoweringError);
+            }
+
+            inline = Some((attr, value));
+        }
+
+        Ok(inline.map_or((hir::Inline::Auto, None), |(attr, value)| {
+            (value, Some(attr))
+        }))
+    }
+
     fn lower_parameter(
         &mut self,
         param: &ast::Parameter,
@@ -338,13 +522,28 @@ impl LoweringContext<'_, '_> {
         &mut self,
         block: &ast::Block,
     ) -> Result<hir::List<hir::Statement>> {
-        let list = self.ctx.lists.extend_zeroed(block.0.len());
+        let list = self.ctx.lists.start_list();
+
+        for stmt in &block.0 {
+            let stmt = self.lower_statement(stmt)?;
+            self.ctx.lists.push(&list, stmt);
+        }
+
+        Ok(self.ctx.lists.finish_list(list))
+    }
+
+    fn lower_expressions(
+        &mut self,
+        exprs: &[ast::Expression],
+    ) -> Result<hir::List<hir::Expression>> {
+        let list = self.ctx.lists.start_list();
 
-        for (i, stmt) in block.0.iter().enumerate() {
-            self.ctx[list][i] = self.lower_statement(stmt)?;
+        for expr in exprs {
+            let expr = self.lower_expression(expr)?;
+            self.ctx.lists.push(&list, expr);
         }
 
-        Ok(list)
+        Ok(self.ctx.lists.finish_list(list))
     }
 
     fn lower_expression(
@@ -361,7 +560,19 @@ impl LoweringContext<'_, '_> {
                                 kind: global.kind(),
                             });
                         } else {
-                            self.reporter.emit(errors::UndefinedName { name });
+                            let similar = find_best_match(
+                                self.scopes
+                                    .iter()
+                                    .flat_map(|scope| scope.keys())
+                                    .map(|&symbol| &self.symbols[symbol]),
+                                &self.symbols[name.symbol],
+                            );
+
+                            self.reporter.emit(errors::UndefinedName {
+                                symbols: self.symbols,
+                                name,
+                                similar,
+                            });
                         }
 
                         return Err(LoweringError);
@@ -384,11 +595,11 @@ impl LoweringContext<'_, '_> {
                     self.lower_expression(rhs)?,
                 ),
                 ast::ExprKind::Call(call) => {
-                    hir::ExprKind::Call(self.lower_call(call)?)
-                }
-                ast::ExprKind::Record(cons) => {
-                    hir::ExprKind::Record(self.lower_constructor(cons)?)
+                    hir::ExprKind::Call(self.lower_call(call, expr.span)?)
                 }
+                ast::ExprKind::Record(cons) => hir::ExprKind::Record(
+                    self.lower_constructor(cons, expr.span)?,
+                ),
             };
 
             Ok(self.ctx.add(hir::Expression {
@@ -398,25 +609,42 @@ impl LoweringContext<'_, '_> {
         })
     }
 
-    fn lower_call(&mut self, call: &ast::Call) -> Result<hir::Call> {
+    fn lower_call(
+        &mut self,
+        call: &ast::Call,
+        span: ast::Span,
+    ) -> Result<hir::Call> {
         let callee = self
             .globals
             .get(&call.name.symbol)
             .ok_or_else(|| {
                 self.reporter.emit(errors::KindNotFound {
+                    symbols: self.symbols,
                     name: &call.name,
                     kind: "function",
+                    similar: similar_global(
+                        self.symbols,
+                        &self.globals,
+                        call.name.symbol,
+                        "function",
+                    ),
                 });
 
                 LoweringError
             })
             .and_then(|&name| match name {
-                Global::Record(_) => {
-                    self.reporter.emit(errors::UnexpectedKind {
+                Global::Record(record) => {
+                    let record = &self.ctx[record];
+
+                    self.reporter.emit(errors::CalledStruct {
+                        symbols: self.symbols,
                         name: &call.name,
-                        expected: "function",
-                        found: "struct",
-                        label: "struct not callable",
+                        fields: self.ctx.fields[record.fields]
+                            .iter()
+                            .map(|field| &self.symbols[field.name.symbol])
+                            .collect(),
+                        generics: record.params.len(),
+                        span,
                     });
 
                     Err(LoweringError)
@@ -424,40 +652,34 @@ impl LoweringContext<'_, '_> {
                 Global::Def(def) => Ok(def),
             })?;
 
-        let generics = self.ctx.lists.extend_zeroed(call.generics.len());
-
-        for (i, expr) in call.generics.iter().enumerate() {
-            self.ctx[generics][i] = self.lower_expression(expr)?;
-        }
+        let generics = self.lower_expressions(&call.generics)?;
 
         let declared_generic_count = self.ctx[callee].generics.len();
         let supplied_generic_count = call.generics.len();
 
         if declared_generic_count != supplied_generic_count {
             self.reporter.emit(errors::ArityMismatch {
+                symbols: self.symbols,
                 callee: &call.name,
+                args: &call.generics,
                 expected: declared_generic_count,
-                found: supplied_generic_count,
                 what: "generic argument",
             });
 
             return Err(LoweringError);
         }
 
-        let args = self.ctx.lists.extend_zeroed(call.args.len());
-
-        for (i, expr) in call.args.iter().enumerate() {
-            self.ctx[args][i] = self.lower_expression(expr)?;
-        }
+        let args = self.lower_expressions(&call.args)?;
 
         let declared_arg_count = self.ctx[callee].inputs.len();
         let supplied_arg_count = call.args.len();
 
         if declared_arg_count != supplied_arg_count {
             self.reporter.emit(errors::ArityMismatch {
+                symbols: self.symbols,
                 callee: &call.name,
+                args: &call.args,
                 expected: declared_arg_count,
-                found: supplied_arg_count,
                 what: "argument",
             });
 
@@ -475,14 +697,22 @@ impl LoweringContext<'_, '_> {
     fn lower_constructor(
         &mut self,
         cons: &ast::Constructor,
+        span: ast::Span,
     ) -> Result<hir::Constructor> {
         let record = self
             .globals
             .get(&cons.name.symbol)
             .ok_or_else(|| {
                 self.reporter.emit(errors::KindNotFound {
+                    symbols: self.symbols,
                     name: &cons.name,
                     kind: "struct",
+                    similar: similar_global(
+                        self.symbols,
+                        &self.globals,
+                        cons.name.symbol,
+                        "struct",
+                    ),
                 });
 
                 LoweringError
@@ -501,20 +731,17 @@ impl LoweringContext<'_, '_> {
                 }
             })?;
 
-        let generics = self.ctx.lists.extend_zeroed(cons.generics.len());
-
-        for (i, expr) in cons.generics.iter().enumerate() {
-            self.ctx[generics][i] = self.lower_expression(expr)?;
-        }
+        let generics = self.lower_expressions(&cons.generics)?;
 
         let declared_param_count = self.ctx[record].params.len();
         let supplied_param_count = cons.generics.len();
 
         if declared_param_count != supplied_param_count {
             self.reporter.emit(errors::ArityMismatch {
+                symbols: self.symbols,
                 callee: &cons.name,
+                args: &cons.generics,
                 expected: declared_param_count,
-                found: supplied_param_count,
                 what: "generic argument",
             });
 
@@ -522,7 +749,7 @@ impl LoweringContext<'_, '_> {
         }
 
         let fields = self.ctx[record].fields;
-        let inits = self.ctx.lists.extend_invalid(fields.len());
+        let mut inits = vec![None; fields.len()];
 
         for init in &cons.fields {
             let i = fields
@@ -531,29 +758,43 @@ impl LoweringContext<'_, '_> {
                     self.ctx[field].name.symbol == init.lhs.symbol
                 })
                 .ok_or_else(|| {
+                    let names = fields.into_iter().map(|field| {
+                        &self.symbols[self.ctx[field].name.symbol]
+                    });
+
                     self.reporter.emit(errors::UnexpectedField {
+                        symbols: self.symbols,
                         ty: &cons.name,
                         field: &init.lhs,
+                        similar: find_best_match(
+                            names,
+                            &self.symbols[init.lhs.symbol],
+                        ),
                     });
 
                     LoweringError
                 })?;
 
-            if self.ctx[inits][i] != hir::Index::INVALID {
-                self.reporter
-                    .emit(errors::DuplicateField { second: &init.lhs });
+            if inits[i].is_some() {
+                self.reporter.emit(errors::DuplicateField {
+                    symbols: self.symbols,
+                    second: &init.lhs,
+                });
 
                 return Err(LoweringError);
             }
 
-            self.ctx[inits][i] = self.lower_expression(&init.rhs)?;
+            inits[i] = Some(self.lower_expression(&init.rhs)?);
         }
 
-        for (field, &init) in iter::zip(fields, &self.ctx[inits]) {
-            if init == hir::Index::INVALID {
+        for (field, init) in iter::zip(fields, &inits) {
+            if init.is_none() {
                 self.reporter.emit(errors::MissingField {
+                    symbols: self.symbols,
                     ty: &cons.name,
                     field: &self.ctx[field].name,
+                    inits: &cons.fields,
+                    span,
                 });
 
                 return Err(LoweringError);
@@ -564,13 +805,14 @@ impl LoweringContext<'_, '_> {
             name: cons.name,
             record,
             generics,
-            inits,
+            inits: self.ctx.lists.extend(inits.into_iter().flatten()),
         })
     }
 
     fn add_global(&mut self, name: &ast::Id, global: Global) -> Result<()> {
         if let Some(prev) = self.globals.insert(name.symbol, global) {
             self.reporter.emit(errors::RedefinedName {
+                symbols: self.symbols,
                 first: prev.name(self.ctx),
                 second: name,
             });
@@ -592,6 +834,21 @@ impl LoweringContext<'_, '_> {
     }
 }
 
+/// Returns the name of the global `kind` closest to `symbol`, if any.
+fn similar_global<'a>(
+    symbols: &'a ast::Symbols,
+    globals: &HashMap<ast::Symbol, Global>,
+    symbol: ast::Symbol,
+    kind: &str,
+) -> Option<&'a str> {
+    let names = globals
+        .iter()
+        .filter(|(_, global)| global.kind() == kind)
+        .map(|(&symbol, _)| &symbols[symbol]);
+
+    find_best_match(names, &symbols[symbol])
+}
+
 #[derive(Clone, Copy)]
 enum Global {
     Record(hir::Index<hir::Record>),
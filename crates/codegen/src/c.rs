@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use adpl_hir as hir;
+use adpl_util::{Reporter, ice, with_sufficient_stack};
+
+use crate::errors;
+
+const PRELUDE: &str = "\
+/* Generated by the ADPL compiler. */
+
+#include <stdint.h>
+
+static inline uint64_t adpl_div(uint64_t a, uint64_t b)
+{
+    return b != 0 ? a / b : UINT64_MAX;
+}
+
+static inline uint64_t adpl_pow(uint64_t base, uint64_t exp)
+{
+    uint64_t acc = 1;
+
+    while (exp != 0) {
+        if (exp & 1) {
+            acc *= base;
+        }
+
+        base *= base;
+        exp >>= 1;
+    }
+
+    return acc;
+}
+
+static inline uint64_t adpl_shl(uint64_t a, uint64_t b)
+{
+    return b < 64 ? a << b : 0;
+}
+
+static inline uint64_t adpl_shr(uint64_t a, uint64_t b)
+{
+    return b < 64 ? a >> b : 0;
+}
+";
+
+const KEYWORDS: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Bool",
+    "_Complex",
+    "_Imaginary",
+    // Not a keyword, but a definition named `main` would clash with the
+    // entry point of the program the output is linked into.
+    "main",
+];
+
+pub fn emit_c(ctx: &hir::Context, reporter: &mut Reporter) -> Option<String> {
+    let mut codegen = CodegenC {
+        ctx,
+        reporter,
+        structs: String::new(),
+        prototypes: String::new(),
+        bodies: String::new(),
+        records: HashMap::new(),
+        instances: HashMap::new(),
+        queue: Vec::new(),
+    };
+
+    codegen.emit_file().ok()?;
+
+    let mut out = String::from(PRELUDE);
+
+    for section in [&codegen.structs, &codegen.prototypes, &codegen.bodies] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+
+    Some(out)
+}
+
+#[derive(Debug)]
+struct CodegenError;
+
+type Result<T> = std::result::Result<T, CodegenError>;
+
+type Instance<T> = (hir::Index<T>, Box<[hir::Const]>);
+
+enum RecordState {
+    InProgress,
+    Done(String),
+}
+
+struct CodegenC<'a, 'src> {
+    ctx: &'a hir::Context,
+    reporter: &'a mut Reporter<'src>,
+    structs: String,
+    prototypes: String,
+    bodies: String,
+    records: HashMap<Instance<hir::Record>, RecordState>,
+    instances: HashMap<Instance<hir::Definition>, String>,
+    queue: Vec<Instance<hir::Definition>>,
+}
+
+impl CodegenC<'_, '_> {
+    fn emit_file(&mut self) -> Result<()> {
+        for (def, decl) in self.ctx.defs.iter() {
+            if decl.generics.is_empty() {
+                self.instance(def, Box::new([]));
+            }
+        }
+
+        let mut next = 0;
+
+        while let Some((def, generics)) = self.queue.get(next).cloned() {
+            let name = &self.ctx[def].name;
+
+            ice::item(&self.ctx.symbols[name.symbol], name.span, || {
+                self.emit_definition(def, &generics)
+            })?;
+            next += 1;
+        }
+
+        Ok(())
+    }
+
+    fn instance(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: Box<[hir::Const]>,
+    ) -> String {
+        if let Some(name) = self.instances.get(&(def, generics.clone())) {
+            return name.clone();
+        }
+
+        let name = mangle(&self.ctx.symbols, &self.ctx[def].name, &generics);
+        self.instances.insert((def, generics.clone()), name.clone());
+        self.queue.push((def, generics));
+
+        name
+    }
+
+    fn emit_definition(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: &[hir::Const],
+    ) -> Result<()> {
+        let decl = &self.ctx[def];
+        let mut cx = hir::TypeCx::new(self.ctx, def, generics);
+
+        let name = self.instances[&(def, generics.into())].clone();
+
+        let output = cx.output_type();
+        let output = self.check(&cx, output)?;
+        let output_span = decl.output.span;
+
+        let mut sig =
+            format!("{} {}(", self.c_type(&cx, &output, output_span)?, name);
+
+        for (i, param) in self.ctx.params[decl.inputs].iter().enumerate() {
+            let ty = cx.resolve_type(param.ty);
+            let ty = self.check(&cx, ty)?;
+
+            if i > 0 {
+                sig.push_str(", ");
+            }
+
+            let ty = self.c_type(&cx, &ty, param.span)?;
+            write!(sig, "{} {}", ty, self.local_name(param.local)).unwrap();
+        }
+
+        if decl.inputs.is_empty() {
+            sig.push_str("void");
+        }
+
+        sig.push(')');
+
+        let Some(body) = decl.body else {
+            writeln!(self.prototypes, "{};", sig).unwrap();
+            return Ok(());
+        };
+
+        writeln!(self.prototypes, "static inline {};", sig).unwrap();
+
+        let mut out = format!("static inline {}\n{{\n", sig);
+
+        if !self.emit_block(&mut cx, &output, body, &mut out)? {
+            self.reporter.emit(errors::MissingReturn {
+                symbols: &self.ctx.symbols,
+                name: &decl.name,
+            });
+
+            return Err(CodegenError);
+        }
+
+        out.push_str("}\n");
+
+        if !self.bodies.is_empty() {
+            self.bodies.push('\n');
+        }
+
+        self.bodies.push_str(&out);
+
+        Ok(())
+    }
+
+    /// Emits the statements of `block` up to the first `return`, and returns
+    /// whether there was one.
+    fn emit_block(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        output: &hir::Ty,
+        block: hir::List<hir::Statement>,
+        out: &mut String,
+    ) -> Result<bool> {
+        for &stmt in &self.ctx[block] {
+            match self.ctx[stmt].kind {
+                hir::StmtKind::Assign(local, expr) => {
+                    let ty = cx.type_of(expr);
+                    let ty = self.check(cx, ty)?;
+                    let span = self.ctx[expr].span;
+                    let value = self.emit_expression(cx, expr)?;
+                    let value = self.coerce(cx, value, &ty, &ty, span)?;
+
+                    writeln!(
+                        out,
+                        "    {} {} = {};",
+                        self.c_type(cx, &ty, span)?,
+                        self.local_name(local),
+                        value,
+                    )
+                    .unwrap();
+                }
+                hir::StmtKind::Return(expr) => {
+                    let ty = cx.type_of(expr);
+                    let ty = self.check(cx, ty)?;
+                    let span = self.ctx[expr].span;
+                    let value = self.emit_expression(cx, expr)?;
+                    let value = self.coerce(cx, value, &ty, output, span)?;
+
+                    writeln!(out, "    return {};", value).unwrap();
+                    return Ok(true);
+                }
+                hir::StmtKind::Unsafe(block) => {
+                    if self.emit_block(cx, output, block, out)? {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn emit_expression(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        index: hir::Index<hir::Expression>,
+    ) -> Result<String> {
+        with_sufficient_stack(|| {
+            let ty = cx.type_of(index);
+            let ty = self.check(cx, ty)?;
+            let expr = &self.ctx[index];
+
+            Ok(match &expr.kind {
+                &hir::ExprKind::Id(local) => match self.ctx[local].kind {
+                    hir::LocalKind::GenericParam(i) => {
+                        literal(value(cx.generics()[i as usize]))
+                    }
+                    _ => widen(&ty, self.local_name(local)),
+                },
+                hir::ExprKind::Lit(lit) => literal(lit.value),
+                &hir::ExprKind::Field(base, name) => {
+                    let base = self.emit_expression(cx, base)?;
+                    widen(
+                        &ty,
+                        format!(
+                            "{}.{}",
+                            base,
+                            sanitize(&self.ctx.symbols, &name)
+                        ),
+                    )
+                }
+                &hir::ExprKind::Unary(op, operand) => {
+                    let operand = self.emit_expression(cx, operand)?;
+
+                    let value = match op.kind {
+                        hir::UnaryKind::Neg => {
+                            format!("(UINT64_C(0) - {})", operand)
+                        }
+                        hir::UnaryKind::Not => format!("~{}", operand),
+                    };
+
+                    masked(value, width(&ty))
+                }
+                &hir::ExprKind::Binary(op, lhs, rhs) => {
+                    let lhs = self.emit_expression(cx, lhs)?;
+                    let rhs = self.emit_expression(cx, rhs)?;
+
+                    let infix = |op| format!("({} {} {})", lhs, op, rhs);
+                    let helper =
+                        |name| format!("adpl_{}({}, {})", name, lhs, rhs);
+                    let compare =
+                        |op| format!("(uint64_t)({} {} {})", lhs, op, rhs);
+
+                    match op.kind {
+                        hir::BinaryKind::Add => masked(infix("+"), width(&ty)),
+                        hir::BinaryKind::Sub => masked(infix("-"), width(&ty)),
+                        hir::BinaryKind::Mul => masked(infix("*"), width(&ty)),
+                        hir::BinaryKind::Div => {
+                            masked(helper("div"), width(&ty))
+                        }
+                        hir::BinaryKind::Pow => {
+                            masked(helper("pow"), width(&ty))
+                        }
+                        hir::BinaryKind::Shl => {
+                            masked(helper("shl"), width(&ty))
+                        }
+                        hir::BinaryKind::Shr => helper("shr"),
+                        hir::BinaryKind::Eq => compare("=="),
+                        hir::BinaryKind::Ne => compare("!="),
+                        hir::BinaryKind::Gt => compare(">"),
+                        hir::BinaryKind::Ge => compare(">="),
+                        hir::BinaryKind::Lt => compare("<"),
+                        hir::BinaryKind::Le => compare("<="),
+                    }
+                }
+                hir::ExprKind::Call(call) => {
+                    let generics = cx.eval_consts(call.generics);
+                    let generics = self.check(cx, generics)?;
+                    let callee = &self.ctx[call.callee];
+
+                    let mut args = Vec::with_capacity(call.args.len());
+
+                    for (&arg, param) in std::iter::zip(
+                        &self.ctx[call.args],
+                        &self.ctx.params[callee.inputs],
+                    ) {
+                        let expected =
+                            self.ctx.resolve_type(param.ty, &generics);
+                        let expected = self.check(cx, expected)?;
+                        let found = cx.type_of(arg);
+                        let found = self.check(cx, found)?;
+
+                        let value = self.emit_expression(cx, arg)?;
+                        let span = self.ctx[arg].span;
+
+                        args.push(
+                            self.coerce(cx, value, &found, &expected, span)?,
+                        );
+                    }
+
+                    let name = self.instance(call.callee, generics);
+                    widen(&ty, format!("{}({})", name, args.join(", ")))
+                }
+                hir::ExprKind::Record(cons) => {
+                    let name = self.c_type(cx, &ty, expr.span)?;
+                    let hir::Ty::Record(_, generics) = &ty else {
+                        unreachable!("constructor of non-record type");
+                    };
+
+                    let mut inits = Vec::with_capacity(cons.inits.len());
+
+                    for (&init, field) in std::iter::zip(
+                        &self.ctx[cons.inits],
+                        &self.ctx.fields[self.ctx[cons.record].fields],
+                    ) {
+                        let expected =
+                            self.ctx.resolve_type(field.ty, generics);
+                        let expected = self.check(cx, expected)?;
+                        let found = cx.type_of(init);
+                        let found = self.check(cx, found)?;
+
+                        let value = self.emit_expression(cx, init)?;
+                        let span = self.ctx[init].span;
+                        let value =
+                            self.coerce(cx, value, &found, &expected, span)?;
+
+                        inits.push(format!(
+                            ".{} = {}",
+                            sanitize(&self.ctx.symbols, &field.name),
+                            value,
+                        ));
+                    }
+
+                    if inits.is_empty() {
+                        format!("({}){{ 0 }}", name)
+                    } else {
+                        format!("({}){{ {} }}", name, inits.join(", "))
+                    }
+                }
+            })
+        })
+    }
+
+    fn coerce(
+        &mut self,
+        cx: &hir::TypeCx,
+        value: String,
+        found: &hir::Ty,
+        expected: &hir::Ty,
+        span: hir::Span,
+    ) -> Result<String> {
+        match (found, expected) {
+            (hir::Ty::Record(..), hir::Ty::Record(..)) if found == expected => {
+                Ok(value)
+            }
+            (hir::Ty::Int | hir::Ty::UInt(_), hir::Ty::Int) => Ok(value),
+            (hir::Ty::Int | hir::Ty::UInt(_), hir::Ty::UInt(_)) => {
+                let ty = self.c_type(cx, expected, span)?;
+
+                let value = match (width(found), width(expected)) {
+                    (Some(found), Some(expected)) if found <= expected => value,
+                    (_, expected) => masked(value, expected),
+                };
+
+                Ok(format!("({}){}", ty, value))
+            }
+            _ => {
+                let error = hir::TyError {
+                    kind: hir::TyErrorKind::Mismatch(
+                        expected.clone(),
+                        found.clone(),
+                    ),
+                    span,
+                };
+
+                self.check(cx, Err(error))
+            }
+        }
+    }
+
+    fn c_type(
+        &mut self,
+        cx: &hir::TypeCx,
+        ty: &hir::Ty,
+        span: hir::Span,
+    ) -> Result<String> {
+        match ty {
+            hir::Ty::Int => Ok("uint64_t".into()),
+            &hir::Ty::UInt(width) => match value(width) {
+                0..=8 => Ok("uint8_t".into()),
+                9..=16 => Ok("uint16_t".into()),
+                17..=32 => Ok("uint32_t".into()),
+                33..=64 => Ok("uint64_t".into()),
+                width => {
+                    self.reporter.emit(errors::UnsupportedWidth {
+                        width,
+                        span,
+                        backend: "C",
+                    });
+
+                    Err(CodegenError)
+                }
+            },
+            hir::Ty::Record(record, args) => {
+                self.emit_record(cx, *record, args)
+            }
+        }
+    }
+
+    fn emit_record(
+        &mut self,
+        cx: &hir::TypeCx,
+        record: hir::Index<hir::Record>,
+        args: &[hir::Const],
+    ) -> Result<String> {
+        let decl = &self.ctx[record];
+
+        match self.records.get(&(record, args.into())) {
+            Some(RecordState::Done(name)) => return Ok(name.clone()),
+            Some(RecordState::InProgress) => {
+                self.reporter.emit(errors::RecursiveRecord {
+                    symbols: &self.ctx.symbols,
+                    name: &decl.name,
+                });
+
+                return Err(CodegenError);
+            }
+            None => {}
+        }
+
+        self.records
+            .insert((record, args.into()), RecordState::InProgress);
+
+        let name = mangle(&self.ctx.symbols, &decl.name, args);
+        let mut out = format!("typedef struct {} {{\n", name);
+
+        for field in &self.ctx.fields[decl.fields] {
+            let ty = self.ctx.resolve_type(field.ty, args);
+            let ty = self.check(cx, ty)?;
+            let ty = self.c_type(cx, &ty, field.span)?;
+
+            writeln!(
+                out,
+                "    {} {};",
+                ty,
+                sanitize(&self.ctx.symbols, &field.name)
+            )
+            .unwrap();
+        }
+
+        if decl.fields.is_empty() {
+            out.push_str("    char adpl_unused;\n");
+        }
+
+        writeln!(out, "}} {};", name).unwrap();
+
+        if !self.structs.is_empty() {
+            self.structs.push('\n');
+        }
+
+        self.structs.push_str(&out);
+        self.records
+            .insert((record, args.into()), RecordState::Done(name.clone()));
+
+        Ok(name)
+    }
+
+    /// Names a local. Lets are numbered to tell shadowed names apart, and get
+    /// the `adpl_` prefix so that they never collide with a parameter; user
+    /// names with that prefix are escaped by `sanitize`.
+    fn local_name(&self, local: hir::Index<hir::Local>) -> String {
+        let decl = &self.ctx[local];
+
+        match decl.kind {
+            hir::LocalKind::Let(_) => {
+                format!(
+                    "adpl_{}_{}",
+                    &self.ctx.symbols[decl.name.symbol],
+                    local.index(),
+                )
+            }
+            _ => sanitize(&self.ctx.symbols, &decl.name),
+        }
+    }
+
+    fn check<T>(
+        &mut self,
+        cx: &hir::TypeCx,
+        result: std::result::Result<T, hir::TyError>,
+    ) -> Result<T> {
+        result.map_err(|error| {
+            let generics = self.ctx[cx.def()].generics;
+            self.reporter.emit(error.to_diagnostic(self.ctx, generics));
+
+            CodegenError
+        })
+    }
+}
+
+fn value(value: hir::Const) -> u64 {
+    match value {
+        hir::Const::Value(value) => value,
+        hir::Const::Param(_) => {
+            unreachable!("generic parameter in monomorphic code")
+        }
+    }
+}
+
+fn width(ty: &hir::Ty) -> Option<u64> {
+    match ty {
+        &hir::Ty::UInt(width) => Some(value(width)),
+        _ => None,
+    }
+}
+
+fn literal(value: u64) -> String {
+    format!("UINT64_C({})", value)
+}
+
+fn masked(value: String, width: Option<u64>) -> String {
+    match width {
+        Some(width) if width < u64::BITS.into() => {
+            format!("({} & UINT64_C({:#x}))", value, hir::mask(width as u32),)
+        }
+        _ => value,
+    }
+}
+
+fn widen(ty: &hir::Ty, value: String) -> String {
+    match ty {
+        hir::Ty::Record(..) => value,
+        _ => format!("(uint64_t){}", value),
+    }
+}
+
+/// Escapes names that C reserves, and names that `hir::escape` escapes.
+fn sanitize(symbols: &hir::Symbols, name: &hir::Id) -> String {
+    let name = &symbols[name.symbol];
+
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        hir::escape(name).into_owned()
+    }
+}
+
+/// Names an instance of an item, with `hir::mangle` if it is generic.
+fn mangle(
+    symbols: &hir::Symbols,
+    name: &hir::Id,
+    generics: &[hir::Const],
+) -> String {
+    if generics.is_empty() {
+        sanitize(symbols, name)
+    } else {
+        hir::mangle(symbols, name, generics)
+    }
+}
@@ -0,0 +1,72 @@
+use adpl_hir as hir;
+use adpl_util::{Diagnostic, codes};
+
+pub struct UnsupportedWidth<'a> {
+    pub width: u64,
+    pub span: hir::Span,
+    pub backend: &'a str,
+}
+
+impl From<UnsupportedWidth<'_>> for Diagnostic {
+    fn from(value: UnsupportedWidth) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0021)
+            .with_message(format!(
+                "`uint[{}]` is not supported by the {} backend",
+                value.width, value.backend,
+            ))
+            .with_primary(value.span, "width exceeds 64 bits")
+    }
+}
+
+pub struct RecursiveRecord<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+}
+
+impl From<RecursiveRecord<'_>> for Diagnostic {
+    fn from(value: RecursiveRecord) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0022)
+            .with_message(format!(
+                "recursive struct `{}` has infinite size",
+                &value.symbols[value.name.symbol],
+            ))
+            .with_primary(value.name.span, "recursive struct")
+    }
+}
+
+pub struct MissingBody<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+    pub backend: &'a str,
+}
+
+impl From<MissingBody<'_>> for Diagnostic {
+    fn from(value: MissingBody) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0026)
+            .with_message(format!(
+                "`{}` has no body, which the {} backend cannot emit",
+                &value.symbols[value.name.symbol], value.backend,
+            ))
+            .with_primary(value.name.span, "declared without a body")
+    }
+}
+
+pub struct MissingReturn<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+}
+
+impl From<MissingReturn<'_>> for Diagnostic {
+    fn from(value: MissingReturn) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0023)
+            .with_message(format!(
+                "definition `{}` does not return a value",
+                &value.symbols[value.name.symbol],
+            ))
+            .with_primary(value.name.span, "missing `return`")
+    }
+}
@@ -0,0 +1,6 @@
+mod c;
+mod errors;
+mod rust;
+
+pub use c::emit_c;
+pub use rust::emit_rust;
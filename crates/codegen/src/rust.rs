@@ -0,0 +1,878 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use adpl_hir as hir;
+use adpl_util::{Diagnostic, Reporter, ice, with_sufficient_stack};
+
+use crate::errors;
+
+const PRELUDE: &str = "\
+// Generated by the ADPL compiler.
+
+#![allow(
+    dead_code,
+    non_camel_case_types,
+    non_snake_case,
+    unused_parens,
+    unused_variables,
+    clippy::all
+)]
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UInt<const W: u64>(u64);
+
+impl<const W: u64> UInt<W> {
+    pub const MASK: u64 = {
+        assert!(W <= 64, \"widths above 64 bits are not supported\");
+
+        if W == 64 { u64::MAX } else { (1 << W) - 1 }
+    };
+
+    #[inline]
+    pub const fn new(value: u64) -> Self {
+        UInt(value & Self::MASK)
+    }
+
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+mod rt {
+    #[inline]
+    pub fn div(a: u64, b: u64) -> u64 {
+        a.checked_div(b).unwrap_or(u64::MAX)
+    }
+
+    #[inline]
+    pub fn pow(mut base: u64, mut exp: u64) -> u64 {
+        let mut acc = 1u64;
+
+        while exp != 0 {
+            if exp & 1 == 1 {
+                acc = acc.wrapping_mul(base);
+            }
+
+            base = base.wrapping_mul(base);
+            exp >>= 1;
+        }
+
+        acc
+    }
+
+    #[inline]
+    pub fn shl(a: u64, b: u64) -> u64 {
+        u32::try_from(b).ok().and_then(|b| a.checked_shl(b)).unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn shr(a: u64, b: u64) -> u64 {
+        u32::try_from(b).ok().and_then(|b| a.checked_shr(b)).unwrap_or(0)
+    }
+}
+";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "dyn", "else",
+    "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "use",
+    "where", "while", "yield", "abstract", "become", "do", "final", "override",
+    "priv", "unsized", "virtual", "gen",
+];
+
+const RESERVED: &[&str] = &["self", "Self", "super", "crate", "UInt", "rt"];
+
+pub fn emit_rust(
+    ctx: &hir::Context,
+    reporter: &mut Reporter,
+) -> Option<String> {
+    let mut codegen = CodegenRust {
+        ctx,
+        reporter,
+        speculative: false,
+        scope: hir::IndexRange {
+            start: hir::Index::INVALID,
+            end: hir::Index::INVALID,
+        },
+        mono_records: HashSet::new(),
+        mono_defs: HashSet::new(),
+        items: String::new(),
+        records: HashMap::new(),
+        instances: HashMap::new(),
+        queue: Vec::new(),
+    };
+
+    codegen.emit_file().ok()?;
+
+    let mut out = String::from(PRELUDE);
+    out.push_str(&codegen.items);
+
+    Some(out)
+}
+
+#[derive(Debug)]
+enum CodegenError {
+    Failed,
+    NotConst,
+}
+
+type Result<T> = std::result::Result<T, CodegenError>;
+
+type Instance<T> = (hir::Index<T>, Box<[hir::Const]>);
+
+struct CodegenRust<'a, 'src> {
+    ctx: &'a hir::Context,
+    reporter: &'a mut Reporter<'src>,
+    speculative: bool,
+    scope: hir::IndexRange<hir::Local>,
+    mono_records: HashSet<hir::Index<hir::Record>>,
+    mono_defs: HashSet<hir::Index<hir::Definition>>,
+    items: String,
+    records: HashMap<Instance<hir::Record>, String>,
+    instances: HashMap<Instance<hir::Definition>, String>,
+    queue: Vec<Instance<hir::Definition>>,
+}
+
+impl CodegenRust<'_, '_> {
+    fn emit_file(&mut self) -> Result<()> {
+        self.check_recursion()?;
+        self.find_monomorphic();
+
+        for record in self.ctx.records.keys() {
+            if !self.mono_records.contains(&record) {
+                let generics = params(self.ctx[record].params.len());
+                self.emit_record(record, &generics)?;
+            }
+        }
+
+        for def in self.ctx.defs.keys() {
+            if !self.mono_defs.contains(&def) {
+                let generics = params(self.ctx[def].generics.len());
+                self.emit_item(def, &generics)?;
+            }
+        }
+
+        let mut next = 0;
+
+        while let Some((def, generics)) = self.queue.get(next).cloned() {
+            self.emit_item(def, &generics)?;
+            next += 1;
+        }
+
+        Ok(())
+    }
+
+    fn check_recursion(&mut self) -> Result<()> {
+        fn visit(
+            ctx: &hir::Context,
+            record: hir::Index<hir::Record>,
+            state: &mut HashMap<hir::Index<hir::Record>, bool>,
+        ) -> bool {
+            match state.get(&record) {
+                Some(&done) => return done,
+                None => state.insert(record, false),
+            };
+
+            for field in &ctx.fields[ctx[record].fields] {
+                if let hir::TypeKind::Record(inner) = ctx[field.ty.ty].kind
+                    && !visit(ctx, inner, state)
+                {
+                    return false;
+                }
+            }
+
+            state.insert(record, true);
+            true
+        }
+
+        let mut state = HashMap::new();
+
+        for (record, decl) in self.ctx.records.iter() {
+            if !visit(self.ctx, record, &mut state) {
+                return Err(self.error(errors::RecursiveRecord {
+                    symbols: &self.ctx.symbols,
+                    name: &decl.name,
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the generic items that cannot be expressed with const generics
+    /// and must be instantiated for every set of generic arguments instead.
+    fn find_monomorphic(&mut self) {
+        self.speculative = true;
+
+        loop {
+            let mut changed = false;
+
+            for (record, decl) in self.ctx.records.iter() {
+                if decl.params.is_empty() || self.mono_records.contains(&record)
+                {
+                    continue;
+                }
+
+                let generics = params(decl.params.len());
+
+                if !self.record_expressible(record, &generics) {
+                    self.mono_records.insert(record);
+                    changed = true;
+                }
+            }
+
+            for (def, decl) in self.ctx.defs.iter() {
+                if decl.generics.is_empty() || self.mono_defs.contains(&def) {
+                    continue;
+                }
+
+                let generics = params(decl.generics.len());
+
+                if let Err(CodegenError::NotConst) =
+                    self.emit_definition(def, &generics)
+                {
+                    self.mono_defs.insert(def);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        self.speculative = false;
+    }
+
+    fn record_expressible(
+        &self,
+        record: hir::Index<hir::Record>,
+        generics: &[hir::Const],
+    ) -> bool {
+        self.ctx.fields[self.ctx[record].fields]
+            .iter()
+            .all(|field| match self.ctx.resolve_type(field.ty, generics) {
+                Ok(hir::Ty::Record(inner, args)) => {
+                    !self.mono_records.contains(&inner)
+                        || args.iter().all(|arg| is_value(*arg))
+                }
+                Ok(_) => true,
+                Err(error) => !matches!(error.kind, hir::TyErrorKind::NotConst),
+            })
+    }
+
+    fn emit_record(
+        &mut self,
+        record: hir::Index<hir::Record>,
+        generics: &[hir::Const],
+    ) -> Result<String> {
+        let decl = &self.ctx[record];
+        let mono = self.mono_records.contains(&record);
+        let scope = std::mem::replace(&mut self.scope, decl.params);
+
+        let name = if mono {
+            hir::mangle(&self.ctx.symbols, &decl.name, generics)
+        } else {
+            sanitize(&self.ctx.symbols, &decl.name)
+        };
+
+        let mut out =
+            String::from("\n#[derive(Clone, Copy, Debug, PartialEq)]\n");
+        write!(out, "pub struct {}", name).unwrap();
+
+        if !mono && !decl.params.is_empty() {
+            let params = self.ctx.locals[decl.params]
+                .iter()
+                .map(|param| {
+                    format!(
+                        "const {}: u64",
+                        sanitize(&self.ctx.symbols, &param.name)
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            write!(out, "<{}>", params.join(", ")).unwrap();
+        }
+
+        out.push_str(" {\n");
+
+        for field in &self.ctx.fields[decl.fields] {
+            let ty = self.ctx.resolve_type(field.ty, generics);
+            let ty = self.check(ty)?;
+            let ty = self.rust_type(&ty, field.span, false)?;
+
+            writeln!(
+                out,
+                "    pub {}: {},",
+                sanitize(&self.ctx.symbols, &field.name),
+                ty
+            )
+            .unwrap();
+        }
+
+        out.push_str("}\n");
+
+        if !self.speculative {
+            self.items.push_str(&out);
+        }
+
+        self.scope = scope;
+
+        Ok(name)
+    }
+
+    fn instance(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: Box<[hir::Const]>,
+        span: hir::Span,
+    ) -> Result<String> {
+        let decl = &self.ctx[def];
+
+        if !self.mono_defs.contains(&def) {
+            return Ok(format!(
+                "{}{}",
+                sanitize(&self.ctx.symbols, &decl.name),
+                self.turbofish(&generics),
+            ));
+        }
+
+        if !generics.iter().all(|&arg| is_value(arg)) {
+            let error = hir::TyError {
+                kind: hir::TyErrorKind::NotConst,
+                span,
+            };
+
+            return self.check(Err(error));
+        }
+
+        let name = hir::mangle(&self.ctx.symbols, &decl.name, &generics);
+
+        if !self.speculative
+            && !self.instances.contains_key(&(def, generics.clone()))
+        {
+            self.instances.insert((def, generics.clone()), name.clone());
+            self.queue.push((def, generics));
+        }
+
+        Ok(name)
+    }
+
+    fn emit_item(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: &[hir::Const],
+    ) -> Result<()> {
+        let name = &self.ctx[def].name;
+
+        ice::item(&self.ctx.symbols[name.symbol], name.span, || {
+            self.emit_definition(def, generics)
+        })
+    }
+
+    fn emit_definition(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: &[hir::Const],
+    ) -> Result<()> {
+        let decl = &self.ctx[def];
+        let mut cx = hir::TypeCx::new(self.ctx, def, generics);
+        let mono = self.mono_defs.contains(&def);
+        self.scope = decl.generics;
+
+        let mut out = String::from("\npub fn ");
+
+        if mono {
+            out.push_str(&hir::mangle(&self.ctx.symbols, &decl.name, generics));
+        } else {
+            out.push_str(&sanitize(&self.ctx.symbols, &decl.name));
+
+            if !decl.generics.is_empty() {
+                let params = self.ctx.locals[decl.generics]
+                    .iter()
+                    .map(|param| {
+                        format!(
+                            "const {}: u64",
+                            sanitize(&self.ctx.symbols, &param.name)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                write!(out, "<{}>", params.join(", ")).unwrap();
+            }
+        }
+
+        out.push('(');
+
+        for (i, param) in self.ctx.params[decl.inputs].iter().enumerate() {
+            let ty = cx.resolve_type(param.ty);
+            let ty = self.check(ty)?;
+
+            if i > 0 {
+                out.push_str(", ");
+            }
+
+            let ty = self.rust_type(&ty, param.span, false)?;
+            let name = sanitize(&self.ctx.symbols, &self.ctx[param.local].name);
+            write!(out, "{}: {}", name, ty).unwrap();
+        }
+
+        let output = cx.output_type();
+        let output = self.check(output)?;
+        let output_span = decl.output.span;
+        let ty = self.rust_type(&output, output_span, false)?;
+
+        writeln!(out, ") -> {} {{", ty).unwrap();
+
+        if let Some(requires) = decl.requires {
+            let requires = requires.get();
+            let value = self.emit_expression(&mut cx, requires)?;
+
+            writeln!(
+                out,
+                "    debug_assert!({} != 0, \"requirement of `{}` violated\");",
+                value, &self.ctx.symbols[decl.name.symbol],
+            )
+            .unwrap();
+        }
+
+        let Some(body) = decl.body else {
+            return Err(self.error(errors::MissingBody {
+                symbols: &self.ctx.symbols,
+                name: &decl.name,
+                backend: "Rust",
+            }));
+        };
+
+        if !self.emit_block(&mut cx, &output, body, &mut out)? {
+            return Err(self.error(errors::MissingReturn {
+                symbols: &self.ctx.symbols,
+                name: &decl.name,
+            }));
+        }
+
+        out.push_str("}\n");
+
+        if !self.speculative {
+            self.items.push_str(&out);
+        }
+
+        Ok(())
+    }
+
+    /// Emits the statements of `block` up to the first `return`, and returns
+    /// whether there was one.
+    fn emit_block(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        output: &hir::Ty,
+        block: hir::List<hir::Statement>,
+        out: &mut String,
+    ) -> Result<bool> {
+        for &stmt in &self.ctx[block] {
+            match self.ctx[stmt].kind {
+                hir::StmtKind::Assign(local, expr) => {
+                    let ty = cx.type_of(expr);
+                    let ty = self.check(ty)?;
+                    let span = self.ctx[expr].span;
+                    let value = self.emit_expression(cx, expr)?;
+                    let value = self.coerce(value, &ty, &ty, span)?;
+                    let name = self.local_name(local);
+
+                    writeln!(out, "    let {} = {};", name, value).unwrap();
+                }
+                hir::StmtKind::Return(expr) => {
+                    let ty = cx.type_of(expr);
+                    let ty = self.check(ty)?;
+                    let span = self.ctx[expr].span;
+                    let value = self.emit_expression(cx, expr)?;
+                    let value = self.coerce(value, &ty, output, span)?;
+
+                    writeln!(out, "    return {};", value).unwrap();
+                    return Ok(true);
+                }
+                hir::StmtKind::Unsafe(block) => {
+                    if self.emit_block(cx, output, block, out)? {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn emit_expression(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        index: hir::Index<hir::Expression>,
+    ) -> Result<String> {
+        with_sufficient_stack(|| {
+            let ty = cx.type_of(index);
+            let ty = self.check(ty)?;
+            let expr = &self.ctx[index];
+
+            Ok(match &expr.kind {
+                &hir::ExprKind::Id(local) => match self.ctx[local].kind {
+                    hir::LocalKind::GenericParam(i) => {
+                        self.constant(cx.generics()[i as usize])
+                    }
+                    _ => unwrap(&ty, self.local_name(local)),
+                },
+                hir::ExprKind::Lit(lit) => format!("{}u64", lit.value),
+                &hir::ExprKind::Field(base, name) => {
+                    let base = self.emit_expression(cx, base)?;
+                    unwrap(
+                        &ty,
+                        format!(
+                            "{}.{}",
+                            base,
+                            sanitize(&self.ctx.symbols, &name)
+                        ),
+                    )
+                }
+                &hir::ExprKind::Unary(op, operand) => {
+                    let operand = self.emit_expression(cx, operand)?;
+
+                    let value = match op.kind {
+                        hir::UnaryKind::Neg => {
+                            format!("({}).wrapping_neg()", operand)
+                        }
+                        hir::UnaryKind::Not => format!("(!{})", operand),
+                    };
+
+                    self.masked(value, &ty)
+                }
+                &hir::ExprKind::Binary(op, lhs, rhs) => {
+                    let lhs = self.emit_expression(cx, lhs)?;
+                    let rhs = self.emit_expression(cx, rhs)?;
+
+                    let method = |name| format!("{}.{}({})", lhs, name, rhs);
+                    let helper =
+                        |name| format!("rt::{}({}, {})", name, lhs, rhs);
+                    let compare =
+                        |op| format!("(({} {} {}) as u64)", lhs, op, rhs);
+
+                    match op.kind {
+                        hir::BinaryKind::Add => {
+                            self.masked(method("wrapping_add"), &ty)
+                        }
+                        hir::BinaryKind::Sub => {
+                            self.masked(method("wrapping_sub"), &ty)
+                        }
+                        hir::BinaryKind::Mul => {
+                            self.masked(method("wrapping_mul"), &ty)
+                        }
+                        hir::BinaryKind::Div => self.masked(helper("div"), &ty),
+                        hir::BinaryKind::Pow => self.masked(helper("pow"), &ty),
+                        hir::BinaryKind::Shl => self.masked(helper("shl"), &ty),
+                        hir::BinaryKind::Shr => helper("shr"),
+                        hir::BinaryKind::Eq => compare("=="),
+                        hir::BinaryKind::Ne => compare("!="),
+                        hir::BinaryKind::Gt => compare(">"),
+                        hir::BinaryKind::Ge => compare(">="),
+                        hir::BinaryKind::Lt => compare("<"),
+                        hir::BinaryKind::Le => compare("<="),
+                    }
+                }
+                hir::ExprKind::Call(call) => {
+                    let generics = cx.eval_consts(call.generics);
+                    let generics = self.check(generics)?;
+                    let callee = &self.ctx[call.callee];
+
+                    let mut args = Vec::with_capacity(call.args.len());
+
+                    for (&arg, param) in std::iter::zip(
+                        &self.ctx[call.args],
+                        &self.ctx.params[callee.inputs],
+                    ) {
+                        let expected =
+                            self.ctx.resolve_type(param.ty, &generics);
+                        let expected = self.check(expected)?;
+                        let found = cx.type_of(arg);
+                        let found = self.check(found)?;
+
+                        let value = self.emit_expression(cx, arg)?;
+                        let span = self.ctx[arg].span;
+
+                        args.push(self.coerce(value, &found, &expected, span)?);
+                    }
+
+                    let name =
+                        self.instance(call.callee, generics, call.name.span)?;
+
+                    unwrap(&ty, format!("{}({})", name, args.join(", ")))
+                }
+                hir::ExprKind::Record(cons) => {
+                    let name = self.rust_type(&ty, expr.span, true)?;
+                    let hir::Ty::Record(_, generics) = &ty else {
+                        unreachable!("constructor of non-record type");
+                    };
+
+                    let mut inits = Vec::with_capacity(cons.inits.len());
+
+                    for (&init, field) in std::iter::zip(
+                        &self.ctx[cons.inits],
+                        &self.ctx.fields[self.ctx[cons.record].fields],
+                    ) {
+                        let expected =
+                            self.ctx.resolve_type(field.ty, generics);
+                        let expected = self.check(expected)?;
+                        let found = cx.type_of(init);
+                        let found = self.check(found)?;
+
+                        let value = self.emit_expression(cx, init)?;
+                        let span = self.ctx[init].span;
+                        let value =
+                            self.coerce(value, &found, &expected, span)?;
+
+                        inits.push(format!(
+                            "{}: {}",
+                            sanitize(&self.ctx.symbols, &field.name),
+                            value,
+                        ));
+                    }
+
+                    if inits.is_empty() {
+                        format!("{} {{}}", name)
+                    } else {
+                        format!("{} {{ {} }}", name, inits.join(", "))
+                    }
+                }
+            })
+        })
+    }
+
+    fn coerce(
+        &mut self,
+        value: String,
+        found: &hir::Ty,
+        expected: &hir::Ty,
+        span: hir::Span,
+    ) -> Result<String> {
+        match (found, expected) {
+            (hir::Ty::Record(..), hir::Ty::Record(..)) if found == expected => {
+                Ok(value)
+            }
+            (hir::Ty::Int | hir::Ty::UInt(_), hir::Ty::Int) => Ok(value),
+            (hir::Ty::Int | hir::Ty::UInt(_), hir::Ty::UInt(_)) => {
+                let ty = self.rust_type(expected, span, true)?;
+
+                Ok(format!("{}::new({})", ty, value))
+            }
+            _ => {
+                let error = hir::TyError {
+                    kind: hir::TyErrorKind::Mismatch(
+                        expected.clone(),
+                        found.clone(),
+                    ),
+                    span,
+                };
+
+                self.check(Err(error))
+            }
+        }
+    }
+
+    fn rust_type(
+        &mut self,
+        ty: &hir::Ty,
+        span: hir::Span,
+        expr: bool,
+    ) -> Result<String> {
+        let sep = if expr { "::" } else { "" };
+
+        match ty {
+            hir::Ty::Int => Ok("u64".into()),
+            &hir::Ty::UInt(hir::Const::Value(width))
+                if width > u64::BITS.into() =>
+            {
+                Err(self.error(errors::UnsupportedWidth {
+                    width,
+                    span,
+                    backend: "Rust",
+                }))
+            }
+            &hir::Ty::UInt(width) => {
+                Ok(format!("UInt{}<{}>", sep, self.constant(width)))
+            }
+            hir::Ty::Record(record, args)
+                if self.mono_records.contains(record) =>
+            {
+                if !args.iter().all(|&arg| is_value(arg)) {
+                    let error = hir::TyError {
+                        kind: hir::TyErrorKind::NotConst,
+                        span,
+                    };
+
+                    return self.check(Err(error));
+                }
+
+                let key = (*record, args.clone());
+
+                if let Some(name) = self.records.get(&key) {
+                    return Ok(name.clone());
+                }
+
+                if self.speculative {
+                    return Ok(hir::mangle(
+                        &self.ctx.symbols,
+                        &self.ctx[*record].name,
+                        args,
+                    ));
+                }
+
+                let name = self.emit_record(*record, args)?;
+                self.records.insert(key, name.clone());
+
+                Ok(name)
+            }
+            hir::Ty::Record(record, args) => Ok(format!(
+                "{}{}",
+                sanitize(&self.ctx.symbols, &self.ctx[*record].name),
+                if expr {
+                    self.turbofish(args)
+                } else {
+                    self.generic_args(args)
+                },
+            )),
+        }
+    }
+
+    fn constant(&self, value: hir::Const) -> String {
+        match value {
+            hir::Const::Value(value) => value.to_string(),
+            hir::Const::Param(i) => sanitize(
+                &self.ctx.symbols,
+                &self.ctx.locals[self.scope][i as usize].name,
+            ),
+        }
+    }
+
+    fn generic_args(&self, args: &[hir::Const]) -> String {
+        if args.is_empty() {
+            return String::new();
+        }
+
+        let args = args
+            .iter()
+            .map(|&arg| self.constant(arg))
+            .collect::<Vec<_>>();
+
+        format!("<{}>", args.join(", "))
+    }
+
+    fn turbofish(&self, args: &[hir::Const]) -> String {
+        if args.is_empty() {
+            String::new()
+        } else {
+            format!("::{}", self.generic_args(args))
+        }
+    }
+
+    fn masked(&self, value: String, ty: &hir::Ty) -> String {
+        match *ty {
+            hir::Ty::UInt(hir::Const::Value(width))
+                if width < u64::BITS.into() =>
+            {
+                format!("({} & {:#x})", value, hir::mask(width as u32))
+            }
+            hir::Ty::UInt(hir::Const::Value(_)) => value,
+            hir::Ty::UInt(width) => {
+                format!("({} & UInt::<{}>::MASK)", value, self.constant(width))
+            }
+            _ => value,
+        }
+    }
+
+    /// Names a local like the C backend does, so that lets never shadow a
+    /// parameter.
+    fn local_name(&self, local: hir::Index<hir::Local>) -> String {
+        let decl = &self.ctx[local];
+
+        match decl.kind {
+            hir::LocalKind::Let(_) => {
+                format!(
+                    "adpl_{}_{}",
+                    &self.ctx.symbols[decl.name.symbol],
+                    local.index(),
+                )
+            }
+            _ => sanitize(&self.ctx.symbols, &decl.name),
+        }
+    }
+
+    fn check<T>(
+        &mut self,
+        result: std::result::Result<T, hir::TyError>,
+    ) -> Result<T> {
+        result.map_err(|error| {
+            // Errors that only arise for symbolic generics (e.g. `uint[W]`
+            // mixed with `uint[8]`) may vanish once the definition is
+            // monomorphised, where they are reported if they persist. Other
+            // errors are reported when the definition is emitted for real.
+            if self.speculative && is_symbolic(&error) {
+                return CodegenError::NotConst;
+            }
+
+            self.error(error.to_diagnostic(self.ctx, self.scope))
+        })
+    }
+
+    fn error<D: Into<Diagnostic>>(&mut self, diagnostic: D) -> CodegenError {
+        if !self.speculative {
+            self.reporter.emit(diagnostic);
+        }
+
+        CodegenError::Failed
+    }
+}
+
+fn params(len: usize) -> Box<[hir::Const]> {
+    (0..len)
+        .map(|i| hir::Const::Param(i.try_into().unwrap()))
+        .collect()
+}
+
+fn is_value(value: hir::Const) -> bool {
+    matches!(value, hir::Const::Value(_))
+}
+
+fn is_symbolic(error: &hir::TyError) -> bool {
+    let has_param = |ty: &hir::Ty| match ty {
+        hir::Ty::UInt(width) => !is_value(*width),
+        hir::Ty::Record(_, args) => !args.iter().all(|&arg| is_value(arg)),
+        hir::Ty::Int => false,
+    };
+
+    match &error.kind {
+        hir::TyErrorKind::NotConst => true,
+        hir::TyErrorKind::Mismatch(expected, found) => {
+            has_param(expected) || has_param(found)
+        }
+        _ => false,
+    }
+}
+
+fn unwrap(ty: &hir::Ty, value: String) -> String {
+    match ty {
+        hir::Ty::UInt(_) => format!("{}.get()", value),
+        _ => value,
+    }
+}
+
+/// Escapes names that Rust or the prelude reserve, and names that
+/// `hir::escape` escapes.
+fn sanitize(symbols: &hir::Symbols, name: &hir::Id) -> String {
+    let name = &symbols[name.symbol];
+
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        hir::escape(name).into_owned()
+    }
+}
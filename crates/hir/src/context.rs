@@ -1,26 +1,62 @@
 use std::ops;
 
-use adpl_arena::{Arena, Index, IndexArena, List};
+use adpl_arena::{
+    Arena, ArenaSnapshot, Index, IndexArena, IndexArenaSnapshot, Interned, List,
+};
 
 use crate::hir;
 
 #[derive(Default)]
 pub struct Context {
+    pub symbols: hir::Symbols,
     pub records: Arena<hir::Record>,
     pub fields: Arena<hir::Field>,
     pub defs: Arena<hir::Definition>,
     pub params: Arena<hir::Parameter>,
     pub stmts: Arena<hir::Statement>,
     pub exprs: Arena<hir::Expression>,
-    pub types: Arena<hir::Type>,
+    pub types: Interned<hir::Type>,
     pub locals: Arena<hir::Local>,
     pub lists: IndexArena,
 }
 
 impl Context {
     #[inline]
-    pub fn new() -> Self {
-        Context::default()
+    pub fn new(symbols: hir::Symbols) -> Self {
+        Context {
+            symbols,
+            ..Context::default()
+        }
+    }
+
+    /// Records the size of every arena, so that the nodes added after this
+    /// point can be discarded with [`Context::rollback_to`].
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            records: self.records.snapshot(),
+            fields: self.fields.snapshot(),
+            defs: self.defs.snapshot(),
+            params: self.params.snapshot(),
+            stmts: self.stmts.snapshot(),
+            exprs: self.exprs.snapshot(),
+            types: self.types.snapshot(),
+            locals: self.locals.snapshot(),
+            lists: self.lists.snapshot(),
+        }
+    }
+
+    /// Removes every node added since `snapshot` was taken. Nodes that were
+    /// modified in place are left as they are.
+    pub fn rollback_to(&mut self, snapshot: Snapshot) {
+        self.records.rollback_to(snapshot.records);
+        self.fields.rollback_to(snapshot.fields);
+        self.defs.rollback_to(snapshot.defs);
+        self.params.rollback_to(snapshot.params);
+        self.stmts.rollback_to(snapshot.stmts);
+        self.exprs.rollback_to(snapshot.exprs);
+        self.types.rollback_to(snapshot.types);
+        self.locals.rollback_to(snapshot.locals);
+        self.lists.rollback_to(snapshot.lists);
     }
 
     #[allow(private_bounds)]
@@ -33,6 +69,20 @@ impl Context {
     }
 }
 
+/// The size of a [`Context`] at some point.
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshot {
+    records: ArenaSnapshot<hir::Record>,
+    fields: ArenaSnapshot<hir::Field>,
+    defs: ArenaSnapshot<hir::Definition>,
+    params: ArenaSnapshot<hir::Parameter>,
+    stmts: ArenaSnapshot<hir::Statement>,
+    exprs: ArenaSnapshot<hir::Expression>,
+    types: ArenaSnapshot<hir::Type>,
+    locals: ArenaSnapshot<hir::Local>,
+    lists: IndexArenaSnapshot,
+}
+
 impl<T> ops::Index<Index<T>> for Context
 where
     Context: Store<T>,
@@ -55,6 +105,15 @@ where
     }
 }
 
+impl ops::Index<Index<hir::Type>> for Context {
+    type Output = hir::Type;
+
+    #[inline]
+    fn index(&self, index: Index<hir::Type>) -> &hir::Type {
+        &self.types[index]
+    }
+}
+
 impl<T> ops::Index<List<T>> for Context {
     type Output = [Index<T>];
 
@@ -99,5 +158,4 @@ store_impl!(defs, hir::Definition);
 store_impl!(params, hir::Parameter);
 store_impl!(stmts, hir::Statement);
 store_impl!(exprs, hir::Expression);
-store_impl!(types, hir::Type);
 store_impl!(locals, hir::Local);
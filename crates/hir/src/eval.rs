@@ -0,0 +1,59 @@
+use crate::{BinaryKind, UnaryKind};
+
+#[inline]
+pub fn mask(width: u32) -> u64 {
+    if width >= u64::BITS {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+pub fn eval_unary(kind: UnaryKind, value: u64, width: u32) -> u64 {
+    let result = match kind {
+        UnaryKind::Neg => value.wrapping_neg(),
+        UnaryKind::Not => !value,
+    };
+
+    result & mask(width)
+}
+
+pub fn eval_binary(kind: BinaryKind, lhs: u64, rhs: u64, width: u32) -> u64 {
+    let result = match kind {
+        BinaryKind::Add => lhs.wrapping_add(rhs),
+        BinaryKind::Sub => lhs.wrapping_sub(rhs),
+        BinaryKind::Mul => lhs.wrapping_mul(rhs),
+        BinaryKind::Div => lhs.checked_div(rhs).unwrap_or(u64::MAX),
+        BinaryKind::Pow => pow(lhs, rhs),
+        BinaryKind::Shl => lhs.checked_shl(shift(rhs)).unwrap_or(0),
+        BinaryKind::Shr => lhs.checked_shr(shift(rhs)).unwrap_or(0),
+        BinaryKind::Eq => (lhs == rhs) as u64,
+        BinaryKind::Ne => (lhs != rhs) as u64,
+        BinaryKind::Gt => (lhs > rhs) as u64,
+        BinaryKind::Ge => (lhs >= rhs) as u64,
+        BinaryKind::Lt => (lhs < rhs) as u64,
+        BinaryKind::Le => (lhs <= rhs) as u64,
+    };
+
+    result & mask(width)
+}
+
+fn pow(mut base: u64, mut exp: u64) -> u64 {
+    let mut acc = 1u64;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc.wrapping_mul(base);
+        }
+
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+
+    acc
+}
+
+#[inline]
+fn shift(amount: u64) -> u32 {
+    amount.try_into().unwrap_or(u32::MAX)
+}
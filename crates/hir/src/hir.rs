@@ -1,6 +1,7 @@
 pub use adpl_arena::{Index, IndexRange, List, NonMaxIndex};
 pub use adpl_ast::{
-    BinaryKind, BinaryOp, Id, Literal, Safety, Span, Symbol, UnaryKind, UnaryOp,
+    BinaryKind, BinaryOp, Id, Literal, Safety, Span, Symbol, Symbols,
+    UnaryKind, UnaryOp,
 };
 
 #[derive(Debug)]
@@ -8,39 +9,70 @@ pub struct Record {
     pub name: Id,
     pub params: IndexRange<Local>,
     pub fields: IndexRange<Field>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Field {
     pub name: Id,
-    pub ty: Index<Type>,
+    pub ty: TypeRef,
     pub span: Span,
 }
 
-#[derive(Debug)]
+/// A type, interned so that equal types share an index.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Type {
-    pub name: Id,
-    pub decl: Index<Record>,
-    pub args: List<Expression>,
+    pub kind: TypeKind,
+    pub args: Box<[ConstArg]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    UInt,
+    Record(Index<Record>),
+}
+
+/// A generic argument of a type, with its constant parts folded.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ConstArg {
+    Value(u64),
+    Param(u16),
+    Unary(UnaryKind, Box<ConstArg>),
+    Binary(BinaryKind, Box<ConstArg>, Box<ConstArg>),
+}
+
+/// A use of an interned type in the source.
+#[derive(Clone, Copy, Debug)]
+pub struct TypeRef {
+    pub ty: Index<Type>,
     pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Definition {
     pub safety: Safety,
+    pub inline: Inline,
     pub name: Id,
     pub generics: IndexRange<Local>,
     pub requires: Option<NonMaxIndex<Expression>>,
     pub implements: Option<NonMaxIndex<Expression>>,
     pub inputs: IndexRange<Parameter>,
-    pub output: Index<Type>,
+    pub output: TypeRef,
     pub body: Option<List<Statement>>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inline {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Debug)]
 pub struct Parameter {
     pub local: Index<Local>,
-    pub ty: Index<Type>,
+    pub ty: TypeRef,
     pub span: Span,
 }
 
@@ -79,7 +111,7 @@ pub struct Local {
     pub name: Id,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum LocalKind {
     Let(Index<Expression>),
     Param(u16),
@@ -1,5 +1,17 @@
 mod context;
+mod eval;
 mod hir;
+mod mangle;
+mod node_map;
+mod serialize;
+mod ty;
+mod visit;
 
-pub use context::Context;
+pub use context::{Context, Snapshot};
+pub use eval::{eval_binary, eval_unary, mask};
 pub use hir::*;
+pub use mangle::{escape, mangle};
+pub use node_map::{Node, NodeMap};
+pub use serialize::{DecodeError, FORMAT_VERSION};
+pub use ty::{Const, DisplayTy, Ty, TyError, TyErrorKind, TypeCx};
+pub use visit::*;
@@ -0,0 +1,36 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::{Const, Id, Symbols};
+
+/// Escapes a user name so that it cannot collide with a name from [`mangle`]
+/// or a name that a backend makes up. Names that start with `adpl_` get a
+/// trailing `_`, and so do names that already end in `_`, which keeps the
+/// mapping one-to-one.
+pub fn escape(name: &str) -> Cow<'_, str> {
+    if name.starts_with("adpl_") || name.ends_with('_') {
+        Cow::Owned(format!("{}_", name))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Names an instance of a generic item, e.g. `adpl_3foo_8` for `foo[8]`.
+/// Escaped names that start with `adpl_` end in `_`, which mangled names
+/// never do, and the length of the name keeps `foo_1` with `8` apart from
+/// `foo` with `1, 8`.
+pub fn mangle(symbols: &Symbols, name: &Id, generics: &[Const]) -> String {
+    let name = &symbols[name.symbol];
+    let mut mangled = format!("adpl_{}{}", name.len(), name);
+
+    for &arg in generics {
+        match arg {
+            Const::Value(value) => write!(mangled, "_{}", value).unwrap(),
+            Const::Param(_) => {
+                unreachable!("generic parameter in monomorphic code")
+            }
+        }
+    }
+
+    mangled
+}
@@ -0,0 +1,296 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+
+use adpl_arena::{ArenaMap, Index};
+use adpl_util::FileId;
+
+use crate::{
+    Context, Definition, ExprKind, Expression, Field, Local, Parameter, Record,
+    Span, Statement, StmtKind,
+};
+
+/// A node of the HIR that belongs to a single parent. Types are interned and
+/// shared, so they are not nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Node {
+    Record(Index<Record>),
+    Field(Index<Field>),
+    Definition(Index<Definition>),
+    Parameter(Index<Parameter>),
+    Statement(Index<Statement>),
+    Expression(Index<Expression>),
+    Local(Index<Local>),
+}
+
+macro_rules! node_from {
+    ($variant:ident, $ty:ty) => {
+        impl From<Index<$ty>> for Node {
+            #[inline]
+            fn from(index: Index<$ty>) -> Node {
+                Node::$variant(index)
+            }
+        }
+    };
+}
+
+node_from!(Record, Record);
+node_from!(Field, Field);
+node_from!(Definition, Definition);
+node_from!(Parameter, Parameter);
+node_from!(Statement, Statement);
+node_from!(Expression, Expression);
+node_from!(Local, Local);
+
+/// The parent of every node of a [`Context`] and the nodes that cover each
+/// byte of the source, built once lowering is done.
+///
+/// The map is not updated when the context changes, so it has to be rebuilt
+/// after inlining or simplification.
+pub struct NodeMap {
+    fields: ArenaMap<Field, Node>,
+    params: ArenaMap<Parameter, Node>,
+    stmts: ArenaMap<Statement, Node>,
+    exprs: ArenaMap<Expression, Node>,
+    locals: ArenaMap<Local, Node>,
+    spans: Vec<SpanEntry>,
+}
+
+struct SpanEntry {
+    file: FileId,
+    range: Range<usize>,
+    node: Node,
+    /// The innermost entry whose span contains this one.
+    enclosing: Option<usize>,
+}
+
+impl NodeMap {
+    pub fn new(ctx: &Context) -> Self {
+        let mut map = NodeMap {
+            fields: ArenaMap::for_arena(&ctx.fields),
+            params: ArenaMap::for_arena(&ctx.params),
+            stmts: ArenaMap::for_arena(&ctx.stmts),
+            exprs: ArenaMap::for_arena(&ctx.exprs),
+            locals: ArenaMap::for_arena(&ctx.locals),
+            spans: Vec::new(),
+        };
+
+        // Parents are visited before their children, which keeps them first
+        // among entries with the same span once sorted.
+        let mut stack = Vec::new();
+
+        for record in ctx.records.keys() {
+            map.visit(ctx, record.into(), &mut stack);
+        }
+
+        for def in ctx.defs.keys() {
+            map.visit(ctx, def.into(), &mut stack);
+        }
+
+        map.spans.sort_by_key(|entry| {
+            (entry.file, entry.range.start, Reverse(entry.range.end))
+        });
+
+        let mut open: Vec<usize> = Vec::new();
+
+        for i in 0..map.spans.len() {
+            let entry = &map.spans[i];
+
+            while let Some(&top) = open.last() {
+                let top = &map.spans[top];
+
+                if top.file == entry.file && top.range.end >= entry.range.end {
+                    break;
+                }
+
+                open.pop();
+            }
+
+            map.spans[i].enclosing = open.last().copied();
+            open.push(i);
+        }
+
+        map
+    }
+
+    /// Returns the node that contains `node`, or `None` for records and
+    /// definitions.
+    pub fn parent(&self, node: impl Into<Node>) -> Option<Node> {
+        match node.into() {
+            Node::Record(_) | Node::Definition(_) => None,
+            Node::Field(field) => self.fields.get(field).copied(),
+            Node::Parameter(param) => self.params.get(param).copied(),
+            Node::Statement(stmt) => self.stmts.get(stmt).copied(),
+            Node::Expression(expr) => self.exprs.get(expr).copied(),
+            Node::Local(local) => self.locals.get(local).copied(),
+        }
+    }
+
+    /// Returns the definition that `node` is part of, if any.
+    pub fn enclosing_definition(
+        &self,
+        node: impl Into<Node>,
+    ) -> Option<Index<Definition>> {
+        let mut node = node.into();
+
+        loop {
+            match node {
+                Node::Definition(def) => return Some(def),
+                _ => node = self.parent(node)?,
+            }
+        }
+    }
+
+    /// Returns the innermost node whose span contains the byte `offset` of
+    /// `file`.
+    ///
+    /// Spans from lowering are nested, but inlining leaves expressions with
+    /// the spans of the callee. Where spans overlap without nesting, this
+    /// returns the one that starts last. An entry is only dropped from the
+    /// `enclosing` chain by a later entry that ends after it, so that entry
+    /// contains every offset of the dropped one past its own start.
+    pub fn node_at(&self, file: FileId, offset: usize) -> Option<Node> {
+        let end = self.spans.partition_point(|entry| {
+            (entry.file, entry.range.start) <= (file, offset)
+        });
+
+        let mut i = end.checked_sub(1)?;
+
+        loop {
+            let entry = &self.spans[i];
+
+            if entry.file == file && entry.range.contains(&offset) {
+                return Some(entry.node);
+            }
+
+            i = entry.enclosing?;
+        }
+    }
+
+    fn visit(
+        &mut self,
+        ctx: &Context,
+        root: Node,
+        stack: &mut Vec<(Node, Node)>,
+    ) {
+        self.record(ctx, root, None);
+        NodeMap::push_children(ctx, root, stack);
+
+        while let Some((node, parent)) = stack.pop() {
+            self.record(ctx, node, Some(parent));
+            NodeMap::push_children(ctx, node, stack);
+        }
+    }
+
+    fn record(&mut self, ctx: &Context, node: Node, parent: Option<Node>) {
+        let span = match node {
+            Node::Record(record) => Some(ctx[record].span),
+            Node::Field(field) => Some(ctx[field].span),
+            Node::Definition(def) => Some(ctx[def].span),
+            Node::Parameter(param) => Some(ctx[param].span),
+            Node::Statement(_) => None,
+            Node::Expression(expr) => Some(ctx[expr].span),
+            Node::Local(local) => Some(ctx[local].name.span),
+        };
+
+        if let Some(span) = span {
+            self.add_span(span, node);
+        }
+
+        let Some(parent) = parent else {
+            return;
+        };
+
+        match node {
+            Node::Record(_) | Node::Definition(_) => {}
+            Node::Field(field) => {
+                self.fields.insert(field, parent);
+            }
+            Node::Parameter(param) => {
+                self.params.insert(param, parent);
+            }
+            Node::Statement(stmt) => {
+                self.stmts.insert(stmt, parent);
+            }
+            Node::Expression(expr) => {
+                self.exprs.insert(expr, parent);
+            }
+            Node::Local(local) => {
+                self.locals.insert(local, parent);
+            }
+        }
+    }
+
+    fn push_children(ctx: &Context, node: Node, stack: &mut Vec<(Node, Node)>) {
+        let mut children = Vec::new();
+
+        match node {
+            Node::Record(record) => {
+                let record = &ctx[record];
+
+                children.extend(record.params.into_iter().map(Node::from));
+                children.extend(record.fields.into_iter().map(Node::from));
+            }
+            Node::Field(_) | Node::Local(_) => {}
+            Node::Definition(def) => {
+                let def = &ctx[def];
+
+                children.extend(def.generics.into_iter().map(Node::from));
+                children.extend(def.inputs.into_iter().map(Node::from));
+
+                for expr in [def.requires, def.implements].into_iter().flatten()
+                {
+                    children.push(expr.get().into());
+                }
+
+                if let Some(body) = def.body {
+                    children
+                        .extend(ctx[body].iter().map(|&stmt| Node::from(stmt)));
+                }
+            }
+            Node::Parameter(param) => children.push(ctx[param].local.into()),
+            Node::Statement(stmt) => match ctx[stmt].kind {
+                StmtKind::Assign(local, expr) => {
+                    children.push(local.into());
+                    children.push(expr.into());
+                }
+                StmtKind::Return(expr) => children.push(expr.into()),
+                StmtKind::Unsafe(block) => {
+                    children.extend(
+                        ctx[block].iter().map(|&stmt| Node::from(stmt)),
+                    );
+                }
+            },
+            Node::Expression(expr) => match &ctx[expr].kind {
+                ExprKind::Id(_) | ExprKind::Lit(_) => {}
+                ExprKind::Field(base, _) => children.push((*base).into()),
+                ExprKind::Unary(_, operand) => children.push((*operand).into()),
+                ExprKind::Binary(_, lhs, rhs) => {
+                    children.push((*lhs).into());
+                    children.push((*rhs).into());
+                }
+                ExprKind::Call(call) => {
+                    let exprs =
+                        ctx[call.generics].iter().chain(&ctx[call.args]);
+                    children.extend(exprs.map(|&expr| Node::from(expr)));
+                }
+                ExprKind::Record(cons) => {
+                    let exprs =
+                        ctx[cons.generics].iter().chain(&ctx[cons.inits]);
+                    children.extend(exprs.map(|&expr| Node::from(expr)));
+                }
+            },
+        }
+
+        // Reversed so that children are popped in source order.
+        stack.extend(children.into_iter().rev().map(|child| (child, node)));
+    }
+
+    fn add_span(&mut self, span: Span, node: Node) {
+        self.spans.push(SpanEntry {
+            file: span.file(),
+            range: Range::from(span),
+            node,
+            enclosing: None,
+        });
+    }
+}
@@ -0,0 +1,1020 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::{error, fmt, str};
+
+use adpl_arena::{Index, IndexArena, IndexRange, List, NonMaxIndex};
+use adpl_util::{FileId, with_sufficient_stack};
+
+use crate::{
+    BinaryKind, BinaryOp, Call, ConstArg, Constructor, Context, Definition,
+    ExprKind, Expression, Field, Id, Inline, Literal, Local, LocalKind,
+    Parameter, Record, Safety, Span, Statement, StmtKind, Symbol, Symbols,
+    Type, TypeKind, TypeRef, UnaryKind, UnaryOp,
+};
+
+const MAGIC: &[u8; 8] = b"ADPLHIR\0";
+
+/// The version of the format written by [`Context::encode`], bumped whenever
+/// the layout of the HIR changes.
+pub const FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    NotHir,
+    Version(u32),
+    Malformed(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => err.fmt(f),
+            DecodeError::NotHir => f.write_str("not a serialized HIR context"),
+            DecodeError::Version(version) => write!(
+                f,
+                "unsupported format version {}, expected {}",
+                version, FORMAT_VERSION,
+            ),
+            DecodeError::Malformed(what) => {
+                write!(f, "malformed HIR: {}", what)
+            }
+        }
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, DecodeError>;
+
+impl Context {
+    /// Writes the context and the symbols it refers to in a compact binary
+    /// format that can be read back with [`Context::decode`].
+    ///
+    /// Spans are written as is, so they are only meaningful if the same files
+    /// are added to the [`Files`](adpl_util::Files) database in the same
+    /// order.
+    pub fn encode<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut body = Encoder::default();
+        let lists = self.lists.list::<()>(0..self.lists.len()).unwrap();
+
+        for &index in &self[lists] {
+            body.index(index);
+        }
+
+        for record in self.records.values() {
+            body.record(record)?;
+        }
+
+        for field in self.fields.values() {
+            body.field(field)?;
+        }
+
+        for def in self.defs.values() {
+            body.definition(def)?;
+        }
+
+        for param in self.params.values() {
+            body.parameter(param)?;
+        }
+
+        for stmt in self.stmts.values() {
+            body.statement(stmt);
+        }
+
+        for expr in self.exprs.values() {
+            body.expression(expr)?;
+        }
+
+        for ty in self.types.values() {
+            body.ty(ty)?;
+        }
+
+        for local in self.locals.values() {
+            body.local(local)?;
+        }
+
+        let mut header = Encoder::default();
+        header.buf.extend_from_slice(MAGIC);
+        header.u32(FORMAT_VERSION);
+        header.len(body.names.len())?;
+
+        for symbol in &body.names {
+            let name = &self.symbols[*symbol];
+
+            header.len(name.len())?;
+            header.buf.extend_from_slice(name.as_bytes());
+        }
+
+        for len in [
+            self.lists.len(),
+            self.records.len(),
+            self.fields.len(),
+            self.defs.len(),
+            self.params.len(),
+            self.stmts.len(),
+            self.exprs.len(),
+            self.types.len(),
+            self.locals.len(),
+        ] {
+            header.len(len)?;
+        }
+
+        writer.write_all(&header.buf)?;
+        writer.write_all(&body.buf)
+    }
+
+    /// Reads a context written by [`Context::encode`], checking that every
+    /// index and list in it is in bounds, that types, calls and constructors
+    /// have as many arguments as they expect, that every parameter is one of
+    /// the enclosing item, and that no expression or statement contains
+    /// itself. Its symbols are interned into `symbols`.
+    pub fn decode<R: Read>(
+        mut reader: R,
+        symbols: &Symbols,
+    ) -> Result<Context> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(DecodeError::Io)?;
+
+        let mut decoder = Decoder {
+            data: &data,
+            symbols: Vec::new(),
+            lens: Lens::default(),
+        };
+
+        if decoder.bytes(MAGIC.len()).ok() != Some(MAGIC) {
+            return Err(DecodeError::NotHir);
+        }
+
+        let version = decoder.u32()?;
+
+        if version != FORMAT_VERSION {
+            return Err(DecodeError::Version(version));
+        }
+
+        for _ in 0..decoder.len()? {
+            let len = decoder.len()?;
+            let name = str::from_utf8(decoder.bytes(len)?)
+                .map_err(|_| DecodeError::Malformed("invalid symbol"))?;
+
+            decoder.symbols.push(symbols.intern(name));
+        }
+
+        let lists = decoder.len()?;
+
+        decoder.lens = Lens {
+            records: decoder.len()?,
+            fields: decoder.len()?,
+            defs: decoder.len()?,
+            params: decoder.len()?,
+            stmts: decoder.len()?,
+            exprs: decoder.len()?,
+            types: decoder.len()?,
+            locals: decoder.len()?,
+        };
+
+        let mut ctx = Context::new(symbols.clone());
+
+        if decoder.data.len() / 4 < lists {
+            return Err(DecodeError::Malformed("unexpected end of input"));
+        }
+
+        let lists = (0..lists)
+            .map(|_| {
+                Index::<()>::new(decoder.u32()?)
+                    .ok_or(DecodeError::Malformed("invalid index"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        ctx.lists.extend(lists);
+
+        for _ in 0..decoder.lens.records {
+            let record = decoder.record()?;
+            ctx.records.push(record);
+        }
+
+        for _ in 0..decoder.lens.fields {
+            let field = decoder.field()?;
+            ctx.fields.push(field);
+        }
+
+        for _ in 0..decoder.lens.defs {
+            let def = decoder.definition(&ctx.lists)?;
+            ctx.defs.push(def);
+        }
+
+        for _ in 0..decoder.lens.params {
+            let param = decoder.parameter()?;
+            ctx.params.push(param);
+        }
+
+        for _ in 0..decoder.lens.stmts {
+            let stmt = decoder.statement(&ctx.lists)?;
+            ctx.stmts.push(stmt);
+        }
+
+        for _ in 0..decoder.lens.exprs {
+            let expr = decoder.expression(&ctx.lists)?;
+            ctx.exprs.push(expr);
+        }
+
+        for _ in 0..decoder.lens.types {
+            let ty = decoder.ty()?;
+
+            if ctx.types.intern(ty).index() + 1 != ctx.types.len() {
+                return Err(DecodeError::Malformed("duplicate type"));
+            }
+        }
+
+        for _ in 0..decoder.lens.locals {
+            let local = decoder.local()?;
+            ctx.locals.push(local);
+        }
+
+        if !decoder.data.is_empty() {
+            return Err(DecodeError::Malformed("trailing data"));
+        }
+
+        validate(&ctx)?;
+
+        Ok(ctx)
+    }
+}
+
+fn validate(ctx: &Context) -> Result<()> {
+    for ty in ctx.types.values() {
+        let params = match ty.kind {
+            TypeKind::UInt => 1,
+            TypeKind::Record(record) => ctx[record].params.len(),
+        };
+
+        if ty.args.len() != params {
+            return Err(DecodeError::Malformed(
+                "wrong number of type arguments",
+            ));
+        }
+    }
+
+    for record in ctx.records.values() {
+        for field in &ctx.fields[record.fields] {
+            type_ref(ctx, field.ty, record.params.len())?;
+        }
+    }
+
+    for def in ctx.defs.values() {
+        let generics = def.generics.len();
+
+        let mut validator = Validator {
+            ctx,
+            bound: HashSet::new(),
+            exprs: HashMap::new(),
+            stmts: HashMap::new(),
+        };
+
+        for (i, local) in def.generics.into_iter().enumerate() {
+            validator.bind(local, LocalKind::GenericParam(i as u16))?;
+        }
+
+        for (i, param) in ctx.params[def.inputs].iter().enumerate() {
+            type_ref(ctx, param.ty, generics)?;
+            validator.bind(param.local, LocalKind::Param(i as u16))?;
+        }
+
+        type_ref(ctx, def.output, generics)?;
+
+        for expr in def.requires.into_iter().chain(def.implements) {
+            validator.expression(expr.get())?;
+        }
+
+        if let Some(body) = def.body {
+            validator.block(body)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the generic arguments of `ty` only refer to the first
+/// `generics` generic parameters.
+fn type_ref(ctx: &Context, ty: TypeRef, generics: usize) -> Result<()> {
+    ctx[ty.ty]
+        .args
+        .iter()
+        .try_for_each(|arg| const_arg(arg, generics))
+}
+
+fn const_arg(arg: &ConstArg, generics: usize) -> Result<()> {
+    with_sufficient_stack(|| match arg {
+        ConstArg::Value(_) => Ok(()),
+        ConstArg::Param(i) if (*i as usize) < generics => Ok(()),
+        ConstArg::Param(_) => {
+            Err(DecodeError::Malformed("generic parameter out of range"))
+        }
+        ConstArg::Unary(_, operand) => const_arg(operand, generics),
+        ConstArg::Binary(_, lhs, rhs) => {
+            const_arg(lhs, generics)?;
+            const_arg(rhs, generics)
+        }
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Walks a definition in order, checking that every local is bound before it
+/// is used.
+struct Validator<'a> {
+    ctx: &'a Context,
+    bound: HashSet<Index<Local>>,
+    exprs: HashMap<Index<Expression>, Visit>,
+    stmts: HashMap<Index<Statement>, Visit>,
+}
+
+impl Validator<'_> {
+    fn bind(&mut self, local: Index<Local>, kind: LocalKind) -> Result<()> {
+        if self.ctx[local].kind != kind || !self.bound.insert(local) {
+            return Err(DecodeError::Malformed("invalid binding"));
+        }
+
+        Ok(())
+    }
+
+    fn block(&mut self, block: List<Statement>) -> Result<()> {
+        for &stmt in &self.ctx[block] {
+            match self.stmts.insert(stmt, Visit::InProgress) {
+                Some(Visit::Done) => {
+                    self.stmts.insert(stmt, Visit::Done);
+                    continue;
+                }
+                Some(Visit::InProgress) => {
+                    return Err(DecodeError::Malformed("cyclic statement"));
+                }
+                None => {}
+            }
+
+            match self.ctx[stmt].kind {
+                StmtKind::Assign(local, expr) => {
+                    self.expression(expr)?;
+                    self.bind(local, LocalKind::Let(expr))?;
+                }
+                StmtKind::Return(expr) => self.expression(expr)?,
+                StmtKind::Unsafe(block) => {
+                    with_sufficient_stack(|| self.block(block))?
+                }
+            }
+
+            self.stmts.insert(stmt, Visit::Done);
+        }
+
+        Ok(())
+    }
+
+    fn expression(&mut self, index: Index<Expression>) -> Result<()> {
+        match self.exprs.insert(index, Visit::InProgress) {
+            Some(Visit::Done) => {
+                self.exprs.insert(index, Visit::Done);
+                return Ok(());
+            }
+            Some(Visit::InProgress) => {
+                return Err(DecodeError::Malformed("cyclic expression"));
+            }
+            None => {}
+        }
+
+        with_sufficient_stack(|| match self.ctx[index].kind {
+            ExprKind::Id(local) if self.bound.contains(&local) => Ok(()),
+            ExprKind::Id(_) => Err(DecodeError::Malformed("unbound local")),
+            ExprKind::Lit(_) => Ok(()),
+            ExprKind::Field(base, _) => self.expression(base),
+            ExprKind::Unary(_, operand) => self.expression(operand),
+            ExprKind::Binary(_, lhs, rhs) => {
+                self.expression(lhs)?;
+                self.expression(rhs)
+            }
+            ExprKind::Call(ref call) => {
+                let callee = &self.ctx[call.callee];
+
+                self.arguments(call.generics, callee.generics.len())?;
+                self.arguments(call.args, callee.inputs.len())
+            }
+            ExprKind::Record(ref cons) => {
+                let record = &self.ctx[cons.record];
+
+                self.arguments(cons.generics, record.params.len())?;
+                self.arguments(cons.inits, record.fields.len())
+            }
+        })?;
+
+        self.exprs.insert(index, Visit::Done);
+
+        Ok(())
+    }
+
+    fn arguments(&mut self, args: List<Expression>, len: usize) -> Result<()> {
+        if args.len() != len {
+            return Err(DecodeError::Malformed("wrong number of arguments"));
+        }
+
+        for i in 0..args.len() {
+            self.expression(self.ctx[args][i])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+    symbols: HashMap<Symbol, u32>,
+    names: Vec<Symbol>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) -> io::Result<()> {
+        self.u32(u32::try_from(len).map_err(io::Error::other)?);
+        Ok(())
+    }
+
+    fn span(&mut self, span: Span) -> io::Result<()> {
+        let range = Range::from(span);
+
+        self.u32(span.file().as_u32());
+        self.len(range.start)?;
+        self.len(range.end)
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        let id = match self.symbols.get(&symbol) {
+            Some(&id) => id,
+            None => {
+                let id = self.names.len() as u32;
+                self.symbols.insert(symbol, id);
+                self.names.push(symbol);
+
+                id
+            }
+        };
+
+        self.u32(id);
+    }
+
+    fn id(&mut self, id: Id) -> io::Result<()> {
+        self.symbol(id.symbol);
+        self.span(id.span)
+    }
+
+    fn index<T>(&mut self, index: Index<T>) {
+        self.u32(index.index() as u32);
+    }
+
+    fn range<T>(&mut self, range: IndexRange<T>) {
+        self.index(range.start);
+        self.index(range.end);
+    }
+
+    fn list<T>(&mut self, list: List<T>) {
+        let range = list.range();
+
+        self.u32(range.start as u32);
+        self.u32(range.end as u32);
+    }
+
+    fn optional<T>(&mut self, index: Option<NonMaxIndex<T>>) {
+        match index {
+            Some(index) => self.index(index.get()),
+            None => self.u32(u32::MAX),
+        }
+    }
+
+    fn record(&mut self, record: &Record) -> io::Result<()> {
+        self.id(record.name)?;
+        self.range(record.params);
+        self.range(record.fields);
+        self.span(record.span)
+    }
+
+    fn field(&mut self, field: &Field) -> io::Result<()> {
+        self.id(field.name)?;
+        self.type_ref(field.ty)?;
+        self.span(field.span)
+    }
+
+    fn definition(&mut self, def: &Definition) -> io::Result<()> {
+        self.u8(match def.safety {
+            Safety::Unsafe => 0,
+            Safety::Safe => 1,
+        });
+        self.u8(match def.inline {
+            Inline::Auto => 0,
+            Inline::Always => 1,
+            Inline::Never => 2,
+        });
+        self.id(def.name)?;
+        self.range(def.generics);
+        self.optional(def.requires);
+        self.optional(def.implements);
+        self.range(def.inputs);
+        self.type_ref(def.output)?;
+
+        match def.body {
+            Some(body) => {
+                self.u8(1);
+                self.list(body);
+            }
+            None => self.u8(0),
+        }
+
+        self.span(def.span)
+    }
+
+    fn parameter(&mut self, param: &Parameter) -> io::Result<()> {
+        self.index(param.local);
+        self.type_ref(param.ty)?;
+        self.span(param.span)
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt.kind {
+            StmtKind::Assign(local, expr) => {
+                self.u8(0);
+                self.index(local);
+                self.index(expr);
+            }
+            StmtKind::Return(expr) => {
+                self.u8(1);
+                self.index(expr);
+            }
+            StmtKind::Unsafe(stmts) => {
+                self.u8(2);
+                self.list(stmts);
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> io::Result<()> {
+        match &expr.kind {
+            ExprKind::Id(local) => {
+                self.u8(0);
+                self.index(*local);
+            }
+            ExprKind::Lit(literal) => {
+                self.u8(1);
+                self.u64(literal.value);
+            }
+            ExprKind::Field(expr, name) => {
+                self.u8(2);
+                self.index(*expr);
+                self.id(*name)?;
+            }
+            ExprKind::Unary(op, operand) => {
+                self.u8(3);
+                self.u8(op.kind as u8);
+                self.span(op.span)?;
+                self.index(*operand);
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                self.u8(4);
+                self.u8(op.kind as u8);
+                self.span(op.span)?;
+                self.index(*lhs);
+                self.index(*rhs);
+            }
+            ExprKind::Call(call) => {
+                self.u8(5);
+                self.id(call.name)?;
+                self.index(call.callee);
+                self.list(call.generics);
+                self.list(call.args);
+            }
+            ExprKind::Record(constructor) => {
+                self.u8(6);
+                self.id(constructor.name)?;
+                self.index(constructor.record);
+                self.list(constructor.generics);
+                self.list(constructor.inits);
+            }
+        }
+
+        self.span(expr.span)
+    }
+
+    fn ty(&mut self, ty: &Type) -> io::Result<()> {
+        match ty.kind {
+            TypeKind::UInt => self.u8(0),
+            TypeKind::Record(record) => {
+                self.u8(1);
+                self.index(record);
+            }
+        }
+
+        self.len(ty.args.len())?;
+
+        for arg in &ty.args {
+            self.const_arg(arg);
+        }
+
+        Ok(())
+    }
+
+    fn const_arg(&mut self, arg: &ConstArg) {
+        with_sufficient_stack(|| match arg {
+            ConstArg::Value(value) => {
+                self.u8(0);
+                self.u64(*value);
+            }
+            ConstArg::Param(i) => {
+                self.u8(1);
+                self.u16(*i);
+            }
+            ConstArg::Unary(kind, operand) => {
+                self.u8(2);
+                self.u8(*kind as u8);
+                self.const_arg(operand);
+            }
+            ConstArg::Binary(kind, lhs, rhs) => {
+                self.u8(3);
+                self.u8(*kind as u8);
+                self.const_arg(lhs);
+                self.const_arg(rhs);
+            }
+        })
+    }
+
+    fn type_ref(&mut self, ty: TypeRef) -> io::Result<()> {
+        self.index(ty.ty);
+        self.span(ty.span)
+    }
+
+    fn local(&mut self, local: &Local) -> io::Result<()> {
+        match local.kind {
+            LocalKind::Let(expr) => {
+                self.u8(0);
+                self.index(expr);
+            }
+            LocalKind::Param(i) => {
+                self.u8(1);
+                self.u16(i);
+            }
+            LocalKind::GenericParam(i) => {
+                self.u8(2);
+                self.u16(i);
+            }
+        }
+
+        self.id(local.name)
+    }
+}
+
+#[derive(Default)]
+struct Lens {
+    records: usize,
+    fields: usize,
+    defs: usize,
+    params: usize,
+    stmts: usize,
+    exprs: usize,
+    types: usize,
+    locals: usize,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    symbols: Vec<Symbol>,
+    lens: Lens,
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(DecodeError::Malformed("unexpected end of input"));
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn tag(&mut self, count: u8) -> Result<u8> {
+        let tag = self.u8()?;
+
+        if tag >= count {
+            return Err(DecodeError::Malformed("invalid tag"));
+        }
+
+        Ok(tag)
+    }
+
+    fn span(&mut self) -> Result<Span> {
+        let file = FileId::from_u32(self.u32()?);
+        let (start, end) = (self.len()?, self.len()?);
+
+        if start > end {
+            return Err(DecodeError::Malformed("invalid span"));
+        }
+
+        Ok(Span::new(file, start, end))
+    }
+
+    fn id(&mut self) -> Result<Id> {
+        let symbol = self.len()?;
+        let symbol = *self
+            .symbols
+            .get(symbol)
+            .ok_or(DecodeError::Malformed("symbol out of range"))?;
+
+        Ok(Id {
+            symbol,
+            span: self.span()?,
+        })
+    }
+
+    fn index<T>(&mut self, len: usize) -> Result<Index<T>> {
+        let index = self.u32()?;
+
+        if index as usize >= len {
+            return Err(DecodeError::Malformed("index out of range"));
+        }
+
+        Ok(Index::new(index).unwrap())
+    }
+
+    fn range<T>(&mut self, len: usize) -> Result<IndexRange<T>> {
+        let (start, end) = (self.u32()?, self.u32()?);
+
+        if start > end || end as usize > len {
+            return Err(DecodeError::Malformed("index out of range"));
+        }
+
+        Ok(IndexRange {
+            start: Index::new(start).unwrap(),
+            end: Index::new(end).unwrap(),
+        })
+    }
+
+    fn list<T>(&mut self, lists: &IndexArena, len: usize) -> Result<List<T>> {
+        let (start, end) = (self.len()?, self.len()?);
+
+        let list = lists
+            .list(start..end)
+            .ok_or(DecodeError::Malformed("list out of range"))?;
+
+        if lists[list].iter().any(|index| index.index() >= len) {
+            return Err(DecodeError::Malformed("index out of range"));
+        }
+
+        Ok(list)
+    }
+
+    fn optional<T>(&mut self, len: usize) -> Result<Option<NonMaxIndex<T>>> {
+        let index = self.u32()?;
+
+        if index == u32::MAX {
+            return Ok(None);
+        }
+
+        if index as usize >= len {
+            return Err(DecodeError::Malformed("index out of range"));
+        }
+
+        Ok(Index::new(index).and_then(NonMaxIndex::new))
+    }
+
+    fn record(&mut self) -> Result<Record> {
+        Ok(Record {
+            name: self.id()?,
+            params: self.range(self.lens.locals)?,
+            fields: self.range(self.lens.fields)?,
+            span: self.span()?,
+        })
+    }
+
+    fn field(&mut self) -> Result<Field> {
+        Ok(Field {
+            name: self.id()?,
+            ty: self.type_ref()?,
+            span: self.span()?,
+        })
+    }
+
+    fn definition(&mut self, lists: &IndexArena) -> Result<Definition> {
+        let safety = match self.tag(2)? {
+            0 => Safety::Unsafe,
+            _ => Safety::Safe,
+        };
+
+        let inline = match self.tag(3)? {
+            0 => Inline::Auto,
+            1 => Inline::Always,
+            _ => Inline::Never,
+        };
+
+        Ok(Definition {
+            safety,
+            inline,
+            name: self.id()?,
+            generics: self.range(self.lens.locals)?,
+            requires: self.optional(self.lens.exprs)?,
+            implements: self.optional(self.lens.exprs)?,
+            inputs: self.range(self.lens.params)?,
+            output: self.type_ref()?,
+            body: match self.tag(2)? {
+                0 => None,
+                _ => Some(self.list(lists, self.lens.stmts)?),
+            },
+            span: self.span()?,
+        })
+    }
+
+    fn parameter(&mut self) -> Result<Parameter> {
+        Ok(Parameter {
+            local: self.index(self.lens.locals)?,
+            ty: self.type_ref()?,
+            span: self.span()?,
+        })
+    }
+
+    fn statement(&mut self, lists: &IndexArena) -> Result<Statement> {
+        let kind = match self.tag(3)? {
+            0 => StmtKind::Assign(
+                self.index(self.lens.locals)?,
+                self.index(self.lens.exprs)?,
+            ),
+            1 => StmtKind::Return(self.index(self.lens.exprs)?),
+            _ => StmtKind::Unsafe(self.list(lists, self.lens.stmts)?),
+        };
+
+        Ok(Statement { kind })
+    }
+
+    fn expression(&mut self, lists: &IndexArena) -> Result<Expression> {
+        let kind = match self.tag(7)? {
+            0 => ExprKind::Id(self.index(self.lens.locals)?),
+            1 => ExprKind::Lit(Literal { value: self.u64()? }),
+            2 => ExprKind::Field(self.index(self.lens.exprs)?, self.id()?),
+            3 => {
+                let op = UnaryOp {
+                    kind: self.unary_kind()?,
+                    span: self.span()?,
+                };
+
+                ExprKind::Unary(op, self.index(self.lens.exprs)?)
+            }
+            4 => {
+                let op = BinaryOp {
+                    kind: self.binary_kind()?,
+                    span: self.span()?,
+                };
+
+                ExprKind::Binary(
+                    op,
+                    self.index(self.lens.exprs)?,
+                    self.index(self.lens.exprs)?,
+                )
+            }
+            5 => ExprKind::Call(Call {
+                name: self.id()?,
+                callee: self.index(self.lens.defs)?,
+                generics: self.list(lists, self.lens.exprs)?,
+                args: self.list(lists, self.lens.exprs)?,
+            }),
+            _ => ExprKind::Record(Constructor {
+                name: self.id()?,
+                record: self.index(self.lens.records)?,
+                generics: self.list(lists, self.lens.exprs)?,
+                inits: self.list(lists, self.lens.exprs)?,
+            }),
+        };
+
+        Ok(Expression {
+            kind,
+            span: self.span()?,
+        })
+    }
+
+    fn unary_kind(&mut self) -> Result<UnaryKind> {
+        Ok(match self.tag(2)? {
+            0 => UnaryKind::Neg,
+            _ => UnaryKind::Not,
+        })
+    }
+
+    fn binary_kind(&mut self) -> Result<BinaryKind> {
+        const KINDS: [BinaryKind; 13] = [
+            BinaryKind::Add,
+            BinaryKind::Sub,
+            BinaryKind::Mul,
+            BinaryKind::Div,
+            BinaryKind::Pow,
+            BinaryKind::Shl,
+            BinaryKind::Shr,
+            BinaryKind::Eq,
+            BinaryKind::Ne,
+            BinaryKind::Gt,
+            BinaryKind::Ge,
+            BinaryKind::Lt,
+            BinaryKind::Le,
+        ];
+
+        Ok(KINDS[self.tag(KINDS.len() as u8)? as usize])
+    }
+
+    fn ty(&mut self) -> Result<Type> {
+        let kind = match self.tag(2)? {
+            0 => TypeKind::UInt,
+            _ => TypeKind::Record(self.index(self.lens.records)?),
+        };
+
+        let args = (0..self.len()?)
+            .map(|_| self.const_arg())
+            .collect::<Result<_>>()?;
+
+        Ok(Type { kind, args })
+    }
+
+    fn const_arg(&mut self) -> Result<ConstArg> {
+        with_sufficient_stack(|| {
+            Ok(match self.tag(4)? {
+                0 => ConstArg::Value(self.u64()?),
+                1 => ConstArg::Param(self.u16()?),
+                2 => ConstArg::Unary(
+                    self.unary_kind()?,
+                    Box::new(self.const_arg()?),
+                ),
+                _ => ConstArg::Binary(
+                    self.binary_kind()?,
+                    Box::new(self.const_arg()?),
+                    Box::new(self.const_arg()?),
+                ),
+            })
+        })
+    }
+
+    fn type_ref(&mut self) -> Result<TypeRef> {
+        Ok(TypeRef {
+            ty: self.index(self.lens.types)?,
+            span: self.span()?,
+        })
+    }
+
+    fn local(&mut self) -> Result<Local> {
+        let kind = match self.tag(3)? {
+            0 => LocalKind::Let(self.index(self.lens.exprs)?),
+            1 => LocalKind::Param(self.u16()?),
+            _ => LocalKind::GenericParam(self.u16()?),
+        };
+
+        Ok(Local {
+            kind,
+            name: self.id()?,
+        })
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use adpl_arena::{Index, IndexRange};
+use adpl_util::{
+    Applicability, Diagnostic, codes, find_best_match, with_sufficient_stack,
+};
+
+use crate::{
+    BinaryKind, ConstArg, Context, Definition, ExprKind, Expression, Field, Id,
+    List, Local, LocalKind, Record, Span, TypeKind, TypeRef, eval,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Const {
+    Value(u64),
+    Param(u16),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Int,
+    UInt(Const),
+    Record(Index<Record>, Box<[Const]>),
+}
+
+impl Ty {
+    pub fn display<'a>(
+        &'a self,
+        ctx: &'a Context,
+        generics: IndexRange<Local>,
+    ) -> DisplayTy<'a> {
+        DisplayTy {
+            ty: self,
+            ctx,
+            generics,
+        }
+    }
+}
+
+pub struct DisplayTy<'a> {
+    ty: &'a Ty,
+    ctx: &'a Context,
+    generics: IndexRange<Local>,
+}
+
+impl DisplayTy<'_> {
+    fn fmt_const(&self, f: &mut fmt::Formatter, value: Const) -> fmt::Result {
+        match value {
+            Const::Value(value) => write!(f, "{}", value),
+            Const::Param(i) => {
+                let local = &self.ctx.locals[self.generics][i as usize];
+                write!(f, "{}", &self.ctx.symbols[local.name.symbol])
+            }
+        }
+    }
+}
+
+impl fmt::Display for DisplayTy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ty {
+            Ty::Int => write!(f, "{{integer}}"),
+            Ty::UInt(width) => {
+                write!(f, "uint[")?;
+                self.fmt_const(f, *width)?;
+                write!(f, "]")
+            }
+            Ty::Record(record, args) => {
+                let name = self.ctx[*record].name.symbol;
+                write!(f, "{}", &self.ctx.symbols[name])?;
+
+                if !args.is_empty() {
+                    write!(f, "[")?;
+
+                    for (i, &arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+
+                        self.fmt_const(f, arg)?;
+                    }
+
+                    write!(f, "]")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TyError {
+    pub kind: TyErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum TyErrorKind {
+    NotConst,
+    NotInteger(Ty),
+    NoField(Ty, Id),
+    Mismatch(Ty, Ty),
+}
+
+impl TyError {
+    pub fn to_diagnostic(
+        &self,
+        ctx: &Context,
+        generics: IndexRange<Local>,
+    ) -> Diagnostic {
+        let display = |ty: &Ty| ty.display(ctx, generics).to_string();
+
+        match &self.kind {
+            TyErrorKind::NotConst => Diagnostic::error()
+                .with_code(codes::E0017)
+                .with_message("expected a compile-time constant")
+                .with_primary(self.span, "not a constant"),
+            TyErrorKind::NotInteger(ty) => Diagnostic::error()
+                .with_code(codes::E0018)
+                .with_message(format!(
+                    "expected an integer, found `{}`",
+                    display(ty),
+                ))
+                .with_primary(self.span, "not an integer"),
+            TyErrorKind::NoField(ty, name) => {
+                let diagnostic = Diagnostic::error()
+                    .with_code(codes::E0019)
+                    .with_message(format!(
+                        "no field `{}` on type `{}`",
+                        &ctx.symbols[name.symbol],
+                        display(ty),
+                    ))
+                    .with_primary(self.span, "unknown field");
+
+                let Ty::Record(record, _) = *ty else {
+                    return diagnostic;
+                };
+
+                let similar = find_best_match(
+                    ctx.fields[ctx[record].fields]
+                        .iter()
+                        .map(|field| &ctx.symbols[field.name.symbol]),
+                    &ctx.symbols[name.symbol],
+                );
+
+                match similar {
+                    Some(similar) => diagnostic.with_suggestion(
+                        self.span,
+                        similar,
+                        format!(
+                            "a field with a similar name exists: `{}`",
+                            similar,
+                        ),
+                        Applicability::MaybeIncorrect,
+                    ),
+                    None => diagnostic,
+                }
+            }
+            TyErrorKind::Mismatch(expected, found) => Diagnostic::error()
+                .with_code(codes::E0020)
+                .with_message(format!(
+                    "mismatched types `{}` and `{}`",
+                    display(expected),
+                    display(found),
+                ))
+                .with_primary(self.span, "types do not match"),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, TyError>;
+
+impl Context {
+    pub fn resolve_type(&self, ty: TypeRef, env: &[Const]) -> Result<Ty> {
+        let args = self[ty.ty]
+            .args
+            .iter()
+            .map(|arg| {
+                substitute(arg, env).ok_or(TyError {
+                    kind: TyErrorKind::NotConst,
+                    span: ty.span,
+                })
+            })
+            .collect::<Result<Box<[Const]>>>()?;
+
+        Ok(match self[ty.ty].kind {
+            TypeKind::UInt => Ty::UInt(args[0]),
+            TypeKind::Record(record) => Ty::Record(record, args),
+        })
+    }
+
+    /// Folds the generic argument `expr` of a type into a [`ConstArg`] that
+    /// only refers to generic parameters.
+    pub fn normalize_const(&self, expr: Index<Expression>) -> Result<ConstArg> {
+        with_sufficient_stack(|| {
+            let expr = &self[expr];
+
+            let not_const = || TyError {
+                kind: TyErrorKind::NotConst,
+                span: expr.span,
+            };
+
+            match &expr.kind {
+                ExprKind::Lit(literal) => Ok(ConstArg::Value(literal.value)),
+                ExprKind::Id(local) => match self[*local].kind {
+                    LocalKind::GenericParam(i) => Ok(ConstArg::Param(i)),
+                    LocalKind::Let(expr) => self.normalize_const(expr),
+                    LocalKind::Param(_) => Err(not_const()),
+                },
+                ExprKind::Unary(op, operand) => {
+                    Ok(match self.normalize_const(*operand)? {
+                        ConstArg::Value(value) => ConstArg::Value(
+                            eval::eval_unary(op.kind, value, u64::BITS),
+                        ),
+                        operand => ConstArg::Unary(op.kind, Box::new(operand)),
+                    })
+                }
+                ExprKind::Binary(op, lhs, rhs) => Ok(
+                    match (
+                        self.normalize_const(*lhs)?,
+                        self.normalize_const(*rhs)?,
+                    ) {
+                        (ConstArg::Value(lhs), ConstArg::Value(rhs)) => {
+                            ConstArg::Value(eval::eval_binary(
+                                op.kind,
+                                lhs,
+                                rhs,
+                                u64::BITS,
+                            ))
+                        }
+                        (lhs, rhs) => ConstArg::Binary(
+                            op.kind,
+                            Box::new(lhs),
+                            Box::new(rhs),
+                        ),
+                    },
+                ),
+                _ => Err(not_const()),
+            }
+        })
+    }
+
+    pub fn eval_const(
+        &self,
+        expr: Index<Expression>,
+        env: &[Const],
+    ) -> Result<Const> {
+        with_sufficient_stack(|| {
+            let expr = &self[expr];
+
+            let not_const = || TyError {
+                kind: TyErrorKind::NotConst,
+                span: expr.span,
+            };
+
+            match &expr.kind {
+                ExprKind::Lit(literal) => Ok(Const::Value(literal.value)),
+                ExprKind::Id(local) => match self[*local].kind {
+                    LocalKind::GenericParam(i) => Ok(env[i as usize]),
+                    LocalKind::Let(expr) => self.eval_const(expr, env),
+                    LocalKind::Param(_) => Err(not_const()),
+                },
+                ExprKind::Unary(op, operand) => {
+                    match self.eval_const(*operand, env)? {
+                        Const::Value(value) => Ok(Const::Value(
+                            eval::eval_unary(op.kind, value, u64::BITS),
+                        )),
+                        Const::Param(_) => Err(not_const()),
+                    }
+                }
+                ExprKind::Binary(op, lhs, rhs) => {
+                    match (
+                        self.eval_const(*lhs, env)?,
+                        self.eval_const(*rhs, env)?,
+                    ) {
+                        (Const::Value(lhs), Const::Value(rhs)) => {
+                            Ok(Const::Value(eval::eval_binary(
+                                op.kind,
+                                lhs,
+                                rhs,
+                                u64::BITS,
+                            )))
+                        }
+                        _ => Err(not_const()),
+                    }
+                }
+                _ => Err(not_const()),
+            }
+        })
+    }
+
+    pub fn field_type(
+        &self,
+        record: Index<Record>,
+        args: &[Const],
+        name: &Id,
+    ) -> Option<(Index<Field>, Result<Ty>)> {
+        self[record]
+            .fields
+            .into_iter()
+            .find(|&field| self[field].name.symbol == name.symbol)
+            .map(|field| (field, self.resolve_type(self[field].ty, args)))
+    }
+}
+
+pub struct TypeCx<'a> {
+    ctx: &'a Context,
+    def: Index<Definition>,
+    generics: &'a [Const],
+    types: HashMap<Index<Expression>, Ty>,
+}
+
+impl<'a> TypeCx<'a> {
+    pub fn new(
+        ctx: &'a Context,
+        def: Index<Definition>,
+        generics: &'a [Const],
+    ) -> Self {
+        TypeCx {
+            ctx,
+            def,
+            generics,
+            types: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn generics(&self) -> &'a [Const] {
+        self.generics
+    }
+
+    #[inline]
+    pub fn def(&self) -> Index<Definition> {
+        self.def
+    }
+
+    pub fn resolve_type(&self, ty: TypeRef) -> Result<Ty> {
+        self.ctx.resolve_type(ty, self.generics)
+    }
+
+    pub fn eval_const(&self, expr: Index<Expression>) -> Result<Const> {
+        self.ctx.eval_const(expr, self.generics)
+    }
+
+    pub fn output_type(&self) -> Result<Ty> {
+        self.resolve_type(self.ctx[self.def].output)
+    }
+
+    pub fn local_type(&mut self, local: Index<Local>) -> Result<Ty> {
+        match self.ctx[local].kind {
+            LocalKind::Let(expr) => self.type_of(expr),
+            LocalKind::Param(i) => {
+                let inputs = self.ctx[self.def].inputs;
+                self.resolve_type(self.ctx.params[inputs][i as usize].ty)
+            }
+            LocalKind::GenericParam(_) => Ok(Ty::Int),
+        }
+    }
+
+    pub fn type_of(&mut self, expr: Index<Expression>) -> Result<Ty> {
+        if let Some(ty) = self.types.get(&expr) {
+            return Ok(ty.clone());
+        }
+
+        let ty = with_sufficient_stack(|| self.compute_type(expr))?;
+        self.types.insert(expr, ty.clone());
+
+        Ok(ty)
+    }
+
+    fn compute_type(&mut self, index: Index<Expression>) -> Result<Ty> {
+        let expr = &self.ctx[index];
+
+        match &expr.kind {
+            ExprKind::Id(local) => self.local_type(*local),
+            ExprKind::Lit(_) => Ok(Ty::Int),
+            ExprKind::Field(base, name) => match self.type_of(*base)? {
+                Ty::Record(record, args) => {
+                    match self.ctx.field_type(record, &args, name) {
+                        Some((_, ty)) => ty,
+                        None => Err(TyError {
+                            kind: TyErrorKind::NoField(
+                                Ty::Record(record, args),
+                                *name,
+                            ),
+                            span: name.span,
+                        }),
+                    }
+                }
+                ty => Err(TyError {
+                    kind: TyErrorKind::NoField(ty, *name),
+                    span: name.span,
+                }),
+            },
+            ExprKind::Unary(_, operand) => self.integer_type_of(*operand),
+            ExprKind::Binary(op, lhs, rhs) => {
+                let lhs = self.integer_type_of(*lhs)?;
+                let rhs = self.integer_type_of(*rhs)?;
+
+                match op.kind {
+                    BinaryKind::Shl | BinaryKind::Shr => Ok(lhs),
+                    BinaryKind::Eq
+                    | BinaryKind::Ne
+                    | BinaryKind::Gt
+                    | BinaryKind::Ge
+                    | BinaryKind::Lt
+                    | BinaryKind::Le => {
+                        unify(lhs, rhs, expr.span)?;
+
+                        Ok(Ty::UInt(Const::Value(1)))
+                    }
+                    BinaryKind::Add
+                    | BinaryKind::Sub
+                    | BinaryKind::Mul
+                    | BinaryKind::Div
+                    | BinaryKind::Pow => unify(lhs, rhs, expr.span),
+                }
+            }
+            ExprKind::Call(call) => {
+                let generics = self.eval_consts(call.generics)?;
+                let output = self.ctx[call.callee].output;
+
+                self.ctx.resolve_type(output, &generics)
+            }
+            ExprKind::Record(cons) => {
+                let generics = self.eval_consts(cons.generics)?;
+
+                Ok(Ty::Record(cons.record, generics))
+            }
+        }
+    }
+
+    pub fn eval_consts(&self, exprs: List<Expression>) -> Result<Box<[Const]>> {
+        self.ctx[exprs]
+            .iter()
+            .map(|&expr| self.eval_const(expr))
+            .collect()
+    }
+
+    fn integer_type_of(&mut self, expr: Index<Expression>) -> Result<Ty> {
+        match self.type_of(expr)? {
+            ty @ Ty::Record(..) => Err(TyError {
+                kind: TyErrorKind::NotInteger(ty),
+                span: self.ctx[expr].span,
+            }),
+            ty => Ok(ty),
+        }
+    }
+}
+
+/// Evaluates `arg` with the generic arguments `env`, or returns `None` if it
+/// does arithmetic on a generic parameter that is still symbolic.
+fn substitute(arg: &ConstArg, env: &[Const]) -> Option<Const> {
+    with_sufficient_stack(|| match arg {
+        ConstArg::Value(value) => Some(Const::Value(*value)),
+        ConstArg::Param(i) => Some(env[*i as usize]),
+        ConstArg::Unary(kind, operand) => match substitute(operand, env)? {
+            Const::Value(value) => {
+                Some(Const::Value(eval::eval_unary(*kind, value, u64::BITS)))
+            }
+            Const::Param(_) => None,
+        },
+        ConstArg::Binary(kind, lhs, rhs) => {
+            match (substitute(lhs, env)?, substitute(rhs, env)?) {
+                (Const::Value(lhs), Const::Value(rhs)) => Some(Const::Value(
+                    eval::eval_binary(*kind, lhs, rhs, u64::BITS),
+                )),
+                _ => None,
+            }
+        }
+    })
+}
+
+fn unify(lhs: Ty, rhs: Ty, span: Span) -> Result<Ty> {
+    match (lhs, rhs) {
+        (Ty::Int, ty) | (ty, Ty::Int) => Ok(ty),
+        (Ty::UInt(Const::Value(lhs)), Ty::UInt(Const::Value(rhs))) => {
+            Ok(Ty::UInt(Const::Value(lhs.max(rhs))))
+        }
+        (lhs, rhs) if lhs == rhs => Ok(lhs),
+        (lhs, rhs) => Err(TyError {
+            kind: TyErrorKind::Mismatch(lhs, rhs),
+            span,
+        }),
+    }
+}
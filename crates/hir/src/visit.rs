@@ -0,0 +1,477 @@
+use adpl_util::with_sufficient_stack;
+
+use crate::{
+    Call, ConstArg, Constructor, Context, Definition, ExprKind, Expression,
+    Field, Index, List, Local, Parameter, Record, Statement, StmtKind, TypeRef,
+};
+
+/// A pass over the HIR that only reads it. Every method defaults to visiting
+/// the children of its node with the matching `walk_*` function, so a pass
+/// only overrides the nodes it cares about and calls `walk_*` to continue.
+pub trait Visitor: Sized {
+    fn visit_record(&mut self, ctx: &Context, record: Index<Record>) {
+        walk_record(self, ctx, record);
+    }
+
+    fn visit_field(&mut self, ctx: &Context, field: Index<Field>) {
+        walk_field(self, ctx, field);
+    }
+
+    fn visit_definition(&mut self, ctx: &Context, def: Index<Definition>) {
+        walk_definition(self, ctx, def);
+    }
+
+    fn visit_parameter(&mut self, ctx: &Context, param: Index<Parameter>) {
+        walk_parameter(self, ctx, param);
+    }
+
+    /// Visits a declaration of `local`. Uses of locals are
+    /// [`ExprKind::Id`] expressions.
+    fn visit_local(&mut self, _ctx: &Context, _local: Index<Local>) {}
+
+    fn visit_type(&mut self, ctx: &Context, ty: TypeRef) {
+        walk_type(self, ctx, ty);
+    }
+
+    fn visit_const_arg(&mut self, arg: &ConstArg) {
+        walk_const_arg(self, arg);
+    }
+
+    fn visit_block(&mut self, ctx: &Context, block: List<Statement>) {
+        walk_block(self, ctx, block);
+    }
+
+    fn visit_statement(&mut self, ctx: &Context, stmt: Index<Statement>) {
+        walk_statement(self, ctx, stmt);
+    }
+
+    fn visit_expression(&mut self, ctx: &Context, expr: Index<Expression>) {
+        walk_expression(self, ctx, expr);
+    }
+
+    /// Visits a generic argument of a call or constructor.
+    fn visit_generic_arg(&mut self, ctx: &Context, expr: Index<Expression>) {
+        self.visit_expression(ctx, expr);
+    }
+
+    fn visit_call(&mut self, ctx: &Context, call: &Call) {
+        walk_call(self, ctx, call);
+    }
+
+    fn visit_constructor(&mut self, ctx: &Context, cons: &Constructor) {
+        walk_constructor(self, ctx, cons);
+    }
+}
+
+/// Visits every record and definition of `ctx`.
+pub fn walk_context<V: Visitor>(visitor: &mut V, ctx: &Context) {
+    for record in ctx.records.keys() {
+        visitor.visit_record(ctx, record);
+    }
+
+    for def in ctx.defs.keys() {
+        visitor.visit_definition(ctx, def);
+    }
+}
+
+pub fn walk_record<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    record: Index<Record>,
+) {
+    let record = &ctx[record];
+
+    for local in record.params {
+        visitor.visit_local(ctx, local);
+    }
+
+    for field in record.fields {
+        visitor.visit_field(ctx, field);
+    }
+}
+
+pub fn walk_field<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    field: Index<Field>,
+) {
+    visitor.visit_type(ctx, ctx[field].ty);
+}
+
+pub fn walk_definition<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    def: Index<Definition>,
+) {
+    let def = &ctx[def];
+
+    for local in def.generics {
+        visitor.visit_local(ctx, local);
+    }
+
+    for param in def.inputs {
+        visitor.visit_parameter(ctx, param);
+    }
+
+    visitor.visit_type(ctx, def.output);
+
+    for expr in [def.requires, def.implements].into_iter().flatten() {
+        visitor.visit_expression(ctx, expr.get());
+    }
+
+    if let Some(body) = def.body {
+        visitor.visit_block(ctx, body);
+    }
+}
+
+pub fn walk_parameter<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    param: Index<Parameter>,
+) {
+    let param = &ctx[param];
+
+    visitor.visit_local(ctx, param.local);
+    visitor.visit_type(ctx, param.ty);
+}
+
+pub fn walk_type<V: Visitor>(visitor: &mut V, ctx: &Context, ty: TypeRef) {
+    for arg in &ctx[ty.ty].args {
+        visitor.visit_const_arg(arg);
+    }
+}
+
+pub fn walk_const_arg<V: Visitor>(visitor: &mut V, arg: &ConstArg) {
+    with_sufficient_stack(|| match arg {
+        ConstArg::Value(_) | ConstArg::Param(_) => {}
+        ConstArg::Unary(_, operand) => visitor.visit_const_arg(operand),
+        ConstArg::Binary(_, lhs, rhs) => {
+            visitor.visit_const_arg(lhs);
+            visitor.visit_const_arg(rhs);
+        }
+    })
+}
+
+pub fn walk_block<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    block: List<Statement>,
+) {
+    for &stmt in &ctx[block] {
+        visitor.visit_statement(ctx, stmt);
+    }
+}
+
+pub fn walk_statement<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    stmt: Index<Statement>,
+) {
+    with_sufficient_stack(|| match ctx[stmt].kind {
+        StmtKind::Assign(local, expr) => {
+            visitor.visit_expression(ctx, expr);
+            visitor.visit_local(ctx, local);
+        }
+        StmtKind::Return(expr) => visitor.visit_expression(ctx, expr),
+        StmtKind::Unsafe(block) => visitor.visit_block(ctx, block),
+    })
+}
+
+pub fn walk_expression<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    expr: Index<Expression>,
+) {
+    with_sufficient_stack(|| match &ctx[expr].kind {
+        ExprKind::Id(_) | ExprKind::Lit(_) => {}
+        ExprKind::Field(operand, _) | ExprKind::Unary(_, operand) => {
+            visitor.visit_expression(ctx, *operand);
+        }
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expression(ctx, *lhs);
+            visitor.visit_expression(ctx, *rhs);
+        }
+        ExprKind::Call(call) => visitor.visit_call(ctx, call),
+        ExprKind::Record(cons) => visitor.visit_constructor(ctx, cons),
+    })
+}
+
+pub fn walk_call<V: Visitor>(visitor: &mut V, ctx: &Context, call: &Call) {
+    for &expr in &ctx[call.generics] {
+        visitor.visit_generic_arg(ctx, expr);
+    }
+
+    for &expr in &ctx[call.args] {
+        visitor.visit_expression(ctx, expr);
+    }
+}
+
+pub fn walk_constructor<V: Visitor>(
+    visitor: &mut V,
+    ctx: &Context,
+    cons: &Constructor,
+) {
+    for &expr in &ctx[cons.generics] {
+        visitor.visit_generic_arg(ctx, expr);
+    }
+
+    for &expr in &ctx[cons.inits] {
+        visitor.visit_expression(ctx, expr);
+    }
+}
+
+/// A pass over the HIR that may change it in place. The walks read the
+/// children of a node after the visitor's method has been called, so a
+/// method that rewrites its node before walking it visits the new children.
+pub trait MutVisitor: Sized {
+    fn visit_record(&mut self, ctx: &mut Context, record: Index<Record>) {
+        walk_record_mut(self, ctx, record);
+    }
+
+    fn visit_field(&mut self, ctx: &mut Context, field: Index<Field>) {
+        walk_field_mut(self, ctx, field);
+    }
+
+    fn visit_definition(&mut self, ctx: &mut Context, def: Index<Definition>) {
+        walk_definition_mut(self, ctx, def);
+    }
+
+    fn visit_parameter(&mut self, ctx: &mut Context, param: Index<Parameter>) {
+        walk_parameter_mut(self, ctx, param);
+    }
+
+    /// Visits a declaration of `local`. Uses of locals are
+    /// [`ExprKind::Id`] expressions.
+    fn visit_local(&mut self, _ctx: &mut Context, _local: Index<Local>) {}
+
+    /// Visits a use of a type. Types are interned, so they are never changed
+    /// in place, but the use can be pointed at another type.
+    fn visit_type(&mut self, ctx: &mut Context, ty: &mut TypeRef) {
+        walk_type_mut(self, ctx, ty);
+    }
+
+    /// Visits an argument of a type visited by [`MutVisitor::visit_type`].
+    /// Arguments are part of the interned type, so they can only be read
+    /// here. A pass that changes them interns the new type and points the
+    /// use at it in `visit_type` instead.
+    fn visit_const_arg(&mut self, arg: &ConstArg) {
+        walk_const_arg_mut(self, arg);
+    }
+
+    fn visit_block(&mut self, ctx: &mut Context, block: List<Statement>) {
+        walk_block_mut(self, ctx, block);
+    }
+
+    fn visit_statement(&mut self, ctx: &mut Context, stmt: Index<Statement>) {
+        walk_statement_mut(self, ctx, stmt);
+    }
+
+    fn visit_expression(&mut self, ctx: &mut Context, expr: Index<Expression>) {
+        walk_expression_mut(self, ctx, expr);
+    }
+
+    /// Visits a generic argument of a call or constructor.
+    fn visit_generic_arg(
+        &mut self,
+        ctx: &mut Context,
+        expr: Index<Expression>,
+    ) {
+        self.visit_expression(ctx, expr);
+    }
+
+    /// Visits the call `expr`.
+    fn visit_call(&mut self, ctx: &mut Context, expr: Index<Expression>) {
+        walk_call_mut(self, ctx, expr);
+    }
+
+    /// Visits the constructor `expr`.
+    fn visit_constructor(
+        &mut self,
+        ctx: &mut Context,
+        expr: Index<Expression>,
+    ) {
+        walk_constructor_mut(self, ctx, expr);
+    }
+}
+
+/// Visits every record and definition of `ctx`.
+pub fn walk_context_mut<V: MutVisitor>(visitor: &mut V, ctx: &mut Context) {
+    for record in ctx.records.keys() {
+        visitor.visit_record(ctx, record);
+    }
+
+    for def in ctx.defs.keys() {
+        visitor.visit_definition(ctx, def);
+    }
+}
+
+pub fn walk_record_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    record: Index<Record>,
+) {
+    let (params, fields) = (ctx[record].params, ctx[record].fields);
+
+    for local in params {
+        visitor.visit_local(ctx, local);
+    }
+
+    for field in fields {
+        visitor.visit_field(ctx, field);
+    }
+}
+
+pub fn walk_field_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    field: Index<Field>,
+) {
+    let mut ty = ctx[field].ty;
+    visitor.visit_type(ctx, &mut ty);
+    ctx[field].ty = ty;
+}
+
+pub fn walk_definition_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    def: Index<Definition>,
+) {
+    for local in ctx[def].generics {
+        visitor.visit_local(ctx, local);
+    }
+
+    for param in ctx[def].inputs {
+        visitor.visit_parameter(ctx, param);
+    }
+
+    let mut output = ctx[def].output;
+    visitor.visit_type(ctx, &mut output);
+    ctx[def].output = output;
+
+    let clauses = [ctx[def].requires, ctx[def].implements];
+
+    for expr in clauses.into_iter().flatten() {
+        visitor.visit_expression(ctx, expr.get());
+    }
+
+    if let Some(body) = ctx[def].body {
+        visitor.visit_block(ctx, body);
+    }
+}
+
+pub fn walk_parameter_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    param: Index<Parameter>,
+) {
+    visitor.visit_local(ctx, ctx[param].local);
+
+    let mut ty = ctx[param].ty;
+    visitor.visit_type(ctx, &mut ty);
+    ctx[param].ty = ty;
+}
+
+pub fn walk_type_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    ty: &mut TypeRef,
+) {
+    for arg in &ctx[ty.ty].args {
+        visitor.visit_const_arg(arg);
+    }
+}
+
+pub fn walk_const_arg_mut<V: MutVisitor>(visitor: &mut V, arg: &ConstArg) {
+    with_sufficient_stack(|| match arg {
+        ConstArg::Value(_) | ConstArg::Param(_) => {}
+        ConstArg::Unary(_, operand) => visitor.visit_const_arg(operand),
+        ConstArg::Binary(_, lhs, rhs) => {
+            visitor.visit_const_arg(lhs);
+            visitor.visit_const_arg(rhs);
+        }
+    })
+}
+
+pub fn walk_block_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    block: List<Statement>,
+) {
+    for i in 0..block.len() {
+        visitor.visit_statement(ctx, ctx[block][i]);
+    }
+}
+
+pub fn walk_statement_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    stmt: Index<Statement>,
+) {
+    with_sufficient_stack(|| match ctx[stmt].kind {
+        StmtKind::Assign(local, expr) => {
+            visitor.visit_expression(ctx, expr);
+            visitor.visit_local(ctx, local);
+        }
+        StmtKind::Return(expr) => visitor.visit_expression(ctx, expr),
+        StmtKind::Unsafe(block) => visitor.visit_block(ctx, block),
+    })
+}
+
+pub fn walk_expression_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    expr: Index<Expression>,
+) {
+    with_sufficient_stack(|| match ctx[expr].kind {
+        ExprKind::Id(_) | ExprKind::Lit(_) => {}
+        ExprKind::Field(operand, _) | ExprKind::Unary(_, operand) => {
+            visitor.visit_expression(ctx, operand);
+        }
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expression(ctx, lhs);
+            visitor.visit_expression(ctx, rhs);
+        }
+        ExprKind::Call(_) => visitor.visit_call(ctx, expr),
+        ExprKind::Record(_) => visitor.visit_constructor(ctx, expr),
+    })
+}
+
+pub fn walk_call_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    expr: Index<Expression>,
+) {
+    let ExprKind::Call(call) = &ctx[expr].kind else {
+        panic!("expected a call expression");
+    };
+
+    let (generics, args) = (call.generics, call.args);
+
+    for i in 0..generics.len() {
+        visitor.visit_generic_arg(ctx, ctx[generics][i]);
+    }
+
+    for i in 0..args.len() {
+        visitor.visit_expression(ctx, ctx[args][i]);
+    }
+}
+
+pub fn walk_constructor_mut<V: MutVisitor>(
+    visitor: &mut V,
+    ctx: &mut Context,
+    expr: Index<Expression>,
+) {
+    let ExprKind::Record(cons) = &ctx[expr].kind else {
+        panic!("expected a constructor expression");
+    };
+
+    let (generics, inits) = (cons.generics, cons.inits);
+
+    for i in 0..generics.len() {
+        visitor.visit_generic_arg(ctx, ctx[generics][i]);
+    }
+
+    for i in 0..inits.len() {
+        visitor.visit_expression(ctx, ctx[inits][i]);
+    }
+}
@@ -62,6 +62,8 @@ pub enum Token<'src> {
 
     #[token("->")]
     Arrow,
+    #[token("#")]
+    Hash,
     #[token(":")]
     Colon,
     #[token(",")]
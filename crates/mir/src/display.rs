@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::{BinaryKind, Function, Module, Op, Port, UnaryKind};
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, function) in self.functions.values().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            fmt_function(f, self, function)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn fmt_function(
+    f: &mut fmt::Formatter,
+    module: &Module,
+    function: &Function,
+) -> fmt::Result {
+    if function.body.is_none() {
+        write!(f, "extern ")?;
+    }
+
+    write!(f, "fn {}(", function.name)?;
+    fmt_ports(f, &function.inputs)?;
+    write!(f, ") -> (")?;
+    fmt_ports(f, &function.outputs)?;
+    write!(f, ")")?;
+
+    let Some(body) = &function.body else {
+        return writeln!(f);
+    };
+
+    writeln!(f, " {{")?;
+
+    for (index, node) in body.nodes.iter() {
+        write!(f, "    %{}", index.index())?;
+
+        if !matches!(node.op, Op::Instance(..)) {
+            write!(f, ": u{}", node.width)?;
+        }
+
+        write!(f, " = ")?;
+
+        match node.op {
+            Op::Input(i) => write!(f, "input {}", i)?,
+            Op::Const(value) => write!(f, "const {}", value)?,
+            Op::Resize(operand) => write!(f, "resize %{}", operand.index())?,
+            Op::Unary(kind, operand) => {
+                write!(f, "{} %{}", unary_name(kind), operand.index())?
+            }
+            Op::Binary(kind, lhs, rhs) => write!(
+                f,
+                "{} %{}, %{}",
+                binary_name(kind),
+                lhs.index(),
+                rhs.index(),
+            )?,
+            Op::Instance(callee, args) => {
+                write!(f, "instance @{}(", module.functions[callee].name)?;
+
+                for (i, arg) in body.lists[args].iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "%{}", arg.index())?;
+                }
+
+                write!(f, ")")?;
+            }
+            Op::Output(instance, i) => {
+                write!(f, "output %{}.{}", instance.index(), i)?
+            }
+        }
+
+        writeln!(f)?;
+    }
+
+    write!(f, "    return")?;
+
+    for (i, result) in body.results.iter().enumerate() {
+        let sep = if i > 0 { "," } else { "" };
+        write!(f, "{} %{}", sep, result.index())?;
+    }
+
+    writeln!(f)?;
+    writeln!(f, "}}")
+}
+
+fn fmt_ports(f: &mut fmt::Formatter, ports: &[Port]) -> fmt::Result {
+    for (i, port) in ports.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{}: u{}", port.name, port.width)?;
+    }
+
+    Ok(())
+}
+
+fn unary_name(kind: UnaryKind) -> &'static str {
+    match kind {
+        UnaryKind::Neg => "neg",
+        UnaryKind::Not => "not",
+    }
+}
+
+fn binary_name(kind: BinaryKind) -> &'static str {
+    match kind {
+        BinaryKind::Add => "add",
+        BinaryKind::Sub => "sub",
+        BinaryKind::Mul => "mul",
+        BinaryKind::Div => "div",
+        BinaryKind::Pow => "pow",
+        BinaryKind::Shl => "shl",
+        BinaryKind::Shr => "shr",
+        BinaryKind::Eq => "eq",
+        BinaryKind::Ne => "ne",
+        BinaryKind::Gt => "gt",
+        BinaryKind::Ge => "ge",
+        BinaryKind::Lt => "lt",
+        BinaryKind::Le => "le",
+    }
+}
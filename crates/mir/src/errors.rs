@@ -0,0 +1,70 @@
+use adpl_hir as hir;
+use adpl_util::{Diagnostic, codes};
+
+pub struct UnsupportedWidth {
+    pub width: u64,
+    pub span: hir::Span,
+}
+
+impl From<UnsupportedWidth> for Diagnostic {
+    fn from(value: UnsupportedWidth) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0021)
+            .with_message(format!(
+                "`uint[{}]` cannot be lowered to MIR",
+                value.width,
+            ))
+            .with_primary(value.span, "width exceeds 64 bits")
+    }
+}
+
+pub struct RecursiveRecord<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+}
+
+impl From<RecursiveRecord<'_>> for Diagnostic {
+    fn from(value: RecursiveRecord) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0022)
+            .with_message(format!(
+                "recursive struct `{}` has infinite size",
+                &value.symbols[value.name.symbol],
+            ))
+            .with_primary(value.name.span, "recursive struct")
+    }
+}
+
+pub struct MissingReturn<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+}
+
+impl From<MissingReturn<'_>> for Diagnostic {
+    fn from(value: MissingReturn) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0023)
+            .with_message(format!(
+                "definition `{}` does not return a value",
+                &value.symbols[value.name.symbol],
+            ))
+            .with_primary(value.name.span, "missing `return`")
+    }
+}
+
+pub struct RecursiveInline<'a> {
+    pub symbols: &'a hir::Symbols,
+    pub name: &'a hir::Id,
+}
+
+impl From<RecursiveInline<'_>> for Diagnostic {
+    fn from(value: RecursiveInline) -> Self {
+        Diagnostic::error()
+            .with_code(codes::E0024)
+            .with_message(format!(
+                "cannot inline recursive call to `{}`",
+                &value.symbols[value.name.symbol],
+            ))
+            .with_primary(value.name.span, "recursive call")
+    }
+}
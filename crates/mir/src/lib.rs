@@ -0,0 +1,9 @@
+mod display;
+mod errors;
+mod lowering;
+mod mir;
+mod verify;
+
+pub use lowering::{CallMode, lower_hir};
+pub use mir::*;
+pub use verify::{VerifyError, verify};
@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+
+use adpl_arena::Index;
+use adpl_hir as hir;
+use adpl_util::{Diagnostic, Reporter, ice, with_sufficient_stack};
+
+use crate::{
+    BinaryKind, Body, Function, Module, Node, Op, Port, errors, verify,
+};
+
+/// How calls that are still in the HIR are lowered.
+///
+/// The HIR inliner runs before MIR lowering unless optimizations are
+/// disabled, so `Instance` only produces instances for the calls it kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallMode {
+    /// Lower each call to an instance of a separate function.
+    Instance,
+    /// Lower each call by copying the body of its callee into the caller.
+    Inline,
+}
+
+pub fn lower_hir(
+    ctx: &hir::Context,
+    mode: CallMode,
+    reporter: &mut Reporter,
+) -> Option<Module> {
+    let mut lowering = Lowering {
+        ctx,
+        reporter,
+        mode,
+        module: Module::default(),
+        instances: HashMap::new(),
+        queue: Vec::new(),
+        inlining: Vec::new(),
+    };
+
+    lowering.lower_module().ok()?;
+
+    if let Err(error) = verify(&lowering.module) {
+        lowering.reporter.emit(
+            Diagnostic::bug().with_message(format!("invalid MIR: {}", error)),
+        );
+
+        return None;
+    }
+
+    Some(lowering.module)
+}
+
+#[derive(Debug)]
+struct LoweringError;
+
+type Result<T> = std::result::Result<T, LoweringError>;
+
+type Instance = (hir::Index<hir::Definition>, Box<[hir::Const]>);
+
+type Locals = HashMap<hir::Index<hir::Local>, Vec<Index<Node>>>;
+
+type Return = (hir::Index<hir::Expression>, Vec<Index<Node>>);
+
+struct Lowering<'a, 'src> {
+    ctx: &'a hir::Context,
+    reporter: &'a mut Reporter<'src>,
+    mode: CallMode,
+    module: Module,
+    instances: HashMap<Instance, Index<Function>>,
+    queue: Vec<(Index<Function>, Instance)>,
+    inlining: Vec<Instance>,
+}
+
+impl Lowering<'_, '_> {
+    fn lower_module(&mut self) -> Result<()> {
+        for (def, decl) in self.ctx.defs.iter() {
+            if decl.generics.is_empty() {
+                self.instance(def, Box::new([]))?;
+            }
+        }
+
+        let mut next = 0;
+
+        while let Some((function, (def, generics))) =
+            self.queue.get(next).cloned()
+        {
+            let name = &self.ctx[def].name;
+            let body =
+                ice::item(&self.ctx.symbols[name.symbol], name.span, || {
+                    self.lower_body(def, &generics)
+                })?;
+            self.module.functions[function].body = body;
+            next += 1;
+        }
+
+        Ok(())
+    }
+
+    fn instance(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: Box<[hir::Const]>,
+    ) -> Result<Index<Function>> {
+        if let Some(&function) = self.instances.get(&(def, generics.clone())) {
+            return Ok(function);
+        }
+
+        let decl = &self.ctx[def];
+        let cx = hir::TypeCx::new(self.ctx, def, &generics);
+
+        let mut inputs = Vec::new();
+
+        for param in &self.ctx.params[decl.inputs] {
+            let ty = cx.resolve_type(param.ty);
+            let ty = self.check(&cx, ty)?;
+            let name =
+                self.ctx.symbols[self.ctx[param.local].name.symbol].to_string();
+
+            self.layout(&cx, &ty, name, param.span, &mut inputs)?;
+        }
+
+        let output = cx.output_type();
+        let output = self.check(&cx, output)?;
+        let span = decl.output.span;
+
+        let mut outputs = Vec::new();
+        self.layout(&cx, &output, "out".into(), span, &mut outputs)?;
+
+        let function = self.module.functions.push(Function {
+            name: mangle(&self.ctx.symbols, &decl.name, &generics),
+            inputs,
+            outputs,
+            body: None,
+        });
+
+        self.instances.insert((def, generics.clone()), function);
+        self.queue.push((function, (def, generics)));
+
+        Ok(function)
+    }
+
+    fn lower_body(
+        &mut self,
+        def: hir::Index<hir::Definition>,
+        generics: &[hir::Const],
+    ) -> Result<Option<Body>> {
+        let decl = &self.ctx[def];
+
+        let Some(block) = decl.body else {
+            return Ok(None);
+        };
+
+        let mut cx = hir::TypeCx::new(self.ctx, def, generics);
+        let mut body = Body::default();
+        let mut locals = Locals::new();
+        let mut input = 0;
+
+        for param in &self.ctx.params[decl.inputs] {
+            let ty = cx.resolve_type(param.ty);
+            let ty = self.check(&cx, ty)?;
+
+            let mut ports = Vec::new();
+            self.layout(&cx, &ty, String::new(), param.span, &mut ports)?;
+
+            let values = ports
+                .iter()
+                .map(|port| {
+                    input += 1;
+                    body.add(Op::Input(input - 1), port.width)
+                })
+                .collect();
+
+            locals.insert(param.local, values);
+        }
+
+        body.results = self.lower_return(&mut cx, &mut body, locals, block)?;
+
+        Ok(Some(body))
+    }
+
+    fn lower_return(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        body: &mut Body,
+        mut locals: Locals,
+        block: hir::List<hir::Statement>,
+    ) -> Result<Vec<Index<Node>>> {
+        let output = cx.output_type();
+        let output = self.check(cx, output)?;
+
+        let Some((expr, values)) =
+            self.lower_block(cx, body, &mut locals, block)?
+        else {
+            let decl = &self.ctx[cx.def()];
+
+            self.reporter.emit(errors::MissingReturn {
+                symbols: &self.ctx.symbols,
+                name: &decl.name,
+            });
+
+            return Err(LoweringError);
+        };
+
+        let ty = cx.type_of(expr);
+        let ty = self.check(cx, ty)?;
+        let span = self.ctx[expr].span;
+
+        self.coerce(cx, body, values, &ty, &output, span)
+    }
+
+    fn lower_block(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        body: &mut Body,
+        locals: &mut Locals,
+        block: hir::List<hir::Statement>,
+    ) -> Result<Option<Return>> {
+        for &stmt in &self.ctx[block] {
+            match self.ctx[stmt].kind {
+                hir::StmtKind::Assign(local, expr) => {
+                    let values =
+                        self.lower_expression(cx, body, locals, expr)?;
+                    locals.insert(local, values);
+                }
+                hir::StmtKind::Return(expr) => {
+                    let values =
+                        self.lower_expression(cx, body, locals, expr)?;
+                    return Ok(Some((expr, values)));
+                }
+                hir::StmtKind::Unsafe(block) => {
+                    if let Some(ret) =
+                        self.lower_block(cx, body, locals, block)?
+                    {
+                        return Ok(Some(ret));
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn lower_expression(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        body: &mut Body,
+        locals: &Locals,
+        index: hir::Index<hir::Expression>,
+    ) -> Result<Vec<Index<Node>>> {
+        with_sufficient_stack(|| {
+            let ty = cx.type_of(index);
+            let ty = self.check(cx, ty)?;
+            let expr = &self.ctx[index];
+
+            Ok(match &expr.kind {
+                &hir::ExprKind::Id(local) => match self.ctx[local].kind {
+                    hir::LocalKind::GenericParam(i) => {
+                        let value = value(cx.generics()[i as usize]);
+                        vec![body.add(Op::Const(value), u64::BITS)]
+                    }
+                    _ => locals[&local].clone(),
+                },
+                hir::ExprKind::Lit(lit) => {
+                    vec![body.add(Op::Const(lit.value), u64::BITS)]
+                }
+                &hir::ExprKind::Field(base, name) => {
+                    let base_ty = cx.type_of(base);
+                    let base_ty = self.check(cx, base_ty)?;
+                    let values =
+                        self.lower_expression(cx, body, locals, base)?;
+
+                    let hir::Ty::Record(record, args) = base_ty else {
+                        unreachable!("field of non-record type");
+                    };
+
+                    let mut offset = 0;
+
+                    for field in &self.ctx.fields[self.ctx[record].fields] {
+                        if field.name.symbol == name.symbol {
+                            break;
+                        }
+
+                        let field_ty = self.ctx.resolve_type(field.ty, &args);
+                        let field_ty = self.check(cx, field_ty)?;
+
+                        let mut ports = Vec::new();
+                        self.layout(
+                            cx,
+                            &field_ty,
+                            String::new(),
+                            field.span,
+                            &mut ports,
+                        )?;
+
+                        offset += ports.len();
+                    }
+
+                    let mut ports = Vec::new();
+                    self.layout(cx, &ty, String::new(), expr.span, &mut ports)?;
+
+                    values[offset..offset + ports.len()].to_vec()
+                }
+                &hir::ExprKind::Unary(op, operand) => {
+                    let operand =
+                        self.lower_expression(cx, body, locals, operand)?;
+                    let width = self.width(&ty, expr.span)?;
+                    let operand = resize(body, operand[0], width);
+
+                    vec![body.add(Op::Unary(op.kind, operand), width)]
+                }
+                &hir::ExprKind::Binary(op, lhs, rhs) => {
+                    let lhs = self.lower_expression(cx, body, locals, lhs)?[0];
+                    let rhs = self.lower_expression(cx, body, locals, rhs)?[0];
+                    let width = self.width(&ty, expr.span)?;
+
+                    vec![binary(body, op.kind, lhs, rhs, width)]
+                }
+                hir::ExprKind::Call(call) => {
+                    self.lower_call(cx, body, locals, call)?
+                }
+                hir::ExprKind::Record(cons) => {
+                    let hir::Ty::Record(_, generics) = &ty else {
+                        unreachable!("constructor of non-record type");
+                    };
+
+                    let mut values = Vec::new();
+
+                    for (&init, field) in std::iter::zip(
+                        &self.ctx[cons.inits],
+                        &self.ctx.fields[self.ctx[cons.record].fields],
+                    ) {
+                        let expected =
+                            self.ctx.resolve_type(field.ty, generics);
+                        let expected = self.check(cx, expected)?;
+                        let found = cx.type_of(init);
+                        let found = self.check(cx, found)?;
+                        let span = self.ctx[init].span;
+
+                        let init =
+                            self.lower_expression(cx, body, locals, init)?;
+
+                        values.extend(
+                            self.coerce(
+                                cx, body, init, &found, &expected, span,
+                            )?,
+                        );
+                    }
+
+                    values
+                }
+            })
+        })
+    }
+
+    fn lower_call(
+        &mut self,
+        cx: &mut hir::TypeCx,
+        body: &mut Body,
+        locals: &Locals,
+        call: &hir::Call,
+    ) -> Result<Vec<Index<Node>>> {
+        let generics = cx.eval_consts(call.generics);
+        let generics = self.check(cx, generics)?;
+        let callee = &self.ctx[call.callee];
+
+        let mut args = Vec::with_capacity(call.args.len());
+
+        for (&arg, param) in std::iter::zip(
+            &self.ctx[call.args],
+            &self.ctx.params[callee.inputs],
+        ) {
+            let expected = self.ctx.resolve_type(param.ty, &generics);
+            let expected = self.check(cx, expected)?;
+            let found = cx.type_of(arg);
+            let found = self.check(cx, found)?;
+            let span = self.ctx[arg].span;
+
+            let values = self.lower_expression(cx, body, locals, arg)?;
+            args.push(self.coerce(cx, body, values, &found, &expected, span)?);
+        }
+
+        if let (CallMode::Inline, Some(block)) = (self.mode, callee.body) {
+            let instance = (call.callee, generics.clone());
+
+            if self.inlining.contains(&instance) {
+                self.reporter.emit(errors::RecursiveInline {
+                    symbols: &self.ctx.symbols,
+                    name: &call.name,
+                });
+
+                return Err(LoweringError);
+            }
+
+            let mut callee_cx =
+                hir::TypeCx::new(self.ctx, call.callee, &generics);
+
+            let callee_locals =
+                std::iter::zip(&self.ctx.params[callee.inputs], args)
+                    .map(|(param, values)| (param.local, values))
+                    .collect();
+
+            self.inlining.push(instance);
+            let values =
+                self.lower_return(&mut callee_cx, body, callee_locals, block);
+            self.inlining.pop();
+
+            return values;
+        }
+
+        let function = self.instance(call.callee, generics)?;
+        let args = body.lists.extend(args.into_iter().flatten());
+        let instance = body.add(Op::Instance(function, args), 0);
+
+        Ok(self.module.functions[function]
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(i, port)| {
+                body.add(
+                    Op::Output(instance, i.try_into().unwrap()),
+                    port.width,
+                )
+            })
+            .collect())
+    }
+
+    fn coerce(
+        &mut self,
+        cx: &hir::TypeCx,
+        body: &mut Body,
+        values: Vec<Index<Node>>,
+        found: &hir::Ty,
+        expected: &hir::Ty,
+        span: hir::Span,
+    ) -> Result<Vec<Index<Node>>> {
+        match (found, expected) {
+            (hir::Ty::Record(..), hir::Ty::Record(..)) if found == expected => {
+                Ok(values)
+            }
+            (
+                hir::Ty::Int | hir::Ty::UInt(_),
+                hir::Ty::Int | hir::Ty::UInt(_),
+            ) => {
+                let width = self.width(expected, span)?;
+
+                Ok(vec![resize(body, values[0], width)])
+            }
+            _ => {
+                let error = hir::TyError {
+                    kind: hir::TyErrorKind::Mismatch(
+                        expected.clone(),
+                        found.clone(),
+                    ),
+                    span,
+                };
+
+                self.check(cx, Err(error))
+            }
+        }
+    }
+
+    fn layout(
+        &mut self,
+        cx: &hir::TypeCx,
+        ty: &hir::Ty,
+        name: String,
+        span: hir::Span,
+        ports: &mut Vec<Port>,
+    ) -> Result<()> {
+        self.layout_in(cx, ty, name, span, ports, &mut Vec::new())
+    }
+
+    fn layout_in(
+        &mut self,
+        cx: &hir::TypeCx,
+        ty: &hir::Ty,
+        name: String,
+        span: hir::Span,
+        ports: &mut Vec<Port>,
+        records: &mut Vec<hir::Index<hir::Record>>,
+    ) -> Result<()> {
+        let hir::Ty::Record(record, args) = ty else {
+            let width = self.width(ty, span)?;
+            ports.push(Port { name, width });
+
+            return Ok(());
+        };
+
+        let decl = &self.ctx[*record];
+
+        if records.contains(record) {
+            self.reporter.emit(errors::RecursiveRecord {
+                symbols: &self.ctx.symbols,
+                name: &decl.name,
+            });
+
+            return Err(LoweringError);
+        }
+
+        records.push(*record);
+
+        for field in &self.ctx.fields[decl.fields] {
+            let ty = self.ctx.resolve_type(field.ty, args);
+            let ty = self.check(cx, ty)?;
+
+            let mut name = name.clone();
+
+            if !name.is_empty() {
+                name.push('.');
+            }
+
+            name.push_str(&self.ctx.symbols[field.name.symbol]);
+
+            self.layout_in(cx, &ty, name, field.span, ports, records)?;
+        }
+
+        records.pop();
+
+        Ok(())
+    }
+
+    fn width(&mut self, ty: &hir::Ty, span: hir::Span) -> Result<u32> {
+        match *ty {
+            hir::Ty::UInt(width) => match value(width) {
+                width @ 0..=64 => Ok(width as u32),
+                width => {
+                    self.reporter
+                        .emit(errors::UnsupportedWidth { width, span });
+
+                    Err(LoweringError)
+                }
+            },
+            _ => Ok(u64::BITS),
+        }
+    }
+
+    fn check<T>(
+        &mut self,
+        cx: &hir::TypeCx,
+        result: std::result::Result<T, hir::TyError>,
+    ) -> Result<T> {
+        result.map_err(|error| {
+            let generics = self.ctx[cx.def()].generics;
+            self.reporter.emit(error.to_diagnostic(self.ctx, generics));
+
+            LoweringError
+        })
+    }
+}
+
+fn resize(body: &mut Body, value: Index<Node>, width: u32) -> Index<Node> {
+    if body.nodes[value].width == width {
+        value
+    } else {
+        body.add(Op::Resize(value), width)
+    }
+}
+
+/// Adds a binary operation whose result is `width` bits wide, resizing the
+/// operands to a common width first.
+///
+/// Wrapping arithmetic only depends on the low bits of its operands, so it is
+/// done at the result width. Every other operation is done at the width of
+/// the wider operand, so that neither operand is truncated, and the result is
+/// resized afterwards.
+fn binary(
+    body: &mut Body,
+    kind: BinaryKind,
+    lhs: Index<Node>,
+    rhs: Index<Node>,
+    width: u32,
+) -> Index<Node> {
+    let operands = match kind {
+        BinaryKind::Add | BinaryKind::Sub | BinaryKind::Mul => width,
+        _ => body.nodes[lhs].width.max(body.nodes[rhs].width),
+    };
+
+    let lhs = resize(body, lhs, operands);
+    let rhs = resize(body, rhs, operands);
+
+    if matches!(
+        kind,
+        BinaryKind::Eq
+            | BinaryKind::Ne
+            | BinaryKind::Gt
+            | BinaryKind::Ge
+            | BinaryKind::Lt
+            | BinaryKind::Le
+    ) {
+        return body.add(Op::Binary(kind, lhs, rhs), width);
+    }
+
+    let result = body.add(Op::Binary(kind, lhs, rhs), operands);
+    resize(body, result, width)
+}
+
+fn value(value: hir::Const) -> u64 {
+    match value {
+        hir::Const::Value(value) => value,
+        hir::Const::Param(_) => {
+            unreachable!("generic parameter in monomorphic code")
+        }
+    }
+}
+
+/// Names an instance like the backends do, so that the MIR dump tells
+/// `foo[8]` apart from a definition called `foo_8`.
+fn mangle(
+    symbols: &hir::Symbols,
+    name: &hir::Id,
+    generics: &[hir::Const],
+) -> String {
+    if generics.is_empty() {
+        hir::escape(&symbols[name.symbol]).into_owned()
+    } else {
+        hir::mangle(symbols, name, generics)
+    }
+}
@@ -0,0 +1,72 @@
+pub use adpl_arena::{Arena, Index, IndexArena, List};
+pub use adpl_hir::{BinaryKind, UnaryKind};
+
+#[derive(Default)]
+pub struct Module {
+    pub functions: Arena<Function>,
+}
+
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+    pub body: Option<Body>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Port {
+    pub name: String,
+    pub width: u32,
+}
+
+#[derive(Default)]
+pub struct Body {
+    pub nodes: Arena<Node>,
+    pub lists: IndexArena,
+    pub results: Vec<Index<Node>>,
+}
+
+impl Body {
+    #[inline]
+    pub fn add(&mut self, op: Op, width: u32) -> Index<Node> {
+        self.nodes.push(Node { op, width })
+    }
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub op: Op,
+    pub width: u32,
+}
+
+#[derive(Debug)]
+pub enum Op {
+    Input(u32),
+    Const(u64),
+    Resize(Index<Node>),
+    Unary(UnaryKind, Index<Node>),
+    Binary(BinaryKind, Index<Node>, Index<Node>),
+    Instance(Index<Function>, List<Node>),
+    Output(Index<Node>, u32),
+}
+
+impl Op {
+    pub fn for_each_operand<F>(&self, body: &Body, mut f: F)
+    where
+        F: FnMut(Index<Node>),
+    {
+        match *self {
+            Op::Input(_) | Op::Const(_) => {}
+            Op::Resize(operand)
+            | Op::Unary(_, operand)
+            | Op::Output(operand, _) => f(operand),
+            Op::Binary(_, lhs, rhs) => {
+                f(lhs);
+                f(rhs);
+            }
+            Op::Instance(_, args) => {
+                body.lists[args].iter().copied().for_each(f)
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use adpl_hir::mask;
+
+use crate::{BinaryKind, Body, Function, Index, Module, Node, Op};
+
+#[derive(Debug)]
+pub struct VerifyError {
+    pub function: String,
+    pub node: Option<Index<Node>>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.node {
+            Some(node) => write!(
+                f,
+                "in `{}`, %{}: {}",
+                self.function,
+                node.index(),
+                self.message,
+            ),
+            None => write!(f, "in `{}`: {}", self.function, self.message),
+        }
+    }
+}
+
+pub fn verify(module: &Module) -> Result<(), VerifyError> {
+    for function in module.functions.values() {
+        let error = |node, message: &str| VerifyError {
+            function: function.name.clone(),
+            node,
+            message: message.into(),
+        };
+
+        if let Some(port) = function
+            .inputs
+            .iter()
+            .chain(&function.outputs)
+            .find(|port| port.width > u64::BITS)
+        {
+            let message = format!("port `{}` is wider than 64 bits", port.name);
+            return Err(error(None, &message));
+        }
+
+        let Some(body) = &function.body else {
+            continue;
+        };
+
+        for (index, node) in body.nodes.iter() {
+            verify_node(module, function, body, index, node)
+                .map_err(|message| error(Some(index), message))?;
+        }
+
+        if body.results.len() != function.outputs.len() {
+            return Err(error(None, "wrong number of results"));
+        }
+
+        for (&result, port) in body.results.iter().zip(&function.outputs) {
+            let node = &body.nodes[result];
+
+            if matches!(node.op, Op::Instance(..)) {
+                return Err(error(Some(result), "instance used as a result"));
+            }
+
+            if node.width != port.width {
+                return Err(error(Some(result), "result width mismatch"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn verify_node(
+    module: &Module,
+    function: &Function,
+    body: &Body,
+    index: Index<Node>,
+    node: &Node,
+) -> Result<(), &'static str> {
+    if node.width > u64::BITS {
+        return Err("node is wider than 64 bits");
+    }
+
+    let mut operands = Ok(());
+
+    node.op.for_each_operand(body, |operand| {
+        if operands.is_err() {
+            return;
+        }
+
+        operands = if operand >= index {
+            Err("operand does not dominate its use")
+        } else {
+            match (&node.op, &body.nodes[operand].op) {
+                (Op::Output(..), Op::Instance(..)) => Ok(()),
+                (Op::Output(..), _) => Err("output of a non-instance node"),
+                (_, Op::Instance(..)) => Err("instance used as an operand"),
+                _ => Ok(()),
+            }
+        };
+    });
+
+    operands?;
+
+    match node.op {
+        Op::Input(i) => match function.inputs.get(i as usize) {
+            Some(port) if port.width == node.width => Ok(()),
+            Some(_) => Err("input width mismatch"),
+            None => Err("input index out of range"),
+        },
+        Op::Const(value) if value & !mask(node.width) != 0 => {
+            Err("constant does not fit its width")
+        }
+        Op::Unary(_, operand) if body.nodes[operand].width != node.width => {
+            Err("operand width mismatch")
+        }
+        Op::Binary(_, lhs, rhs)
+            if body.nodes[lhs].width != body.nodes[rhs].width =>
+        {
+            Err("operand width mismatch")
+        }
+        Op::Binary(
+            BinaryKind::Eq
+            | BinaryKind::Ne
+            | BinaryKind::Gt
+            | BinaryKind::Ge
+            | BinaryKind::Lt
+            | BinaryKind::Le,
+            _,
+            _,
+        ) => match node.width {
+            1 => Ok(()),
+            _ => Err("comparison is not 1 bit wide"),
+        },
+        Op::Binary(_, lhs, _) if body.nodes[lhs].width != node.width => {
+            Err("operand width mismatch")
+        }
+        Op::Instance(callee, args) => {
+            let Some(callee) = module.functions.get(callee) else {
+                return Err("instance of unknown function");
+            };
+
+            let args = &body.lists[args];
+
+            if args.len() != callee.inputs.len() {
+                return Err("wrong number of instance arguments");
+            }
+
+            if args
+                .iter()
+                .zip(&callee.inputs)
+                .any(|(&arg, port)| body.nodes[arg].width != port.width)
+            {
+                return Err("instance argument width mismatch");
+            }
+
+            if node.width != 0 {
+                return Err("instance node has a width");
+            }
+
+            Ok(())
+        }
+        Op::Output(instance, i) => {
+            let Op::Instance(callee, _) = body.nodes[instance].op else {
+                unreachable!("checked above");
+            };
+
+            let Some(callee) = module.functions.get(callee) else {
+                return Err("instance of unknown function");
+            };
+
+            match callee.outputs.get(i as usize) {
+                Some(port) if port.width == node.width => Ok(()),
+                Some(_) => Err("output width mismatch"),
+                None => Err("output index out of range"),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Port;
+
+    fn module(resize: bool) -> Module {
+        let port = |name: &str, width| Port {
+            name: name.into(),
+            width,
+        };
+
+        let mut body = Body::default();
+        let a = body.add(Op::Input(0), 8);
+        let b = body.add(Op::Input(1), 64);
+        let b = if resize {
+            body.add(Op::Resize(b), 8)
+        } else {
+            b
+        };
+        let sum = body.add(Op::Binary(BinaryKind::Add, a, b), 8);
+        body.results.push(sum);
+
+        let mut module = Module::default();
+        module.functions.push(Function {
+            name: "f".into(),
+            inputs: vec![port("a", 8), port("b", 64)],
+            outputs: vec![port("out", 8)],
+            body: Some(body),
+        });
+        module
+    }
+
+    #[test]
+    fn operand_widths() {
+        assert!(verify(&module(true)).is_ok());
+
+        let error = verify(&module(false)).unwrap_err();
+        assert_eq!(error.message, "operand width mismatch");
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::{iter, mem};
+
+use adpl_hir::{
+    self as hir, Context, Definition, ExprKind, Expression, Index, Inline,
+    List, Literal, Local, LocalKind, Statement, StmtKind,
+};
+use adpl_util::with_sufficient_stack;
+
+use crate::{generic_params, walk_block, walk_expression};
+
+/// Callees with at most this many expressions are inlined unless they are
+/// marked `#[noinline]`.
+const INLINE_THRESHOLD: usize = 32;
+
+/// Replaces calls with the body of their callee.
+///
+/// A call is inlined if its callee has no `where` clause and is either marked
+/// `#[inline]`, or small and not marked `#[noinline]`. The requirement of a
+/// callee is checked on entry to it, so inlining the callee would drop the
+/// check. Recursive calls are never inlined, and neither are calls that rely
+/// on the implicit conversion of an argument or the return value to a
+/// declared type.
+pub fn inline(ctx: &mut Context) {
+    let mut inliner = Inliner {
+        ctx,
+        states: HashMap::new(),
+    };
+
+    for def in inliner.ctx.defs.keys() {
+        inliner.visit(def);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    InProgress,
+    Done,
+}
+
+#[derive(Clone, Copy)]
+enum Subst {
+    Local(Index<Local>),
+    Expr(Index<Expression>),
+}
+
+struct Inliner<'a> {
+    ctx: &'a mut Context,
+    states: HashMap<Index<Definition>, State>,
+}
+
+impl Inliner<'_> {
+    fn visit(&mut self, def: Index<Definition>) {
+        if self.states.contains_key(&def) {
+            return;
+        }
+
+        self.states.insert(def, State::InProgress);
+
+        if let Some(body) = self.ctx[def].body {
+            let mut callees = Vec::new();
+
+            walk_block(self.ctx, body, &mut |expr| {
+                if let ExprKind::Call(ref call) = self.ctx[expr].kind {
+                    callees.push(call.callee);
+                }
+            });
+
+            for callee in callees {
+                with_sufficient_stack(|| self.visit(callee));
+            }
+
+            let body = self.inline_block(def, body);
+            self.ctx.defs[def].body = Some(body);
+        }
+
+        self.states.insert(def, State::Done);
+    }
+
+    fn inline_block(
+        &mut self,
+        def: Index<Definition>,
+        block: List<Statement>,
+    ) -> List<Statement> {
+        let mut stmts = Vec::with_capacity(block.len());
+
+        for i in 0..block.len() {
+            let stmt = self.ctx[block][i];
+
+            match self.ctx[stmt].kind {
+                StmtKind::Assign(_, expr) | StmtKind::Return(expr) => {
+                    let mut calls = Vec::new();
+
+                    collect_calls(self.ctx, expr, &mut calls);
+
+                    for call in calls {
+                        if self.should_inline(def, call) {
+                            self.inline_call(call, &mut stmts);
+                        }
+                    }
+                }
+                StmtKind::Unsafe(inner) => {
+                    let inner = self.inline_block(def, inner);
+                    self.ctx[stmt].kind = StmtKind::Unsafe(inner);
+                }
+            }
+
+            stmts.push(stmt);
+        }
+
+        if self.ctx[block] == stmts[..] {
+            block
+        } else {
+            self.ctx.lists.extend(stmts)
+        }
+    }
+
+    fn should_inline(
+        &self,
+        caller: Index<Definition>,
+        index: Index<Expression>,
+    ) -> bool {
+        let ExprKind::Call(ref call) = self.ctx[index].kind else {
+            unreachable!("expected a call");
+        };
+
+        let callee = &self.ctx[call.callee];
+
+        if callee.inline == Inline::Never
+            || callee.requires.is_some()
+            || self.states.get(&call.callee) != Some(&State::Done)
+        {
+            return false;
+        }
+
+        let Some(body) = callee.body else {
+            return false;
+        };
+
+        if callee.inline == Inline::Auto
+            && block_size(self.ctx, body) > INLINE_THRESHOLD
+        {
+            return false;
+        }
+
+        let Some(ret) = find_return(self.ctx, body) else {
+            return false;
+        };
+
+        let generics = generic_params(&self.ctx[caller]);
+        let mut cx = hir::TypeCx::new(self.ctx, caller, &generics);
+
+        let Ok(args) = cx.eval_consts(call.generics) else {
+            return false;
+        };
+
+        for (&arg, param) in
+            iter::zip(&self.ctx[call.args], &self.ctx.params[callee.inputs])
+        {
+            match (cx.type_of(arg), self.ctx.resolve_type(param.ty, &args)) {
+                (Ok(found), Ok(expected)) if found == expected => {}
+                _ => return false,
+            }
+        }
+
+        let mut cx = hir::TypeCx::new(self.ctx, call.callee, &args);
+
+        matches!(
+            (cx.type_of(ret), cx.output_type()),
+            (Ok(found), Ok(expected)) if found == expected,
+        )
+    }
+
+    fn inline_call(
+        &mut self,
+        index: Index<Expression>,
+        stmts: &mut Vec<Index<Statement>>,
+    ) {
+        let ExprKind::Call(ref call) = self.ctx[index].kind else {
+            unreachable!("expected a call");
+        };
+
+        let (generics, args) = (call.generics, call.args);
+        let callee = &self.ctx[call.callee];
+        let (params, body) = (callee.inputs, callee.body.unwrap());
+
+        let mut substs = iter::zip(callee.generics, &self.ctx[generics])
+            .map(|(local, &arg)| (local, Subst::Expr(arg)))
+            .collect::<HashMap<_, _>>();
+
+        for (param, arg) in iter::zip(params, self.ctx[args].to_vec()) {
+            let param = self.ctx[param].local;
+            let name = self.ctx[param].name;
+
+            let local = self.ctx.add(Local {
+                kind: LocalKind::Let(arg),
+                name,
+            });
+
+            stmts.push(self.ctx.add(Statement {
+                kind: StmtKind::Assign(local, arg),
+            }));
+
+            substs.insert(param, Subst::Local(local));
+        }
+
+        let ret = self.copy_block(body, &mut substs, stmts).unwrap();
+        let placeholder = ExprKind::Lit(Literal { value: 0 });
+
+        self.ctx[index].kind =
+            mem::replace(&mut self.ctx[ret].kind, placeholder);
+    }
+
+    fn copy_block(
+        &mut self,
+        block: List<Statement>,
+        substs: &mut HashMap<Index<Local>, Subst>,
+        stmts: &mut Vec<Index<Statement>>,
+    ) -> Option<Index<Expression>> {
+        for i in 0..block.len() {
+            let stmt = self.ctx[block][i];
+
+            match self.ctx[stmt].kind {
+                StmtKind::Assign(local, expr) => {
+                    let expr = self.copy_expression(expr, substs);
+                    let name = self.ctx[local].name;
+
+                    let copy = self.ctx.add(Local {
+                        kind: LocalKind::Let(expr),
+                        name,
+                    });
+
+                    stmts.push(self.ctx.add(Statement {
+                        kind: StmtKind::Assign(copy, expr),
+                    }));
+
+                    substs.insert(local, Subst::Local(copy));
+                }
+                StmtKind::Return(expr) => {
+                    return Some(self.copy_expression(expr, substs));
+                }
+                StmtKind::Unsafe(block) => {
+                    if let Some(ret) = self.copy_block(block, substs, stmts) {
+                        return Some(ret);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn copy_expression(
+        &mut self,
+        index: Index<Expression>,
+        substs: &HashMap<Index<Local>, Subst>,
+    ) -> Index<Expression> {
+        with_sufficient_stack(|| {
+            let span = self.ctx[index].span;
+
+            let kind = match self.ctx[index].kind {
+                ExprKind::Id(local) => match substs.get(&local) {
+                    Some(&Subst::Local(local)) => ExprKind::Id(local),
+                    Some(&Subst::Expr(expr)) => {
+                        return self.copy_expression(expr, substs);
+                    }
+                    None => ExprKind::Id(local),
+                },
+                ExprKind::Lit(ref lit) => ExprKind::Lit(lit.clone()),
+                ExprKind::Field(base, name) => {
+                    ExprKind::Field(self.copy_expression(base, substs), name)
+                }
+                ExprKind::Unary(op, operand) => {
+                    ExprKind::Unary(op, self.copy_expression(operand, substs))
+                }
+                ExprKind::Binary(op, lhs, rhs) => ExprKind::Binary(
+                    op,
+                    self.copy_expression(lhs, substs),
+                    self.copy_expression(rhs, substs),
+                ),
+                ExprKind::Call(ref call) => {
+                    let (name, callee) = (call.name, call.callee);
+                    let (generics, args) = (call.generics, call.args);
+
+                    ExprKind::Call(hir::Call {
+                        name,
+                        callee,
+                        generics: self.copy_list(generics, substs),
+                        args: self.copy_list(args, substs),
+                    })
+                }
+                ExprKind::Record(ref cons) => {
+                    let (name, record) = (cons.name, cons.record);
+                    let (generics, inits) = (cons.generics, cons.inits);
+
+                    ExprKind::Record(hir::Constructor {
+                        name,
+                        record,
+                        generics: self.copy_list(generics, substs),
+                        inits: self.copy_list(inits, substs),
+                    })
+                }
+            };
+
+            self.ctx.add(Expression { kind, span })
+        })
+    }
+
+    fn copy_list(
+        &mut self,
+        list: List<Expression>,
+        substs: &HashMap<Index<Local>, Subst>,
+    ) -> List<Expression> {
+        let exprs = (0..list.len())
+            .map(|i| {
+                let expr = self.ctx[list][i];
+                self.copy_expression(expr, substs)
+            })
+            .collect::<Vec<_>>();
+
+        self.ctx.lists.extend(exprs)
+    }
+}
+
+fn collect_calls(
+    ctx: &Context,
+    expr: Index<Expression>,
+    calls: &mut Vec<Index<Expression>>,
+) {
+    walk_expression(ctx, expr, &mut |expr| {
+        if let ExprKind::Call(_) = ctx[expr].kind {
+            calls.push(expr);
+        }
+    });
+}
+
+fn block_size(ctx: &Context, block: List<Statement>) -> usize {
+    let mut size = 0;
+    walk_block(ctx, block, &mut |_| size += 1);
+
+    size
+}
+
+fn find_return(
+    ctx: &Context,
+    block: List<Statement>,
+) -> Option<Index<Expression>> {
+    ctx[block].iter().find_map(|&stmt| match ctx[stmt].kind {
+        StmtKind::Assign(..) => None,
+        StmtKind::Return(expr) => Some(expr),
+        StmtKind::Unsafe(block) => find_return(ctx, block),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use adpl_hir::{Context, Definition, ExprKind, Index, StmtKind};
+
+    use super::{INLINE_THRESHOLD, inline};
+    use crate::{lower_source, walk_block};
+
+    const SRC: &str = "
+#[inline]
+def checked(a: uint[8]) -> uint[8] where a > 0 {
+    return a - 1;
+}
+
+def guarded(a: uint[8]) -> uint[8] where a > 0 {
+    return a - 1;
+}
+
+def small(a: uint[8]) -> uint[8] {
+    return a + 1;
+}
+
+#[noinline]
+def kept(a: uint[8]) -> uint[8] {
+    return a + 2;
+}
+
+#[inline]
+def forced(a: uint[8]) -> uint[8] {
+    return a + 3;
+}
+
+def f(a: uint[8]) -> uint[8] {
+    return checked(a) + guarded(a) + small(a) + kept(a) + forced(a);
+}
+";
+
+    fn find(ctx: &Context, def: &str) -> Index<Definition> {
+        ctx.defs
+            .iter()
+            .find(|(_, decl)| &ctx.symbols[decl.name.symbol] == def)
+            .map(|(index, _)| index)
+            .unwrap()
+    }
+
+    /// Names of the definitions that `def` still calls.
+    fn callees(ctx: &Context, def: &str) -> Vec<String> {
+        let body = ctx[find(ctx, def)].body.unwrap();
+        let mut callees = Vec::new();
+
+        walk_block(ctx, body, &mut |expr| {
+            if let ExprKind::Call(ref call) = ctx[expr].kind {
+                callees.push(ctx.symbols[call.name.symbol].to_string());
+            }
+        });
+
+        callees
+    }
+
+    #[test]
+    fn requirements_are_kept() {
+        let mut ctx = lower_source(SRC);
+        inline(&mut ctx);
+
+        assert_eq!(callees(&ctx, "f"), ["checked", "guarded", "kept"]);
+    }
+
+    #[test]
+    fn recursion() {
+        let mut ctx = lower_source(
+            "
+def g(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+#[inline]
+def f(a: uint[8]) -> uint[8] {
+    return g(a) + 1;
+}
+",
+        );
+
+        // Calls can only refer to earlier definitions in the source, so the
+        // call is pointed back at its caller by hand.
+        let f = find(&ctx, "f");
+        let mut calls = Vec::new();
+
+        walk_block(&ctx, ctx[f].body.unwrap(), &mut |expr| {
+            if let ExprKind::Call(_) = ctx[expr].kind {
+                calls.push(expr);
+            }
+        });
+
+        for call in calls {
+            let ExprKind::Call(ref mut call) = ctx[call].kind else {
+                unreachable!();
+            };
+
+            call.callee = f;
+        }
+
+        inline(&mut ctx);
+
+        assert_eq!(ctx[ctx[f].body.unwrap()].len(), 1);
+        assert_eq!(callees(&ctx, "f"), ["g"]);
+    }
+
+    #[test]
+    fn size_threshold() {
+        let body = |terms: usize| {
+            let mut body = String::from("a");
+
+            for _ in 0..terms {
+                body.push_str(" + 1");
+            }
+
+            body
+        };
+
+        // Every term adds a literal and an addition to the first `a`.
+        let size = |terms: usize| 1 + 2 * terms;
+        assert!(size(15) <= INLINE_THRESHOLD && size(16) > INLINE_THRESHOLD);
+
+        let mut src = String::new();
+
+        for (name, attr, terms) in [
+            ("fits", "", 15),
+            ("large", "", 16),
+            ("forced", "#[inline]", 16),
+        ] {
+            write!(
+                src,
+                "{attr}\ndef {name}(a: uint[8]) -> uint[8] {{\n    \
+                 return {};\n}}\n\n",
+                body(terms),
+            )
+            .unwrap();
+        }
+
+        src.push_str(
+            "def f(a: uint[8]) -> uint[8] {\n    \
+             return fits(a) + large(a) + forced(a);\n}\n",
+        );
+
+        let mut ctx = lower_source(&src);
+        inline(&mut ctx);
+
+        assert_eq!(callees(&ctx, "f"), ["large"]);
+    }
+
+    #[test]
+    fn conversions() {
+        let mut ctx = lower_source(
+            "
+def id[W](a: uint[W]) -> uint[W] {
+    return a;
+}
+
+def wide(a: uint[8]) -> uint[16] {
+    return a;
+}
+
+def f(a: uint[8]) -> uint[16] {
+    return id[8](a) + id[16](a) + wide(a);
+}
+",
+        );
+
+        inline(&mut ctx);
+
+        // `id[16]` would convert its argument and `wide` its return value.
+        assert_eq!(callees(&ctx, "f"), ["id", "wide"]);
+    }
+
+    #[test]
+    fn nested_unsafe() {
+        let mut ctx = lower_source(
+            "
+def g(a: uint[8]) -> uint[8] {
+    unsafe {
+        b = a + 1;
+        unsafe {
+            return b * 2;
+        }
+    }
+}
+
+def f(a: uint[8]) -> uint[8] {
+    return g(a) + 3;
+}
+",
+        );
+
+        inline(&mut ctx);
+
+        assert!(callees(&ctx, "f").is_empty());
+
+        // The statements of the nested blocks are hoisted into `f` before the
+        // statement that used the call, after the argument.
+        let body = ctx[find(&ctx, "f")].body.unwrap();
+        let lets = ctx[body]
+            .iter()
+            .filter_map(|&stmt| match ctx[stmt].kind {
+                StmtKind::Assign(local, _) => {
+                    Some(ctx.symbols[ctx[local].name.symbol].to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(lets, ["a", "b"]);
+
+        let &[.., ret] = &ctx[body][..] else {
+            unreachable!();
+        };
+        let StmtKind::Return(ret) = ctx[ret].kind else {
+            panic!("`f` no longer ends in a return");
+        };
+        let ExprKind::Binary(_, lhs, _) = ctx[ret].kind else {
+            unreachable!();
+        };
+
+        assert!(matches!(ctx[lhs].kind, ExprKind::Binary(..)));
+    }
+}
@@ -0,0 +1,58 @@
+mod inline;
+mod simplify;
+
+use adpl_hir::{
+    Const, Context, Definition, Expression, Index, List, Statement, Visitor,
+};
+
+pub use inline::inline;
+pub use simplify::simplify;
+
+/// Generic arguments that leave every generic parameter of `def` symbolic.
+fn generic_params(def: &Definition) -> Box<[Const]> {
+    (0..def.generics.len())
+        .map(|i| Const::Param(i.try_into().unwrap()))
+        .collect()
+}
+
+/// Calls `f` on every expression in `block`, operands before their users.
+fn walk_block<F>(ctx: &Context, block: List<Statement>, f: &mut F)
+where
+    F: FnMut(Index<Expression>),
+{
+    PostOrder(f).visit_block(ctx, block);
+}
+
+/// Calls `f` on `index` and all of its subexpressions, operands before their
+/// users.
+fn walk_expression<F>(ctx: &Context, index: Index<Expression>, f: &mut F)
+where
+    F: FnMut(Index<Expression>),
+{
+    PostOrder(f).visit_expression(ctx, index);
+}
+
+struct PostOrder<'f, F>(&'f mut F);
+
+impl<F> Visitor for PostOrder<'_, F>
+where
+    F: FnMut(Index<Expression>),
+{
+    fn visit_expression(&mut self, ctx: &Context, expr: Index<Expression>) {
+        adpl_hir::walk_expression(self, ctx, expr);
+        (self.0)(expr);
+    }
+}
+
+#[cfg(test)]
+fn lower_source(src: &str) -> Context {
+    let mut files = adpl_util::Files::new();
+    let file = files.add("test.adpl", src);
+    let mut reporter = adpl_util::Reporter::new(&files);
+    let symbols = adpl_ast::Symbols::new();
+
+    let ast =
+        adpl_parse::parse(file, src, &symbols).expect("test source parses");
+    adpl_ast_lowering::lower_ast(&ast, &symbols, &mut reporter)
+        .expect("test source lowers")
+}
@@ -0,0 +1,590 @@
+use std::collections::HashMap;
+use std::mem;
+
+use adpl_hir::{
+    self as hir, BinaryKind, BinaryOp, Context, ExprKind, Expression, Index,
+    Literal, Ty, UnaryKind, UnaryOp, eval_binary, eval_unary,
+};
+
+use adpl_util::with_sufficient_stack;
+
+use crate::{generic_params, walk_block, walk_expression};
+
+/// Folds constant subexpressions and applies algebraic identities, rewriting
+/// expressions in place.
+///
+/// Every rewrite keeps the type of the expression it replaces, so the width an
+/// expression is evaluated at never changes. Constants can only be produced
+/// for `{integer}` expressions, and comparisons with a known result become
+/// `0 = 0` or `0 != 0`; any other `uint[N]` expression is only ever replaced
+/// by one of its own operands or a cheaper operation of the same type.
+pub fn simplify(ctx: &mut Context) {
+    for def in ctx.defs.keys() {
+        simplify_definition(ctx, def);
+    }
+}
+
+fn simplify_definition(ctx: &mut Context, def: Index<hir::Definition>) {
+    let decl = &ctx[def];
+
+    let generics = generic_params(decl);
+    let mut order = Vec::new();
+    let mut push = |expr| order.push(expr);
+
+    for expr in decl.requires.into_iter().chain(decl.implements) {
+        walk_expression(ctx, expr.get(), &mut push);
+    }
+
+    if let Some(body) = decl.body {
+        walk_block(ctx, body, &mut push);
+    }
+
+    let mut cx = hir::TypeCx::new(ctx, def, &generics);
+
+    let types = order
+        .iter()
+        .filter_map(|&expr| Some((expr, cx.type_of(expr).ok()?)))
+        .collect();
+
+    let mut simplifier = Simplifier { ctx, types };
+
+    for expr in order {
+        simplifier.simplify(expr);
+    }
+}
+
+enum Rewrite {
+    /// Replace the expression with a literal.
+    Lit(u64),
+    /// Replace the expression with one of its operands.
+    Move(Index<Expression>),
+    /// Apply a unary operator to the given operand.
+    Unary(UnaryKind, Index<Expression>),
+    /// Replace a comparison with `0 = 0` if true or `0 != 0` if false,
+    /// reusing the operand expressions for the literals.
+    Bool(bool, Index<Expression>, Index<Expression>),
+    /// Shift the given operand by a constant, reusing the literal expression
+    /// for the shift amount.
+    Shift(BinaryKind, Index<Expression>, Index<Expression>, u64),
+}
+
+struct Simplifier<'a> {
+    ctx: &'a mut Context,
+    types: HashMap<Index<Expression>, Ty>,
+}
+
+impl Simplifier<'_> {
+    fn simplify(&mut self, index: Index<Expression>) {
+        let Some(ty) = self.types.get(&index) else {
+            return;
+        };
+
+        let rewrite = match self.ctx[index].kind {
+            ExprKind::Unary(op, operand) => {
+                self.simplify_unary(ty, op, operand)
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                self.simplify_binary(ty, op, lhs, rhs)
+            }
+            _ => None,
+        };
+
+        let Some(rewrite) = rewrite else {
+            return;
+        };
+
+        let span = self.ctx[index].span;
+
+        self.ctx[index].kind = match rewrite {
+            Rewrite::Lit(value) => ExprKind::Lit(Literal { value }),
+            Rewrite::Move(operand) => {
+                let placeholder = ExprKind::Lit(Literal { value: 0 });
+                mem::replace(&mut self.ctx[operand].kind, placeholder)
+            }
+            Rewrite::Unary(kind, operand) => {
+                ExprKind::Unary(UnaryOp { kind, span }, operand)
+            }
+            Rewrite::Bool(value, lhs, rhs) => {
+                self.ctx[lhs].kind = ExprKind::Lit(Literal { value: 0 });
+                self.ctx[rhs].kind = ExprKind::Lit(Literal { value: 0 });
+
+                let kind = if value {
+                    BinaryKind::Eq
+                } else {
+                    BinaryKind::Ne
+                };
+
+                ExprKind::Binary(BinaryOp { kind, span }, lhs, rhs)
+            }
+            Rewrite::Shift(kind, operand, amount, value) => {
+                self.ctx[amount].kind = ExprKind::Lit(Literal { value });
+
+                ExprKind::Binary(BinaryOp { kind, span }, operand, amount)
+            }
+        };
+    }
+
+    fn simplify_unary(
+        &self,
+        ty: &Ty,
+        op: UnaryOp,
+        operand: Index<Expression>,
+    ) -> Option<Rewrite> {
+        if let Some(value) = self.literal(operand) {
+            return constant(ty, eval_unary(op.kind, value, u64::BITS));
+        }
+
+        // `--x` and `!!x` are the identity, since `x` already fits its width.
+        match self.ctx[operand].kind {
+            ExprKind::Unary(inner, x) if inner.kind == op.kind => {
+                self.keep(ty, x)
+            }
+            _ => None,
+        }
+    }
+
+    fn simplify_binary(
+        &self,
+        ty: &Ty,
+        op: BinaryOp,
+        lhs: Index<Expression>,
+        rhs: Index<Expression>,
+    ) -> Option<Rewrite> {
+        use BinaryKind::*;
+
+        let (l, r) = (self.literal(lhs), self.literal(rhs));
+
+        if let (Some(l), Some(r)) = (l, r) {
+            let value = eval_binary(op.kind, l, r, u64::BITS);
+
+            return match op.kind {
+                Eq | Ne | Gt | Ge | Lt | Le => {
+                    Some(Rewrite::Bool(value != 0, lhs, rhs))
+                }
+                _ => constant(ty, value),
+            };
+        }
+
+        if self.same(lhs, rhs) {
+            match op.kind {
+                Eq | Ge | Le => return Some(Rewrite::Bool(true, lhs, rhs)),
+                Ne | Gt | Lt => return Some(Rewrite::Bool(false, lhs, rhs)),
+                _ => {}
+            }
+        }
+
+        match (op.kind, l, r) {
+            (Add | Sub | Shl | Shr, _, Some(0))
+            | (Mul | Div | Pow, _, Some(1)) => self.keep(ty, lhs),
+            (Add, Some(0), _) | (Mul, Some(1), _) => self.keep(ty, rhs),
+            (Sub, Some(0), _) if self.has_type(rhs, ty) => {
+                Some(Rewrite::Unary(UnaryKind::Neg, rhs))
+            }
+            (Mul, _, Some(0)) | (Mul | Shl | Shr, Some(0), _) => {
+                constant(ty, 0)
+            }
+            (Pow, _, Some(0)) | (Pow, Some(1), _) => constant(ty, 1),
+            (Shl | Shr, _, Some(amount)) if amount >= u64::BITS.into() => {
+                constant(ty, 0)
+            }
+            (Mul, _, Some(r)) if r.is_power_of_two() => {
+                self.shift(ty, Shl, lhs, rhs, r.trailing_zeros().into())
+            }
+            (Mul, Some(l), _) if l.is_power_of_two() => {
+                self.shift(ty, Shl, rhs, lhs, l.trailing_zeros().into())
+            }
+            (Div, _, Some(r)) if r.is_power_of_two() => {
+                self.shift(ty, Shr, lhs, rhs, r.trailing_zeros().into())
+            }
+            // Every value is at least zero.
+            (Ge, _, Some(0)) | (Le, Some(0), _) => {
+                Some(Rewrite::Bool(true, lhs, rhs))
+            }
+            (Lt, _, Some(0)) | (Gt, Some(0), _) => {
+                Some(Rewrite::Bool(false, lhs, rhs))
+            }
+            // Comparing a `uint[1]` against a bit is the bit or its inverse.
+            (Eq | Ge, _, Some(1)) | (Ne | Gt, _, Some(0)) => self.keep(ty, lhs),
+            (Eq | Le, Some(1), _) | (Ne | Lt, Some(0), _) => self.keep(ty, rhs),
+            (Eq | Le, _, Some(0)) | (Ne | Lt, _, Some(1)) => {
+                self.invert(ty, lhs)
+            }
+            (Eq | Ge, Some(0), _) | (Ne | Gt, Some(1), _) => {
+                self.invert(ty, rhs)
+            }
+            (Shl | Shr, _, Some(amount)) => match self.ctx[lhs].kind {
+                ExprKind::Binary(inner, x, inner_rhs)
+                    if inner.kind == op.kind =>
+                {
+                    let inner_amount = self.literal(inner_rhs)?;
+                    let amount = amount.saturating_add(inner_amount);
+
+                    self.shift(ty, op.kind, x, rhs, amount)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn keep(&self, ty: &Ty, operand: Index<Expression>) -> Option<Rewrite> {
+        self.has_type(operand, ty).then_some(Rewrite::Move(operand))
+    }
+
+    fn invert(&self, ty: &Ty, operand: Index<Expression>) -> Option<Rewrite> {
+        self.has_type(operand, ty)
+            .then_some(Rewrite::Unary(UnaryKind::Not, operand))
+    }
+
+    fn shift(
+        &self,
+        ty: &Ty,
+        kind: BinaryKind,
+        operand: Index<Expression>,
+        amount: Index<Expression>,
+        value: u64,
+    ) -> Option<Rewrite> {
+        self.has_type(operand, ty)
+            .then_some(Rewrite::Shift(kind, operand, amount, value))
+    }
+
+    fn has_type(&self, expr: Index<Expression>, ty: &Ty) -> bool {
+        self.types.get(&expr) == Some(ty)
+    }
+
+    /// Whether `a` and `b` are the same expression, and so have the same
+    /// value.
+    fn same(&self, a: Index<Expression>, b: Index<Expression>) -> bool {
+        with_sufficient_stack(|| match (&self.ctx[a].kind, &self.ctx[b].kind) {
+            (ExprKind::Id(a), ExprKind::Id(b)) => a == b,
+            (ExprKind::Lit(a), ExprKind::Lit(b)) => a.value == b.value,
+            (&ExprKind::Field(a, a_name), &ExprKind::Field(b, b_name)) => {
+                a_name.symbol == b_name.symbol && self.same(a, b)
+            }
+            (&ExprKind::Unary(a_op, a), &ExprKind::Unary(b_op, b)) => {
+                a_op.kind == b_op.kind && self.same(a, b)
+            }
+            (
+                &ExprKind::Binary(a_op, a_lhs, a_rhs),
+                &ExprKind::Binary(b_op, b_lhs, b_rhs),
+            ) => {
+                a_op.kind == b_op.kind
+                    && self.same(a_lhs, b_lhs)
+                    && self.same(a_rhs, b_rhs)
+            }
+            _ => false,
+        })
+    }
+
+    fn literal(&self, expr: Index<Expression>) -> Option<u64> {
+        match self.ctx[expr].kind {
+            ExprKind::Lit(ref lit) => Some(lit.value),
+            _ => None,
+        }
+    }
+}
+
+fn constant(ty: &Ty, value: u64) -> Option<Rewrite> {
+    (*ty == Ty::Int).then_some(Rewrite::Lit(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use adpl_hir::{
+        self as hir, Const, Context, ExprKind, Expression, Index, List,
+        LocalKind, Statement, StmtKind, Ty, TypeCx, eval_binary, eval_unary,
+        mask,
+    };
+
+    use super::simplify;
+    use crate::lower_source;
+
+    const XS: &[u64] = &[0, 1, 2, 3, 127, 128, 255];
+    const CS: &[u64] = &[0, 1];
+    const NS: &[u64] = &[0, 1, 2, 63, 64, 65, u64::MAX];
+
+    /// Evaluates the body of a definition the way the generated code does:
+    /// every operation is done on 64 bits and its result is truncated to the
+    /// width of its type. Fields, calls and constructors evaluate to `None`,
+    /// and so does anything that depends on them.
+    struct Eval<'a> {
+        ctx: &'a Context,
+        cx: TypeCx<'a>,
+        locals: HashMap<Index<hir::Local>, u64>,
+    }
+
+    impl Eval<'_> {
+        fn block(&mut self, block: List<Statement>) -> Option<u64> {
+            for &stmt in &self.ctx[block] {
+                match self.ctx[stmt].kind {
+                    StmtKind::Assign(local, expr) => {
+                        if let Some(value) = self.expression(expr) {
+                            self.locals.insert(local, value);
+                        }
+                    }
+                    StmtKind::Return(expr) => return self.expression(expr),
+                    StmtKind::Unsafe(block) => {
+                        if let Some(value) = self.block(block) {
+                            return Some(value);
+                        }
+                    }
+                }
+            }
+
+            None
+        }
+
+        fn expression(&mut self, index: Index<Expression>) -> Option<u64> {
+            let width = width(&self.cx.type_of(index).unwrap());
+
+            match self.ctx[index].kind {
+                ExprKind::Id(local) => match self.ctx[local].kind {
+                    LocalKind::GenericParam(i) => {
+                        match self.cx.generics()[i as usize] {
+                            Const::Value(value) => Some(value),
+                            Const::Param(_) => unreachable!(),
+                        }
+                    }
+                    _ => self.locals.get(&local).copied(),
+                },
+                ExprKind::Lit(ref lit) => Some(lit.value),
+                ExprKind::Unary(op, operand) => {
+                    let operand = self.expression(operand)?;
+                    Some(eval_unary(op.kind, operand, width))
+                }
+                ExprKind::Binary(op, lhs, rhs) => {
+                    let lhs = self.expression(lhs)?;
+                    let rhs = self.expression(rhs)?;
+                    Some(eval_binary(op.kind, lhs, rhs, width))
+                }
+                ExprKind::Field(..)
+                | ExprKind::Call(_)
+                | ExprKind::Record(_) => None,
+            }
+        }
+    }
+
+    fn width(ty: &Ty) -> u32 {
+        match *ty {
+            Ty::UInt(Const::Value(width)) => width.try_into().unwrap(),
+            _ => u64::BITS,
+        }
+    }
+
+    fn eval(ctx: &Context, n: u64, args: &[u64]) -> Option<u64> {
+        let (def, decl) = ctx.defs.iter().next_back().unwrap();
+        let generics = [Const::Value(n)];
+        let cx = TypeCx::new(ctx, def, &generics);
+        let output = width(&cx.output_type().unwrap());
+
+        let mut eval = Eval {
+            ctx,
+            cx,
+            locals: HashMap::new(),
+        };
+
+        for (param, &arg) in ctx.params[decl.inputs].iter().zip(args) {
+            let ty = eval.cx.resolve_type(param.ty).unwrap();
+            eval.locals.insert(param.local, arg & mask(width(&ty)));
+        }
+
+        eval.block(decl.body.unwrap())
+            .map(|value| value & mask(output))
+    }
+
+    /// Renders the returned expression, with operators prefixed.
+    fn shape(ctx: &Context) -> String {
+        fn list(ctx: &Context, exprs: List<Expression>) -> String {
+            ctx[exprs]
+                .iter()
+                .map(|&expr| expression(ctx, expr))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+
+        fn expression(ctx: &Context, index: Index<Expression>) -> String {
+            match ctx[index].kind {
+                ExprKind::Id(local) => {
+                    ctx.symbols[ctx[local].name.symbol].to_string()
+                }
+                ExprKind::Lit(ref lit) => lit.value.to_string(),
+                ExprKind::Unary(op, operand) => {
+                    format!("({:?} {})", op.kind, expression(ctx, operand))
+                }
+                ExprKind::Binary(op, lhs, rhs) => format!(
+                    "({:?} {} {})",
+                    op.kind,
+                    expression(ctx, lhs),
+                    expression(ctx, rhs),
+                ),
+                ExprKind::Field(base, ref name) => format!(
+                    "(. {} {})",
+                    expression(ctx, base),
+                    &ctx.symbols[name.symbol],
+                ),
+                ExprKind::Call(ref call) => format!(
+                    "(call {} [{}] {})",
+                    &ctx.symbols[call.name.symbol],
+                    list(ctx, call.generics),
+                    list(ctx, call.args),
+                ),
+                ExprKind::Record(ref cons) => format!(
+                    "(new {} [{}] {})",
+                    &ctx.symbols[cons.name.symbol],
+                    list(ctx, cons.generics),
+                    list(ctx, cons.inits),
+                ),
+            }
+        }
+
+        let decl = ctx.defs.values().next_back().unwrap();
+        let &[stmt] = &ctx[decl.body.unwrap()][..] else {
+            unreachable!();
+        };
+        let StmtKind::Return(expr) = ctx[stmt].kind else {
+            unreachable!();
+        };
+
+        expression(ctx, expr)
+    }
+
+    /// Checks that `body` simplifies to `expected` without changing its value
+    /// for any of the sample inputs that the evaluator can evaluate.
+    #[track_caller]
+    fn check(body: &str, expected: &str) {
+        let src = format!(
+            "def g[W](a: uint[W]) -> uint[W] {{\n    return a;\n}}\n\n\
+             def f[N](x: uint[8], y: uint[8], c: uint[1]) -> uint[8] {{\n\
+             return {body};\n}}\n",
+        );
+        let mut ctx = lower_source(&src);
+
+        let mut before = Vec::new();
+
+        for &n in NS {
+            for &x in XS {
+                for &y in XS {
+                    for &c in CS {
+                        before.push(eval(&ctx, n, &[x, y, c]));
+                    }
+                }
+            }
+        }
+
+        simplify(&mut ctx);
+        assert_eq!(shape(&ctx), expected, "simplifying `{body}`");
+
+        let mut before = before.into_iter();
+
+        for &n in NS {
+            for &x in XS {
+                for &y in XS {
+                    for &c in CS {
+                        let Some(before) = before.next().unwrap() else {
+                            continue;
+                        };
+
+                        assert_eq!(
+                            eval(&ctx, n, &[x, y, c]),
+                            Some(before),
+                            "`{body}` with N = {n}, x = {x}, y = {y}, c = {c}",
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn folding() {
+        check("x + -3", "(Add x 18446744073709551613)");
+        check("x + 2 * 3", "(Add x 6)");
+        check("x << 10 - 8", "(Shl x 2)");
+        check("x * 0", "(Mul x 0)");
+    }
+
+    #[test]
+    fn unary() {
+        check("--x", "x");
+        check("!!x", "x");
+        check("-!x", "(Neg (Not x))");
+    }
+
+    #[test]
+    fn identities() {
+        check("x + 0", "x");
+        check("x - 0", "x");
+        check("x << 0", "x");
+        check("x >> 0", "x");
+        check("x * 1", "x");
+        check("x / 1", "x");
+        check("x ^ 1", "x");
+        check("0 + x", "x");
+        check("1 * x", "x");
+        check("0 - x", "(Neg x)");
+        check("g[8](x) * 1 + 0", "(call g [8] x)");
+        check("x + g[8](y) * 0", "(Add x (Mul (call g [8] y) 0))");
+    }
+
+    #[test]
+    fn annihilators() {
+        check("N * 0", "0");
+        check("0 * N", "0");
+        check("0 << N", "0");
+        check("0 >> N", "0");
+        check("N ^ 0", "1");
+        check("1 ^ N", "1");
+        check("N << 64", "0");
+        check("N >> 70", "0");
+    }
+
+    #[test]
+    fn strength_reduction() {
+        check("x * 4", "(Shl x 2)");
+        check("4 * x", "(Shl x 2)");
+        check("x / 8", "(Shr x 3)");
+        check("x << 2 << 3", "(Shl x 5)");
+        check("x >> 1 >> 2", "(Shr x 3)");
+        check("x << 1 >> 1", "(Shr (Shl x 1) 1)");
+    }
+
+    #[test]
+    fn comparisons() {
+        check("2 < 3", "(Eq 0 0)");
+        check("3 < 2", "(Ne 0 0)");
+        check("x = x", "(Eq 0 0)");
+        check("x + y != x + y", "(Ne 0 0)");
+        check("x < x", "(Ne 0 0)");
+        check("x > x", "(Ne 0 0)");
+        check("x >= x", "(Eq 0 0)");
+        check("x <= x", "(Eq 0 0)");
+        check("x = y", "(Eq x y)");
+        check("x >= 0", "(Eq 0 0)");
+        check("0 <= x", "(Eq 0 0)");
+        check("x < 0", "(Ne 0 0)");
+        check("0 > x", "(Ne 0 0)");
+        check("x = 1", "(Eq x 1)");
+    }
+
+    #[test]
+    fn bits() {
+        check("c = 1", "c");
+        check("c >= 1", "c");
+        check("c != 0", "c");
+        check("c > 0", "c");
+        check("1 = c", "c");
+        check("1 <= c", "c");
+        check("0 != c", "c");
+        check("0 < c", "c");
+        check("c = 0", "(Not c)");
+        check("c <= 0", "(Not c)");
+        check("c != 1", "(Not c)");
+        check("c < 1", "(Not c)");
+        check("0 = c", "(Not c)");
+        check("0 >= c", "(Not c)");
+        check("1 != c", "(Not c)");
+        check("1 > c", "(Not c)");
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::Range;
+mod print;
 
 use chumsky::error::Cheap;
 use chumsky::extra;
@@ -6,28 +6,57 @@ use chumsky::input::{Input as _, MapExtra, Stream, ValueInput};
 use chumsky::pratt::{infix, left, postfix, prefix, right};
 use chumsky::primitive::{choice, group, just};
 use chumsky::recursive::recursive;
+use chumsky::span::{SimpleSpan, Span as _};
 use chumsky::{IterParser as _, Parser, select};
 
 use adpl_ast as ast;
 use adpl_lex::{Lexer, Token};
 
-pub type Span = Range<usize>;
+pub use print::{Print, Printer, print};
+
+pub type Span = SimpleSpan<usize, ast::FileId>;
 pub type Error = Cheap<Span>;
 
+fn to_ast(span: Span) -> ast::Span {
+    ast::Span::new(span.context, span.start, span.end)
+}
+
+/// Binding power of field access, the tightest operator.
+const FIELD_POWER: u16 = 7;
+/// Binding power of the prefix operators `-` and `!`.
+const PREFIX_POWER: u16 = 5;
+
+/// Binding power of a binary operator. Only `^` is right-associative.
+const fn binary_power(kind: ast::BinaryKind) -> u16 {
+    match kind {
+        ast::BinaryKind::Pow => 6,
+        ast::BinaryKind::Mul | ast::BinaryKind::Div => 4,
+        ast::BinaryKind::Add | ast::BinaryKind::Sub => 3,
+        ast::BinaryKind::Shl | ast::BinaryKind::Shr => 2,
+        ast::BinaryKind::Gt
+        | ast::BinaryKind::Ge
+        | ast::BinaryKind::Lt
+        | ast::BinaryKind::Le => 1,
+        ast::BinaryKind::Eq | ast::BinaryKind::Ne => 0,
+    }
+}
+
 enum AtomTail {
     Call(Vec<ast::Expression>),
     Record(Vec<ast::Assignment>),
 }
 
-fn parser<'tk, 'src, I>() -> impl Parser<'tk, I, ast::File, extra::Err<Error>>
+fn parser<'tk, 'src, I>(
+    symbols: &'tk ast::Symbols,
+) -> impl Parser<'tk, I, ast::File, extra::Err<Error>>
 where
     I: ValueInput<'tk, Token = Token<'src>, Span = Span>,
     'src: 'tk,
 {
     let id = select! {
         Token::Ident(symbol) = e => ast::Id {
-            symbol: ast::Symbol::from(symbol),
-            span: ast::Span::from(e.span()),
+            symbol: symbols.intern(symbol),
+            span: to_ast(e.span()),
         },
     };
 
@@ -96,52 +125,54 @@ where
         ))
         .map_with(|kind, e| ast::Expression {
             kind,
-            span: ast::Span::from(e.span()),
+            span: to_ast(e.span()),
         });
 
         let map_binary =
             |kind, e: &mut MapExtra<'tk, '_, I, _>| ast::BinaryOp {
                 kind,
-                span: ast::Span::from(e.span()),
+                span: to_ast(e.span()),
             };
 
         let fold_binary =
             |lhs, op, rhs, e: &mut MapExtra<'tk, '_, I, _>| ast::Expression {
                 kind: ast::ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
-                span: ast::Span::from(e.span()),
+                span: to_ast(e.span()),
             };
 
         atom.pratt((
-            postfix(7, just(Token::Dot).ignore_then(id), |lhs, id, e| {
-                ast::Expression {
+            postfix(
+                FIELD_POWER,
+                just(Token::Dot).ignore_then(id),
+                |lhs, id, e| ast::Expression {
                     kind: ast::ExprKind::Field(Box::new(lhs), id),
-                    span: ast::Span::from(e.span()),
-                }
-            }),
+                    span: to_ast(e.span()),
+                },
+            ),
             infix(
-                right(6),
+                right(binary_power(ast::BinaryKind::Pow)),
                 just(Token::Caret)
                     .to(ast::BinaryKind::Pow)
                     .map_with(map_binary),
                 fold_binary,
             ),
             prefix(
-                5,
+                PREFIX_POWER,
                 choice((
                     just(Token::Minus).to(ast::UnaryKind::Neg),
                     just(Token::Bang).to(ast::UnaryKind::Not),
                 ))
                 .map_with(|kind, e| ast::UnaryOp {
                     kind,
-                    span: ast::Span::from(e.span()),
+                    span: to_ast(e.span()),
                 }),
                 |op, rhs, e| ast::Expression {
                     kind: ast::ExprKind::Unary(op, Box::new(rhs)),
-                    span: ast::Span::from(e.span()),
+                    span: to_ast(e.span()),
                 },
             ),
             infix(
-                left(4),
+                left(binary_power(ast::BinaryKind::Mul)),
                 choice((
                     just(Token::Star).to(ast::BinaryKind::Mul),
                     just(Token::Slash).to(ast::BinaryKind::Div),
@@ -150,7 +181,7 @@ where
                 fold_binary,
             ),
             infix(
-                left(3),
+                left(binary_power(ast::BinaryKind::Add)),
                 choice((
                     just(Token::Plus).to(ast::BinaryKind::Add),
                     just(Token::Minus).to(ast::BinaryKind::Sub),
@@ -159,7 +190,7 @@ where
                 fold_binary,
             ),
             infix(
-                left(2),
+                left(binary_power(ast::BinaryKind::Shl)),
                 choice((
                     just(Token::Shl).to(ast::BinaryKind::Shl),
                     just(Token::Shr).to(ast::BinaryKind::Shr),
@@ -168,7 +199,7 @@ where
                 fold_binary,
             ),
             infix(
-                left(1),
+                left(binary_power(ast::BinaryKind::Gt)),
                 choice((
                     just(Token::Gt).to(ast::BinaryKind::Gt),
                     just(Token::Ge).to(ast::BinaryKind::Ge),
@@ -179,7 +210,7 @@ where
                 fold_binary,
             ),
             infix(
-                left(0),
+                left(binary_power(ast::BinaryKind::Eq)),
                 choice((
                     just(Token::Eq).to(ast::BinaryKind::Eq),
                     just(Token::Ne).to(ast::BinaryKind::Ne),
@@ -227,7 +258,7 @@ where
         .map_with(|(name, args), e| ast::Type {
             name,
             args: args.unwrap_or_else(Vec::new),
-            span: ast::Span::from(e.span()),
+            span: to_ast(e.span()),
         })
         .boxed();
 
@@ -243,7 +274,7 @@ where
         .map_with(|(name, ty), e| ast::Field {
             name,
             ty,
-            span: ast::Span::from(e.span()),
+            span: to_ast(e.span()),
         })
         .separated_by(just(Token::Comma))
         .allow_trailing()
@@ -273,7 +304,7 @@ where
         .map_with(|(name, ty), e| ast::Parameter {
             name,
             ty,
-            span: ast::Span::from(e.span()),
+            span: to_ast(e.span()),
         })
         .separated_by(just(Token::Comma))
         .allow_trailing()
@@ -311,20 +342,48 @@ where
         },
     );
 
-    choice((record, definition))
-        .map(|kind| ast::Item { kind })
+    let attr_args = id
+        .separated_by(just(Token::Comma))
+        .allow_trailing()
+        .collect()
+        .delimited_by(just(Token::OpenParen), just(Token::CloseParen));
+
+    let attr =
+        just(Token::Hash)
+            .ignore_then(id.then(attr_args.or_not()).delimited_by(
+                just(Token::OpenBracket),
+                just(Token::CloseBracket),
+            ))
+            .map_with(|(name, args), e| ast::Attribute {
+                name,
+                args,
+                span: to_ast(e.span()),
+            });
+
+    attr.repeated()
+        .collect()
+        .then(choice((record, definition)))
+        .map_with(|(attrs, kind), e| ast::Item {
+            attrs,
+            kind,
+            span: to_ast(e.span()),
+        })
         .repeated()
         .collect()
         .map(|items| ast::File { items })
 }
 
-pub fn parse(src: &str) -> Result<ast::File, Vec<Error>> {
+pub fn parse(
+    file: ast::FileId,
+    src: &str,
+    symbols: &ast::Symbols,
+) -> Result<ast::File, Vec<Error>> {
     let lexer = Lexer::new(src)
         .spanned()
-        .map(|(tk, span)| (tk.unwrap_or(Token::Error), span));
+        .map(|(tk, span)| (tk.unwrap_or(Token::Error), Span::new(file, span)));
 
-    let eoi = src.len()..src.len();
+    let eoi = Span::new(file, src.len()..src.len());
     let stream = Stream::from_iter(lexer).map(eoi, |tk| tk);
 
-    parser().parse(stream).into_result()
+    parser(symbols).parse(stream).into_result()
 }
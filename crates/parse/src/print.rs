@@ -0,0 +1,526 @@
+use adpl_ast as ast;
+use adpl_util::with_sufficient_stack;
+
+use crate::{FIELD_POWER, PREFIX_POWER, binary_power};
+
+/// Binding power of expressions that never need parentheses.
+const ATOM_POWER: u16 = FIELD_POWER + 1;
+
+/// A syntax tree node that can be rendered as source.
+pub trait Print {
+    fn print(&self, printer: &mut Printer<'_>);
+}
+
+pub struct Printer<'a> {
+    symbols: &'a ast::Symbols,
+    out: String,
+    indent: usize,
+}
+
+/// Renders `node` as source that [`parse`](crate::parse) turns back into the
+/// same tree, up to spans, using as few parentheses as the precedence of the
+/// operators allows.
+pub fn print<N: Print + ?Sized>(node: &N, symbols: &ast::Symbols) -> String {
+    let mut printer = Printer {
+        symbols,
+        out: String::new(),
+        indent: 0,
+    };
+
+    node.print(&mut printer);
+    printer.out
+}
+
+impl Printer<'_> {
+    fn word(&mut self, word: &str) {
+        self.out.push_str(word);
+    }
+
+    fn id(&mut self, id: &ast::Id) {
+        self.out.push_str(&self.symbols[id.symbol]);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        items: &[T],
+        open: &str,
+        close: &str,
+        mut f: impl FnMut(&mut Self, &T),
+    ) {
+        self.word(open);
+
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.word(", ");
+            }
+
+            f(self, item);
+        }
+
+        self.word(close);
+    }
+
+    /// Prints `[items]`, or nothing when there are no items.
+    fn generics<T>(&mut self, items: &[T], f: impl FnMut(&mut Self, &T)) {
+        if !items.is_empty() {
+            self.list(items, "[", "]", f);
+        }
+    }
+
+    fn attribute(&mut self, attr: &ast::Attribute) {
+        self.word("#[");
+        self.id(&attr.name);
+
+        if let Some(args) = &attr.args {
+            self.list(args, "(", ")", Self::id);
+        }
+
+        self.word("]");
+    }
+
+    fn record(&mut self, record: &ast::Record) {
+        self.word("struct ");
+        self.id(&record.name);
+        self.generics(&record.params, Self::id);
+        self.word(" {");
+
+        if !record.fields.is_empty() {
+            self.indent += 1;
+
+            for field in &record.fields {
+                self.newline();
+                self.field(field);
+                self.word(",");
+            }
+
+            self.indent -= 1;
+            self.newline();
+        }
+
+        self.word("}");
+    }
+
+    fn field(&mut self, field: &ast::Field) {
+        self.id(&field.name);
+        self.word(": ");
+        self.ty(&field.ty);
+    }
+
+    fn ty(&mut self, ty: &ast::Type) {
+        self.id(&ty.name);
+        self.generics(&ty.args, |p, arg| p.expression(arg, 0));
+    }
+
+    fn definition(&mut self, def: &ast::Definition) {
+        if def.safety == ast::Safety::Unsafe {
+            self.word("unsafe ");
+        }
+
+        self.word("def ");
+        self.id(&def.name);
+        self.generics(&def.generics, Self::id);
+        self.signature(&def.sig);
+
+        // An empty body after a clause that ends in a name would be parsed
+        // as a constructor, as in `where x > n {}`.
+        let guard = def.body.as_ref().is_some_and(|body| body.0.is_empty());
+        let last = def.implements.is_some();
+
+        if let Some(requires) = &def.requires {
+            self.clause("where", requires, guard && !last);
+        }
+
+        if let Some(implements) = &def.implements {
+            self.clause("implements", implements, guard);
+        }
+
+        match &def.body {
+            Some(body) => {
+                self.word(" ");
+                self.block(body);
+            }
+            None => self.word(";"),
+        }
+    }
+
+    fn clause(&mut self, keyword: &str, expr: &ast::Expression, guard: bool) {
+        self.word(" ");
+        self.word(keyword);
+        self.word(" ");
+
+        let start = self.out.len();
+        self.expression(expr, 0);
+
+        if guard && ends_with_name(&self.out[start..]) {
+            self.out.insert(start, '(');
+            self.out.push(')');
+        }
+    }
+
+    fn signature(&mut self, sig: &ast::Signature) {
+        self.list(&sig.inputs, "(", ")", Self::parameter);
+        self.word(" -> ");
+        self.ty(&sig.output);
+    }
+
+    fn parameter(&mut self, param: &ast::Parameter) {
+        self.id(&param.name);
+        self.word(": ");
+        self.ty(&param.ty);
+    }
+
+    fn block(&mut self, block: &ast::Block) {
+        self.word("{");
+
+        if !block.0.is_empty() {
+            self.indent += 1;
+
+            for stmt in &block.0 {
+                self.newline();
+                self.statement(stmt);
+            }
+
+            self.indent -= 1;
+            self.newline();
+        }
+
+        self.word("}");
+    }
+
+    fn statement(&mut self, stmt: &ast::Statement) {
+        with_sufficient_stack(|| match &stmt.kind {
+            ast::StmtKind::Assign(assign) => {
+                self.assignment(assign);
+                self.word(";");
+            }
+            ast::StmtKind::Return(expr) => {
+                self.word("return ");
+                self.expression(expr, 0);
+                self.word(";");
+            }
+            ast::StmtKind::Unsafe(block) => {
+                self.word("unsafe ");
+                self.block(block);
+            }
+        })
+    }
+
+    fn assignment(&mut self, assign: &ast::Assignment) {
+        self.id(&assign.lhs);
+        self.word(" = ");
+        self.expression(&assign.rhs, 0);
+    }
+
+    /// Prints `expr` in a position where operators binding less tightly than
+    /// `min` have to be parenthesized.
+    fn expression(&mut self, expr: &ast::Expression, min: u16) {
+        if power(expr) < min {
+            self.word("(");
+            self.expression(expr, 0);
+            self.word(")");
+            return;
+        }
+
+        with_sufficient_stack(|| match &expr.kind {
+            ast::ExprKind::Id(id) => self.id(id),
+            ast::ExprKind::Lit(lit) => self.word(&lit.value.to_string()),
+            ast::ExprKind::Field(base, name) => {
+                self.expression(base, FIELD_POWER);
+                self.word(".");
+                self.id(name);
+            }
+            ast::ExprKind::Unary(op, operand) => {
+                self.word(match op.kind {
+                    ast::UnaryKind::Neg => "-",
+                    ast::UnaryKind::Not => "!",
+                });
+                self.expression(operand, PREFIX_POWER);
+            }
+            ast::ExprKind::Binary(op, lhs, rhs) => {
+                let power = binary_power(op.kind);
+
+                let (lhs_min, rhs_min) = match op.kind {
+                    ast::BinaryKind::Pow => (power + 1, power),
+                    _ => (power, power + 1),
+                };
+
+                // A prefix operator is parsed wherever an operand starts, so
+                // it never needs parentheses on the right.
+                let rhs_min = match rhs.kind {
+                    ast::ExprKind::Unary(..) => 0,
+                    _ => rhs_min,
+                };
+
+                self.expression(lhs, lhs_min);
+                self.word(" ");
+                self.word(binary_symbol(op.kind));
+                self.word(" ");
+                self.expression(rhs, rhs_min);
+            }
+            ast::ExprKind::Call(call) => self.call(call),
+            ast::ExprKind::Record(cons) => self.constructor(cons),
+        })
+    }
+
+    fn call(&mut self, call: &ast::Call) {
+        self.id(&call.name);
+        self.generics(&call.generics, |p, arg| p.expression(arg, 0));
+        self.list(&call.args, "(", ")", |p, arg| p.expression(arg, 0));
+    }
+
+    fn constructor(&mut self, cons: &ast::Constructor) {
+        self.id(&cons.name);
+        self.generics(&cons.generics, |p, arg| p.expression(arg, 0));
+
+        if cons.fields.is_empty() {
+            self.word(" {}");
+        } else {
+            self.list(&cons.fields, " { ", " }", Self::assignment);
+        }
+    }
+}
+
+fn power(expr: &ast::Expression) -> u16 {
+    match &expr.kind {
+        ast::ExprKind::Field(..) => FIELD_POWER,
+        ast::ExprKind::Unary(..) => PREFIX_POWER,
+        ast::ExprKind::Binary(op, ..) => binary_power(op.kind),
+        ast::ExprKind::Id(_)
+        | ast::ExprKind::Lit(_)
+        | ast::ExprKind::Call(_)
+        | ast::ExprKind::Record(_) => ATOM_POWER,
+    }
+}
+
+fn binary_symbol(kind: ast::BinaryKind) -> &'static str {
+    match kind {
+        ast::BinaryKind::Add => "+",
+        ast::BinaryKind::Sub => "-",
+        ast::BinaryKind::Mul => "*",
+        ast::BinaryKind::Div => "/",
+        ast::BinaryKind::Pow => "^",
+        ast::BinaryKind::Shl => "<<",
+        ast::BinaryKind::Shr => ">>",
+        ast::BinaryKind::Eq => "=",
+        ast::BinaryKind::Ne => "!=",
+        ast::BinaryKind::Gt => ">",
+        ast::BinaryKind::Ge => ">=",
+        ast::BinaryKind::Lt => "<",
+        ast::BinaryKind::Le => "<=",
+    }
+}
+
+/// Whether `text` ends with a name that is not a field, which is the only
+/// way for an expression to end in something that can start a constructor.
+fn ends_with_name(text: &str) -> bool {
+    let rest =
+        text.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    let name = &text[rest.len()..];
+
+    name.starts_with(|c: char| !c.is_ascii_digit()) && !rest.ends_with('.')
+}
+
+macro_rules! print_via {
+    ($ty:ty, $method:ident) => {
+        impl Print for $ty {
+            fn print(&self, printer: &mut Printer<'_>) {
+                printer.$method(self);
+            }
+        }
+    };
+}
+
+print_via!(ast::Attribute, attribute);
+print_via!(ast::Record, record);
+print_via!(ast::Field, field);
+print_via!(ast::Type, ty);
+print_via!(ast::Definition, definition);
+print_via!(ast::Signature, signature);
+print_via!(ast::Parameter, parameter);
+print_via!(ast::Block, block);
+print_via!(ast::Statement, statement);
+print_via!(ast::Assignment, assignment);
+print_via!(ast::Call, call);
+print_via!(ast::Constructor, constructor);
+print_via!(ast::Id, id);
+
+impl Print for ast::Expression {
+    fn print(&self, printer: &mut Printer<'_>) {
+        printer.expression(self, 0);
+    }
+}
+
+impl Print for ast::Item {
+    fn print(&self, printer: &mut Printer<'_>) {
+        for attr in &self.attrs {
+            printer.attribute(attr);
+            printer.newline();
+        }
+
+        match &self.kind {
+            ast::ItemKind::Record(record) => printer.record(record),
+            ast::ItemKind::Def(def) => printer.definition(def),
+        }
+    }
+}
+
+impl Print for ast::File {
+    fn print(&self, printer: &mut Printer<'_>) {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                printer.newline();
+            }
+
+            item.print(printer);
+            printer.newline();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use adpl_ast::{self as ast, Visit};
+    use adpl_util::FileId;
+
+    use super::print;
+    use crate::parse;
+
+    const SRC: &str = "
+#[inline]
+struct Pair[W] {
+    lo: uint[W],
+    hi: uint[W + 1],
+}
+
+unsafe def f[N](a: uint[N], b: uint[N], c: Pair[N]) -> uint[N * 2] {
+    x = (a + b) * c.lo - (a - (b - 1)) + a - b - 1;
+    y = a ^ b ^ 2 + (a ^ b) ^ 2 + -a ^ b + (-a) ^ b;
+    z = a * -b + a - -b + a ^ -b + a << !b + 1 + a * -(b + 1);
+    unsafe {
+        w = (a < b) = (b > a) + (a != b);
+        v = a << 1 >> 2 + (a << 1 >> 2 >> 3);
+    }
+    p = Pair[N] { lo = (a + b).x, hi = f[N + 1](a, -b, c).lo };
+    return !(x + y) * z / w ^ v;
+}
+
+def g(a: uint[8], n: uint[8]) -> uint[8] where (a > n) {}
+
+def h(a: uint[8]) -> uint[8] where a > 1 implements (a) {}
+
+def k(a: Pair[8]) -> uint[8] where a.lo implements -a.hi {}
+
+def m(a: uint[8]) -> uint[8] where a > 1;
+";
+
+    /// Renders every expression fully parenthesized, ignoring spans.
+    struct Shapes<'a> {
+        symbols: &'a ast::Symbols,
+        shapes: Vec<String>,
+    }
+
+    impl Shapes<'_> {
+        fn shape(&self, expr: &ast::Expression) -> String {
+            let list = |exprs: &[ast::Expression]| {
+                exprs
+                    .iter()
+                    .map(|expr| self.shape(expr))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+
+            match &expr.kind {
+                ast::ExprKind::Id(id) => self.symbols[id.symbol].to_string(),
+                ast::ExprKind::Lit(lit) => lit.value.to_string(),
+                ast::ExprKind::Field(base, name) => format!(
+                    "(. {} {})",
+                    self.shape(base),
+                    &self.symbols[name.symbol],
+                ),
+                ast::ExprKind::Unary(op, operand) => {
+                    format!("({:?} {})", op.kind, self.shape(operand))
+                }
+                ast::ExprKind::Binary(op, lhs, rhs) => format!(
+                    "({:?} {} {})",
+                    op.kind,
+                    self.shape(lhs),
+                    self.shape(rhs),
+                ),
+                ast::ExprKind::Call(call) => format!(
+                    "(call {} [{}] {})",
+                    &self.symbols[call.name.symbol],
+                    list(&call.generics),
+                    list(&call.args),
+                ),
+                ast::ExprKind::Record(cons) => format!(
+                    "(new {} [{}] {})",
+                    &self.symbols[cons.name.symbol],
+                    list(&cons.generics),
+                    cons.fields
+                        .iter()
+                        .map(|field| format!(
+                            "{}={}",
+                            &self.symbols[field.lhs.symbol],
+                            self.shape(&field.rhs),
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+            }
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Shapes<'_> {
+        fn visit_expression(&mut self, expr: &'ast ast::Expression) {
+            self.shapes.push(self.shape(expr));
+        }
+    }
+
+    fn shapes(file: &ast::File, symbols: &ast::Symbols) -> Vec<String> {
+        let mut shapes = Shapes {
+            symbols,
+            shapes: Vec::new(),
+        };
+
+        shapes.visit_file(file);
+        shapes.shapes
+    }
+
+    #[test]
+    fn round_trip() {
+        let symbols = ast::Symbols::new();
+        let file = FileId::from_u32(0);
+
+        let ast = parse(file, SRC, &symbols).expect("test source parses");
+        let printed = print(&ast, &symbols);
+
+        let reparsed = parse(file, &printed, &symbols)
+            .unwrap_or_else(|_| panic!("printed source parses:\n{printed}"));
+
+        assert_eq!(shapes(&reparsed, &symbols), shapes(&ast, &symbols));
+        assert_eq!(print(&reparsed, &symbols), printed);
+
+        for expected in [
+            "x = (a + b) * c.lo - (a - (b - 1)) + a - b - 1;",
+            "y = a ^ b ^ 2 + (a ^ b) ^ 2 + -a ^ b + (-a) ^ b;",
+            "z = a * -b + a - -b + a ^ -b + a << !b + 1 + a * -(b + 1);",
+            "v = a << 1 >> 2 + (a << 1 >> 2 >> 3);",
+            "where (a > n) {}",
+            "where a > 1 implements (a) {}",
+            "where a.lo implements -a.hi {}",
+        ] {
+            assert!(
+                printed.contains(expected),
+                "no `{expected}` in:\n{printed}"
+            );
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::fmt;
+
+/// A stable diagnostic code such as `E0007`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Code(&'static str);
+
+impl Code {
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Returns the long-form explanation of `code`, if it is registered.
+pub fn explain(code: &str) -> Option<&'static str> {
+    CODES
+        .iter()
+        .find(|(registered, _)| registered.as_str() == code)
+        .map(|&(_, explanation)| explanation)
+}
+
+// Registering a code twice defines the same constant twice, which does not
+// compile; the assertion below additionally keeps the table well-formed and
+// sorted.
+macro_rules! codes {
+    ($($code:ident => $explanation:literal,)*) => {
+        $(pub const $code: Code = Code(stringify!($code));)*
+
+        /// Every registered code with its explanation, in ascending order.
+        pub const CODES: &[(Code, &str)] = &[$(($code, $explanation),)*];
+    };
+}
+
+const fn is_well_formed(code: &str) -> bool {
+    let bytes = code.as_bytes();
+
+    if bytes.len() != 5 || bytes[0] != b'E' {
+        return false;
+    }
+
+    let mut i = 1;
+
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() {
+            return false;
+        }
+
+        i += 1;
+    }
+
+    true
+}
+
+const fn is_less(lhs: &str, rhs: &str) -> bool {
+    let (lhs, rhs) = (lhs.as_bytes(), rhs.as_bytes());
+    let mut i = 0;
+
+    while i < lhs.len() && i < rhs.len() {
+        if lhs[i] != rhs[i] {
+            return lhs[i] < rhs[i];
+        }
+
+        i += 1;
+    }
+
+    lhs.len() < rhs.len()
+}
+
+const _: () = {
+    let mut i = 0;
+
+    while i < CODES.len() {
+        assert!(is_well_formed(CODES[i].0.0), "malformed diagnostic code");
+        assert!(
+            i == 0 || is_less(CODES[i - 1].0.0, CODES[i].0.0),
+            "diagnostic codes must be unique and sorted",
+        );
+
+        i += 1;
+    }
+};
+
+codes! {
+    E0001 => "\
+The source file could not be parsed.
+
+Erroneous code example:
+
+    def f(a: uint[8]) -> uint[8] {
+        return a +;
+    }
+
+The parser stopped at the first token that cannot continue the current item,
+statement or expression. Check the highlighted token and the code just before
+it for missing operands, separators or delimiters.
+",
+    E0002 => "\
+A parameter name was used more than once in the same parameter list.
+
+Erroneous code example:
+
+    def add(a: uint[8], a: uint[8]) -> uint[8] {
+        return a + a;
+    }
+
+Every generic parameter and every value parameter of a definition or struct
+must have a distinct name:
+
+    def add(a: uint[8], b: uint[8]) -> uint[8] {
+        return a + b;
+    }
+",
+    E0003 => "\
+A value parameter has the same name as a generic parameter.
+
+Erroneous code example:
+
+    def f[W](W: uint[8]) -> uint[8] {
+        return W;
+    }
+
+Inside the definition, the name would be ambiguous. Rename one of them:
+
+    def f[W](x: uint[W]) -> uint[W] {
+        return x;
+    }
+",
+    E0004 => "\
+A struct declares the same field twice.
+
+Erroneous code example:
+
+    struct Pair {
+        lo: uint[8],
+        lo: uint[8],
+    }
+
+Field names must be unique within a struct:
+
+    struct Pair {
+        lo: uint[8],
+        hi: uint[8],
+    }
+",
+    E0005 => "\
+Two items were defined with the same name.
+
+Erroneous code example:
+
+    struct Value { x: uint[8] }
+
+    def Value(a: uint[8]) -> uint[8] {
+        return a;
+    }
+
+Structs and definitions share a single namespace, so every item needs a
+unique name.
+",
+    E0006 => "\
+An expression refers to a name that is not in scope.
+
+Erroneous code example:
+
+    def f(a: uint[8]) -> uint[8] {
+        return b;
+    }
+
+Only parameters, generic parameters and variables assigned earlier in the
+definition can be referred to. Check the spelling, or assign the variable
+before its first use.
+",
+    E0007 => "\
+A type or function was referred to by a name that does not exist.
+
+Erroneous code example:
+
+    def f(a: uint[8]) -> word {
+        return a;
+    }
+
+Types must be `uint` or a struct declared in the same file, and calls must name
+a definition declared in the same file:
+
+    struct word { value: uint[8] }
+
+    def f(a: uint[8]) -> word {
+        return word { value = a };
+    }
+",
+    E0008 => "\
+A struct or function name was used where a value was expected.
+
+Erroneous code example:
+
+    def one() -> uint[8] {
+        return 1;
+    }
+
+    def f(a: uint[8]) -> uint[8] {
+        return a + one;
+    }
+
+Definitions have to be called and structs have to be constructed to produce a
+value:
+
+    def f(a: uint[8]) -> uint[8] {
+        return a + one();
+    }
+",
+    E0009 => "\
+A name refers to the wrong kind of item.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(a: uint[8]) -> uint[8] {
+        p = Pair(a, a);
+        return p.lo;
+    }
+
+Structs are built with an initializer rather than called, and definitions
+cannot be used as types:
+
+    def f(a: uint[8]) -> uint[8] {
+        p = Pair { lo = a, hi = a };
+        return p.lo;
+    }
+",
+    E0010 => "\
+An item was given the wrong number of arguments.
+
+Erroneous code example:
+
+    def add(a: uint[8], b: uint[8]) -> uint[8] {
+        return a + b;
+    }
+
+    def f(a: uint[8]) -> uint[8] {
+        return add(a);
+    }
+
+The number of arguments, generic arguments of a call, and generic arguments of
+a type must match the declaration. `uint` always takes exactly one generic
+argument, its width.
+",
+    E0011 => "\
+A struct initializer names a field that the struct does not have.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(a: uint[8]) -> Pair {
+        return Pair { lo = a, mid = a };
+    }
+
+Only the fields declared in the struct can be initialized.
+",
+    E0012 => "\
+A struct initializer sets the same field more than once.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(a: uint[8]) -> Pair {
+        return Pair { lo = a, lo = a, hi = a };
+    }
+
+Each field must be initialized exactly once.
+",
+    E0013 => "\
+A struct initializer does not set every field.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(a: uint[8]) -> Pair {
+        return Pair { lo = a };
+    }
+
+Fields have no default values, so all of them must be initialized:
+
+    def f(a: uint[8]) -> Pair {
+        return Pair { lo = a, hi = 0 };
+    }
+",
+    E0014 => "\
+An item has an attribute the compiler does not know.
+
+Erroneous code example:
+
+    #[inlined]
+    def f(a: uint[8]) -> uint[8] {
+        return a;
+    }
+
+The recognized attributes are `#[inline]` and `#[noinline]` on definitions,
+and the lint level attributes `#[allow(..)]`, `#[warn(..)]` and `#[deny(..)]` on
+any item.
+",
+    E0015 => "\
+An attribute was placed on an item it does not apply to.
+
+Erroneous code example:
+
+    #[inline]
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+Inlining attributes only apply to definitions.
+",
+    E0016 => "\
+An item has attributes that contradict each other.
+
+Erroneous code example:
+
+    #[inline]
+    #[noinline]
+    def f(a: uint[8]) -> uint[8] {
+        return a;
+    }
+
+An item can be marked with at most one of `#[inline]` and `#[noinline]`.
+",
+    E0017 => "\
+An expression that must be known at compile time depends on a runtime value.
+
+Erroneous code example:
+
+    def f(n: uint[8]) -> uint[n] {
+        return n;
+    }
+
+Widths and other generic arguments may only use literals, generic parameters
+and variables computed from them:
+
+    def f[N](n: uint[8]) -> uint[N] {
+        return n;
+    }
+",
+    E0018 => "\
+An arithmetic or comparison operator was applied to a struct.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(p: Pair) -> uint[8] {
+        return p + 1;
+    }
+
+Operators are only defined on integers. Apply them to the fields instead:
+
+    def f(p: Pair) -> uint[8] {
+        return p.lo + 1;
+    }
+",
+    E0019 => "\
+A field was accessed on a value that does not have it.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(p: Pair) -> uint[8] {
+        return p.mid;
+    }
+
+Only structs have fields, and only the fields in their declaration.
+",
+    E0020 => "\
+Two types that must agree do not.
+
+Erroneous code example:
+
+    struct Pair { lo: uint[8], hi: uint[8] }
+
+    def f(p: Pair) -> uint[8] {
+        return p;
+    }
+
+Integers of different concrete widths are converted implicitly, but a width
+that depends on a generic parameter only agrees with the same width, and a
+struct only agrees with the same struct and generic arguments.
+",
+    E0021 => "\
+An integer is too wide for the selected output.
+
+Erroneous code example:
+
+    def f(a: uint[128]) -> uint[128] {
+        return a;
+    }
+
+The C and Rust backends and MIR represent integers with at most 64 bits.
+Split wider values into a struct of narrower fields.
+",
+    E0022 => "\
+A struct contains itself, directly or through the fields of other structs.
+
+Such a struct would need infinitely many bits. Hardware values have a fixed
+size, so recursive structs cannot be generated.
+",
+    E0023 => "\
+A definition with a body does not return a value.
+
+Erroneous code example:
+
+    def f(a: uint[8]) -> uint[8] {
+        b = a + 1;
+    }
+
+Every definition with a body must reach a `return` statement:
+
+    def f(a: uint[8]) -> uint[8] {
+        b = a + 1;
+        return b;
+    }
+",
+    E0024 => "\
+A recursive call was asked to be inlined.
+
+Inlining every call into the caller, as `--emit mir --inline` does, requires
+the call graph to be free of cycles, because a recursive definition would be
+inlined into itself forever.
+",
+    E0025 => "\
+An attribute was written with the wrong arguments.
+
+Erroneous code example:
+
+    #[allow]
+    def f(a: uint[8]) -> uint[8] {
+        return a;
+    }
+
+Lint level attributes take a list of lint names, and `#[inline]` and
+`#[noinline]` take no arguments:
+
+    #[allow(warnings)]
+    def f(a: uint[8]) -> uint[8] {
+        return a;
+    }
+",
+    E0026 => "\
+A definition without a body was emitted by a backend that needs one.
+
+Erroneous code example:
+
+    def ext(a: uint[8]) -> uint[8];
+
+The C backend declares such a definition and leaves it to be linked in, but
+the Rust backend generates a self-contained golden model and has nothing to
+call. Give the definition a body, or use the C backend:
+
+    def ext(a: uint[8]) -> uint[8] {
+        return a;
+    }
+",
+}
@@ -1,105 +1,718 @@
-use std::io::{self, IsTerminal};
-use std::ops::Range;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::ptr;
+use std::str::FromStr;
 use std::sync::LazyLock;
 
-use codespan_reporting::diagnostic::{Diagnostic as InnerDiagnostic, Label};
-use codespan_reporting::files::SimpleFile;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use codespan_reporting::diagnostic::{
+    Diagnostic as InnerDiagnostic, Label, LabelStyle, Severity,
+};
+use codespan_reporting::term::termcolor::{
+    ColorChoice, NoColor, StandardStream, WriteColor,
+};
 use codespan_reporting::term::{self, Config};
 
-pub struct Diagnostic(InnerDiagnostic<()>);
+use crate::codes::Code;
+use crate::files::{FileId, Files, Span};
+use crate::lints::{Lint, LintLevels, LintScope};
+use crate::suggestion::{Applicability, Suggestion};
+use crate::{json, sarif};
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub(crate) inner: InnerDiagnostic<FileId>,
+    pub(crate) suggestions: Vec<Suggestion>,
+    pub(crate) lint: Option<&'static Lint>,
+    pub(crate) by_default: bool,
+}
 
 impl Diagnostic {
+    fn new(inner: InnerDiagnostic<FileId>) -> Diagnostic {
+        Diagnostic {
+            inner,
+            suggestions: Vec::new(),
+            lint: None,
+            by_default: false,
+        }
+    }
+
     #[inline]
     pub fn bug() -> Diagnostic {
-        Self(InnerDiagnostic::bug())
+        Self::new(InnerDiagnostic::bug())
     }
 
     #[inline]
     pub fn error() -> Diagnostic {
-        Self(InnerDiagnostic::error())
+        Self::new(InnerDiagnostic::error())
     }
 
     #[inline]
     pub fn warning() -> Diagnostic {
-        Self(InnerDiagnostic::warning())
+        Self::new(InnerDiagnostic::warning())
+    }
+
+    #[inline]
+    pub fn severity(&self) -> Severity {
+        self.inner.severity
+    }
+
+    #[inline]
+    pub fn code(&self) -> Option<&str> {
+        self.inner.code.as_deref()
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    #[inline]
+    pub fn lint(&self) -> Option<&'static Lint> {
+        self.lint
+    }
+
+    /// Marks a warning as belonging to `lint`, so that its level can be
+    /// changed by the [`Reporter`].
+    pub fn with_lint(mut self, lint: &'static Lint) -> Diagnostic {
+        self.lint = Some(lint);
+        self
+    }
+
+    pub fn with_code(mut self, code: Code) -> Diagnostic {
+        self.inner.code = Some(code.as_str().into());
+        self
     }
 
     pub fn with_message<M: Into<String>>(mut self, message: M) -> Diagnostic {
-        self.0.message = message.into();
+        self.inner.message = message.into();
         self
     }
 
-    pub fn with_primary<S, L>(mut self, span: S, label: L) -> Diagnostic
-    where
-        S: Into<Range<usize>>,
-        L: Into<String>,
-    {
-        self.0
+    pub fn with_primary<L: Into<String>>(
+        mut self,
+        span: Span,
+        label: L,
+    ) -> Diagnostic {
+        self.inner
             .labels
-            .push(Label::primary((), span).with_message(label));
+            .push(Label::primary(span.file(), span).with_message(label));
 
         self
     }
 
-    pub fn with_secondary<S, L>(mut self, span: S, label: L) -> Diagnostic
-    where
-        S: Into<Range<usize>>,
-        L: Into<String>,
-    {
-        self.0
+    pub fn with_secondary<L: Into<String>>(
+        mut self,
+        span: Span,
+        label: L,
+    ) -> Diagnostic {
+        self.inner
             .labels
-            .push(Label::secondary((), span).with_message(label));
+            .push(Label::secondary(span.file(), span).with_message(label));
 
         self
     }
 
     pub fn with_note<N: Into<String>>(mut self, note: N) -> Diagnostic {
-        self.0.notes.push(note.into());
+        self.inner.notes.push(note.into());
+        self
+    }
+
+    /// Suggests replacing `span` with `replacement`, shown as `help: message`.
+    pub fn with_suggestion<R, M>(
+        mut self,
+        span: Span,
+        replacement: R,
+        message: M,
+        applicability: Applicability,
+    ) -> Diagnostic
+    where
+        R: Into<String>,
+        M: Into<String>,
+    {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        });
+
         self
     }
+
+    #[inline]
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    /// Returns where the primary label starts, which diagnostics are sorted
+    /// by.
+    fn position(&self) -> Option<(FileId, usize)> {
+        self.inner
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .map(|label| (label.file_id, label.range.start))
+    }
+}
+
+impl PartialEq for Diagnostic {
+    fn eq(&self, other: &Diagnostic) -> bool {
+        let lint = match (self.lint, other.lint) {
+            (Some(a), Some(b)) => ptr::eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+
+        self.inner == other.inner
+            && self.suggestions == other.suggestions
+            && lint
+            && self.by_default == other.by_default
+    }
+}
+
+impl Eq for Diagnostic {}
+
+/// How a [`Reporter`] renders diagnostics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Annotated source snippets for humans.
+    #[default]
+    Human,
+    /// One JSON object per line.
+    Json,
+    /// A SARIF 2.1.0 log, written by [`Reporter::finish`].
+    Sarif,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            _ => Err(format!(
+                "unknown error format `{}`, expected `human`, `json` or `sarif`",
+                s,
+            )),
+        }
+    }
 }
 
+/// Collects diagnostics and, unless it is capturing, writes them out sorted
+/// by position when [finished](Reporter::finish) or dropped.
 pub struct Reporter<'src> {
-    file: SimpleFile<&'src str, &'src str>,
-    writer: StandardStream,
+    files: &'src Files,
+    writer: Option<Box<dyn WriteColor>>,
+    format: ErrorFormat,
+    lint_levels: LintLevels,
+    error_limit: Option<usize>,
+    limit_reached: bool,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'src> Reporter<'src> {
     pub fn early() -> Reporter<'static> {
-        Reporter::new("", "")
+        static FILES: Files = Files::new();
+
+        Reporter::new(&FILES)
     }
 
-    pub fn new(filename: &'src str, source: &'src str) -> Reporter<'src> {
+    pub fn new(files: &'src Files) -> Reporter<'src> {
         let choice = if io::stderr().is_terminal() {
             ColorChoice::Auto
         } else {
             ColorChoice::Never
         };
 
+        let mut reporter = Reporter::capture(files);
+        reporter.writer = Some(Box::new(StandardStream::stderr(choice)));
+        reporter
+    }
+
+    /// Creates a reporter that only collects diagnostics, so that they can be
+    /// inspected or [rendered](Reporter::render) later.
+    pub fn capture(files: &'src Files) -> Reporter<'src> {
         Reporter {
-            file: SimpleFile::new(filename, source),
-            writer: StandardStream::stderr(choice),
+            files,
+            writer: None,
+            format: ErrorFormat::Human,
+            lint_levels: LintLevels::new(),
+            error_limit: None,
+            limit_reached: false,
+            diagnostics: Vec::new(),
         }
     }
 
+    pub fn with_format(mut self, format: ErrorFormat) -> Reporter<'src> {
+        self.format = format;
+        self
+    }
+
+    /// Writes diagnostics to `file` instead of stderr.
+    pub fn with_output(mut self, file: File) -> Reporter<'src> {
+        self.writer = Some(Box::new(NoColor::new(file)));
+        self
+    }
+
+    pub fn with_lint_levels(mut self, levels: LintLevels) -> Reporter<'src> {
+        self.lint_levels = levels;
+        self
+    }
+
+    /// Ignores every diagnostic after the first `limit` errors.
+    pub fn with_error_limit(mut self, limit: usize) -> Reporter<'src> {
+        self.error_limit = Some(limit);
+        self
+    }
+
+    /// Overrides lint levels within an item, as requested by an attribute.
+    pub fn push_lint_scope(&mut self, scope: LintScope) {
+        self.lint_levels.push_scope(scope);
+    }
+
+    /// Every distinct diagnostic emitted so far, sorted by position.
+    #[inline]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns the number of diagnostics emitted with `severity`.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity() == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors() > 0
+    }
+
+    fn errors(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity() >= Severity::Error)
+            .count()
+    }
+
+    /// Returns `true` once the error limit has been reached and further
+    /// diagnostics are being ignored.
+    #[inline]
+    pub fn limit_reached(&self) -> bool {
+        self.limit_reached
+    }
+
     pub fn emit_diagnostic(&mut self, diagnostic: &Diagnostic) {
-        term::emit(
-            &mut self.writer,
-            Reporter::config(),
-            &self.file,
-            &diagnostic.0,
-        )
-        .unwrap();
+        // Internal compiler errors are reported no matter what came before.
+        if self.limit_reached && diagnostic.severity() != Severity::Bug {
+            return;
+        }
+
+        let Some(diagnostic) = self.lint_levels.apply(diagnostic) else {
+            return;
+        };
+
+        if self.diagnostics.contains(&diagnostic) {
+            return;
+        }
+
+        let position = diagnostic.position();
+        let index = self
+            .diagnostics
+            .partition_point(|emitted| emitted.position() <= position);
+
+        self.diagnostics.insert(index, diagnostic);
+
+        if let Some(limit) = self.error_limit
+            && self.errors() >= limit
+        {
+            self.limit_reached = true;
+        }
     }
 
     pub fn emit<D: Into<Diagnostic>>(&mut self, diagnostic: D) {
         self.emit_diagnostic(&diagnostic.into());
     }
 
-    fn config() -> &'static Config {
-        static CONFIG: LazyLock<Config> = LazyLock::new(Config::default);
+    /// Writes every diagnostic emitted so far to `writer` in `format`.
+    pub fn render<W: Write>(
+        &self,
+        writer: W,
+        format: ErrorFormat,
+    ) -> io::Result<()> {
+        self.write_all(&mut NoColor::new(writer), format)
+    }
+
+    /// Writes out every diagnostic followed by a summary. Later calls do
+    /// nothing. Dropping the reporter does the same, but ignores errors.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+
+        self.write_all(&mut writer, self.format)?;
+        writer.flush()
+    }
+
+    fn write_all<W: WriteColor>(
+        &self,
+        writer: &mut W,
+        format: ErrorFormat,
+    ) -> io::Result<()> {
+        let mut noted = HashSet::new();
+
+        // Lints at their default level only say so the first time.
+        let diagnostics =
+            self.diagnostics
+                .iter()
+                .map(|diagnostic| match diagnostic.lint {
+                    Some(lint)
+                        if diagnostic.by_default && noted.insert(lint.name) =>
+                    {
+                        let level = lint.default;
+
+                        Cow::Owned(diagnostic.clone().with_note(format!(
+                            "`#[{}({})]` on by default",
+                            level, lint.name,
+                        )))
+                    }
+                    _ => Cow::Borrowed(diagnostic),
+                });
+
+        if format == ErrorFormat::Sarif {
+            let diagnostics =
+                diagnostics.map(Cow::into_owned).collect::<Vec<_>>();
+
+            return sarif::emit(writer, self.files, &diagnostics);
+        }
+
+        for diagnostic in diagnostics {
+            write(writer, format, self.files, &diagnostic)?;
+        }
+
+        if format == ErrorFormat::Human {
+            self.write_summary(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a line like `error: aborting due to 2 previous errors`.
+    fn write_summary<W: WriteColor>(&self, writer: &mut W) -> io::Result<()> {
+        let plural = |count: usize, what: &str| {
+            if count == 1 {
+                format!("{} {}", count, what)
+            } else {
+                format!("{} {}s", count, what)
+            }
+        };
+
+        let (errors, warnings) = (self.errors(), self.count(Severity::Warning));
+
+        let summary = if errors > 0 {
+            let mut summary = InnerDiagnostic::error().with_message(format!(
+                "aborting due to {}",
+                plural(errors, "previous error"),
+            ));
+
+            if warnings > 0 {
+                summary.message +=
+                    &format!("; {} emitted", plural(warnings, "warning"));
+            }
+
+            if let (true, Some(limit)) = (self.limit_reached, self.error_limit)
+            {
+                summary = summary.with_notes(vec![format!(
+                    "stopped after reaching the error limit of {}",
+                    limit,
+                )]);
+            }
+
+            summary
+        } else if warnings > 0 {
+            InnerDiagnostic::warning().with_message(format!(
+                "{} emitted",
+                plural(warnings, "warning"),
+            ))
+        } else {
+            return Ok(());
+        };
+
+        term::emit(writer, &CONFIG, self.files, &summary)
+            .map_err(io::Error::other)
+    }
+}
+
+impl Drop for Reporter<'_> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+static CONFIG: LazyLock<Config> = LazyLock::new(Config::default);
+
+fn write<W: WriteColor>(
+    writer: &mut W,
+    format: ErrorFormat,
+    files: &Files,
+    diagnostic: &Diagnostic,
+) -> io::Result<()> {
+    match format {
+        ErrorFormat::Human => {
+            term::emit(writer, &CONFIG, files, &diagnostic.inner)
+                .map_err(io::Error::other)?;
+
+            for suggestion in &diagnostic.suggestions {
+                suggestion.emit(writer, &CONFIG, files)?;
+            }
+
+            Ok(())
+        }
+        ErrorFormat::Json => json::emit(writer, files, diagnostic),
+        ErrorFormat::Sarif => unreachable!("SARIF is written as a whole log"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan_reporting::diagnostic::Severity;
+
+    use super::{Diagnostic, ErrorFormat, Reporter};
+    use crate::files::{FileId, Files, Span};
+
+    const SRC: &str = "def f(a: uint[8]) -> uint[8] {\n    return a;\n}\n";
+
+    fn files() -> (Files, FileId) {
+        let mut files = Files::new();
+        let file = files.add("test.adpl", SRC);
+
+        (files, file)
+    }
+
+    fn messages(reporter: &Reporter) -> Vec<String> {
+        reporter
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn capture() {
+        let (files, file) = files();
+        let mut reporter = Reporter::capture(&files);
+
+        assert!(!reporter.has_errors());
+
+        reporter.emit(
+            Diagnostic::warning()
+                .with_message("first")
+                .with_primary(Span::new(file, 0, 3), ""),
+        );
+        reporter.emit(
+            Diagnostic::error()
+                .with_message("second")
+                .with_primary(Span::new(file, 4, 5), ""),
+        );
+        reporter.emit(
+            Diagnostic::warning()
+                .with_message("third")
+                .with_primary(Span::new(file, 35, 41), ""),
+        );
+        reporter.emit(
+            Diagnostic::bug()
+                .with_message("fourth")
+                .with_primary(Span::new(file, 42, 43), ""),
+        );
+
+        assert_eq!(messages(&reporter), ["first", "second", "third", "fourth"]);
+
+        let severities = reporter
+            .diagnostics()
+            .iter()
+            .map(Diagnostic::severity)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            severities,
+            [
+                Severity::Warning,
+                Severity::Error,
+                Severity::Warning,
+                Severity::Bug,
+            ],
+        );
+
+        assert_eq!(reporter.count(Severity::Bug), 1);
+        assert_eq!(reporter.count(Severity::Error), 1);
+        assert_eq!(reporter.count(Severity::Warning), 2);
+        assert_eq!(reporter.count(Severity::Note), 0);
+        assert!(reporter.has_errors());
+
+        // Nothing is written until the diagnostics are rendered.
+        reporter.finish().unwrap();
+
+        let mut out = Vec::new();
+        reporter.render(&mut out, ErrorFormat::Json).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 4);
+        assert!(out.lines().next().unwrap().contains("\"first\""));
+    }
+
+    fn error(file: FileId, message: &str, start: usize) -> Diagnostic {
+        Diagnostic::error()
+            .with_message(message)
+            .with_primary(Span::new(file, start, start + 1), "here")
+    }
+
+    fn human(reporter: &Reporter) -> String {
+        let mut out = Vec::new();
+        reporter.render(&mut out, ErrorFormat::Human).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn deduplication() {
+        let (files, file) = files();
+        let mut reporter = Reporter::capture(&files);
+
+        reporter.emit(error(file, "same", 4));
+        reporter.emit(error(file, "same", 4));
+        reporter.emit(error(file, "same", 35));
+        reporter.emit(error(file, "other", 4));
+        reporter.emit(error(file, "same", 4).with_note("a note"));
+        reporter.emit(error(file, "same", 4));
+
+        assert_eq!(reporter.diagnostics().len(), 4);
+        assert_eq!(reporter.count(Severity::Error), 4);
+    }
+
+    #[test]
+    fn sorting() {
+        let mut files = Files::new();
+        let file = files.add("test.adpl", SRC);
+        let other = files.add("other.adpl", SRC);
+
+        let mut reporter = Reporter::capture(&files);
+
+        reporter.emit(error(other, "fifth", 0));
+        reporter.emit(error(file, "fourth", 35));
+        reporter.emit(error(file, "second", 4));
+        reporter.emit(error(file, "third", 4));
+        reporter.emit(Diagnostic::error().with_message("first"));
+        reporter.emit(
+            Diagnostic::error()
+                .with_message("sixth")
+                .with_secondary(Span::new(file, 0, 1), "not primary")
+                .with_primary(Span::new(other, 35, 36), "primary"),
+        );
+
+        // Without a primary label first, then by file and offset, keeping
+        // the order of emission for equal positions.
+        assert_eq!(
+            messages(&reporter),
+            ["first", "second", "third", "fourth", "fifth", "sixth"],
+        );
+    }
+
+    #[test]
+    fn error_limit() {
+        let (files, file) = files();
+        let mut reporter = Reporter::capture(&files).with_error_limit(2);
+
+        reporter.emit(error(file, "first", 35));
+        reporter.emit(
+            Diagnostic::warning()
+                .with_message("warning")
+                .with_primary(Span::new(file, 0, 1), ""),
+        );
+        assert!(!reporter.limit_reached());
+
+        reporter.emit(error(file, "second", 4));
+        assert!(reporter.limit_reached());
+
+        reporter.emit(error(file, "third", 0));
+        reporter.emit(
+            Diagnostic::warning()
+                .with_message("late warning")
+                .with_primary(Span::new(file, 0, 1), ""),
+        );
+        reporter.emit(Diagnostic::bug().with_message("bug"));
+
+        assert_eq!(messages(&reporter), ["bug", "warning", "second", "first"]);
+
+        let out = human(&reporter);
+        assert!(
+            out.contains(
+                "error: aborting due to 3 previous errors; 1 warning emitted"
+            ),
+            "{out}",
+        );
+        assert!(
+            out.contains("stopped after reaching the error limit of 2"),
+            "{out}",
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let (files, file) = files();
+        let mut reporter = Reporter::capture(&files);
+
+        assert_eq!(human(&reporter), "");
+
+        let warning = |start| {
+            Diagnostic::warning()
+                .with_message("warning")
+                .with_primary(Span::new(file, start, start + 1), "")
+        };
+
+        reporter.emit(warning(0));
+        assert!(human(&reporter).ends_with("warning: 1 warning emitted\n\n"));
+
+        reporter.emit(warning(4));
+        assert!(human(&reporter).ends_with("warning: 2 warnings emitted\n\n"));
+
+        reporter.emit(error(file, "error", 35));
+        assert!(human(&reporter).ends_with(
+            "error: aborting due to 1 previous error; 2 warnings emitted\n\n"
+        ));
+
+        reporter.emit(error(file, "error", 4));
+        assert!(human(&reporter).ends_with(
+            "error: aborting due to 2 previous errors; 2 warnings emitted\n\n"
+        ));
+
+        // Only the human format has a summary.
+        let mut out = Vec::new();
+        reporter.render(&mut out, ErrorFormat::Json).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 4);
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let (files, file) = files();
+        let path = std::env::temp_dir()
+            .join(format!("adpl-flush-on-drop-{}.txt", std::process::id()));
+
+        {
+            let output = std::fs::File::create(&path).unwrap();
+            let mut reporter = Reporter::new(&files)
+                .with_format(ErrorFormat::Json)
+                .with_output(output);
+
+            reporter.emit(error(file, "dropped", 4));
+        }
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-        &CONFIG
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.contains("\"dropped\""), "{written}");
     }
 }
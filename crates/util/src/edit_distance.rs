@@ -0,0 +1,49 @@
+/// Returns the number of single-char insertions, deletions, substitutions and
+/// transpositions of adjacent chars needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    let width = b.len() + 1;
+    let mut table = vec![0; (a.len() + 1) * width];
+
+    for i in 0..=a.len() {
+        table[i * width] = i;
+    }
+
+    for (j, cell) in table[..width].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (table[(i - 1) * width + j] + 1)
+                .min(table[i * width + j - 1] + 1)
+                .min(table[(i - 1) * width + j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(table[(i - 2) * width + j - 2] + 1);
+            }
+
+            table[i * width + j] = distance;
+        }
+    }
+
+    table[a.len() * width + b.len()]
+}
+
+/// Returns the candidate closest to `lookup`, if any is close enough to be a
+/// plausible misspelling of it.
+pub fn find_best_match<'a, I>(candidates: I, lookup: &str) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = lookup.chars().count().max(3) / 3;
+
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != lookup)
+        .map(|candidate| (edit_distance(candidate, lookup), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
@@ -0,0 +1,149 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use codespan_reporting::files::{self, Error};
+
+/// Identifies a source file in a [`Files`] database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    #[inline]
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Creates the id of the file added `index`th to a [`Files`] database.
+    #[inline]
+    pub fn from_u32(index: u32) -> FileId {
+        FileId(index)
+    }
+}
+
+/// A byte range in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    file: FileId,
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn new(file: FileId, start: usize, end: usize) -> Span {
+        Span { file, start, end }
+    }
+
+    #[inline]
+    pub fn file(self) -> FileId {
+        self.file
+    }
+
+    /// Returns `true` if `other` lies entirely within `self`.
+    #[inline]
+    pub fn contains(self, other: Span) -> bool {
+        self.file == other.file
+            && self.start <= other.start
+            && other.end <= self.end
+    }
+}
+
+impl From<Span> for Range<usize> {
+    #[inline]
+    fn from(value: Span) -> Self {
+        Range {
+            start: value.start,
+            end: value.end,
+        }
+    }
+}
+
+struct File {
+    name: String,
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+/// The source files of a compilation, keyed by [`FileId`].
+#[derive(Default)]
+pub struct Files {
+    files: Vec<File>,
+}
+
+impl Files {
+    pub const fn new() -> Files {
+        Files { files: Vec::new() }
+    }
+
+    pub fn add<N, S>(&mut self, name: N, source: S) -> FileId
+    where
+        N: Into<String>,
+        S: Into<String>,
+    {
+        let id = FileId(self.files.len().try_into().unwrap());
+        let source = source.into();
+
+        self.files.push(File {
+            name: name.into(),
+            line_starts: files::line_starts(&source).collect(),
+            source,
+        });
+
+        id
+    }
+
+    #[inline]
+    pub fn name(&self, id: FileId) -> &str {
+        &self.files[id.0 as usize].name
+    }
+
+    #[inline]
+    pub fn source(&self, id: FileId) -> &str {
+        &self.files[id.0 as usize].source
+    }
+
+    fn get(&self, id: FileId) -> Result<&File, Error> {
+        self.files.get(id.0 as usize).ok_or(Error::FileMissing)
+    }
+}
+
+impl<'a> files::Files<'a> for Files {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, Error> {
+        Ok(&self.get(id)?.name)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, Error> {
+        Ok(&self.get(id)?.source)
+    }
+
+    fn line_index(&'a self, id: FileId, byte: usize) -> Result<usize, Error> {
+        let line_starts = &self.get(id)?.line_starts;
+
+        Ok(line_starts
+            .binary_search(&byte)
+            .unwrap_or_else(|next| next - 1))
+    }
+
+    fn line_range(
+        &'a self,
+        id: FileId,
+        line: usize,
+    ) -> Result<Range<usize>, Error> {
+        let file = self.get(id)?;
+
+        let line_start = |line: usize| match line.cmp(&file.line_starts.len()) {
+            Ordering::Less => Ok(file.line_starts[line]),
+            Ordering::Equal => Ok(file.source.len()),
+            Ordering::Greater => Err(Error::LineTooLarge {
+                given: line,
+                max: file.line_starts.len() - 1,
+            }),
+        };
+
+        Ok(line_start(line)?..line_start(line + 1)?)
+    }
+}
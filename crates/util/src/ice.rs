@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::mem;
+
+use crate::files::Span;
+
+/// What the compiler was doing at some point, so that a panic can be reported
+/// as an internal compiler error pointing at the offending item.
+#[derive(Clone, Debug, Default)]
+pub struct Activity {
+    pub phase: Option<&'static str>,
+    pub item: Option<(String, Span)>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Activity> = const {
+        RefCell::new(Activity { phase: None, item: None })
+    };
+}
+
+/// Returns what the current thread is working on.
+pub fn current() -> Activity {
+    CURRENT.with_borrow(Activity::clone)
+}
+
+/// Runs `f` as the compiler phase `name`.
+pub fn phase<R, F>(name: &'static str, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    with(|activity| activity.phase = Some(name), f)
+}
+
+/// Runs `f` while processing the item `name` spanning `span`.
+///
+/// In debug builds, setting `ADPL_FORCE_ICE` to the name of an item makes the
+/// compiler panic when it first processes that item, which tests use to check
+/// how internal compiler errors are reported.
+pub fn item<R, F>(name: &str, span: Span, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    with(
+        |activity| activity.item = Some((name.to_owned(), span)),
+        || {
+            if cfg!(debug_assertions)
+                && std::env::var_os("ADPL_FORCE_ICE")
+                    .is_some_and(|item| item == name)
+            {
+                panic!("forced by `ADPL_FORCE_ICE`");
+            }
+
+            f()
+        },
+    )
+}
+
+fn with<R, F>(update: impl FnOnce(&mut Activity), f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Restore(Activity);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.set(mem::take(&mut self.0));
+        }
+    }
+
+    let mut next = current();
+    update(&mut next);
+
+    let _restore = Restore(CURRENT.replace(next));
+
+    f()
+}
@@ -0,0 +1,448 @@
+use std::io::{self, Write};
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+use codespan_reporting::files::Files as _;
+
+use crate::Diagnostic;
+use crate::files::{FileId, Files};
+
+/// Writes `diagnostic` as a single-line JSON object.
+pub fn emit<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    diagnostic: &Diagnostic,
+) -> io::Result<()> {
+    let (diagnostic, suggestions) =
+        (&diagnostic.inner, &diagnostic.suggestions);
+    let severity = match diagnostic.severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    };
+
+    write!(writer, "{{\"severity\":")?;
+    write_string(writer, severity)?;
+    write!(writer, ",\"message\":")?;
+    write_string(writer, &diagnostic.message)?;
+    write!(writer, ",\"code\":")?;
+
+    match &diagnostic.code {
+        Some(code) => write_string(writer, code)?,
+        None => write!(writer, "null")?,
+    }
+
+    write!(writer, ",\"labels\":[")?;
+
+    for (i, label) in diagnostic.labels.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        let style = match label.style {
+            LabelStyle::Primary => "primary",
+            LabelStyle::Secondary => "secondary",
+        };
+
+        write!(writer, "{{\"style\":")?;
+        write_string(writer, style)?;
+        write!(writer, ",\"message\":")?;
+        write_string(writer, &label.message)?;
+        write_location(writer, files, label.file_id, label.range.clone())?;
+        write!(writer, "}}")?;
+    }
+
+    write!(writer, "],\"notes\":[")?;
+
+    for (i, note) in diagnostic.notes.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write_string(writer, note)?;
+    }
+
+    write!(writer, "],\"suggestions\":[")?;
+
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write!(writer, "{{\"message\":")?;
+        write_string(writer, &suggestion.message)?;
+        write!(writer, ",\"replacement\":")?;
+        write_string(writer, &suggestion.replacement)?;
+        write!(writer, ",\"applicability\":")?;
+        write_string(writer, suggestion.applicability.as_str())?;
+        write_location(
+            writer,
+            files,
+            suggestion.span.file(),
+            Range::from(suggestion.span),
+        )?;
+        write!(writer, "}}")?;
+    }
+
+    writeln!(writer, "]}}")
+}
+
+/// Writes the `file`, `byte_*`, `line_*` and `column_*` members of a span.
+fn write_location<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    file: FileId,
+    range: Range<usize>,
+) -> io::Result<()> {
+    let start = files
+        .location(file, range.start)
+        .map_err(io::Error::other)?;
+    let end = files.location(file, range.end).map_err(io::Error::other)?;
+
+    write!(writer, ",\"file\":")?;
+    write_string(writer, files.name(file))?;
+    write!(
+        writer,
+        ",\"byte_start\":{},\"byte_end\":{}",
+        range.start, range.end,
+    )?;
+    write!(
+        writer,
+        ",\"line_start\":{},\"column_start\":{}",
+        start.line_number, start.column_number,
+    )?;
+    write!(
+        writer,
+        ",\"line_end\":{},\"column_end\":{}",
+        end.line_number, end.column_number,
+    )
+}
+
+pub(crate) fn write_string<W: Write>(
+    writer: &mut W,
+    s: &str,
+) -> io::Result<()> {
+    write!(writer, "\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            '\r' => write!(writer, "\\r")?,
+            '\t' => write!(writer, "\\t")?,
+            c if c.is_control() => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+
+    write!(writer, "\"")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::iter::Peekable;
+    use std::ops::Index;
+    use std::str::Chars;
+
+    use super::emit;
+    use crate::files::{Files, Span};
+    use crate::{Applicability, Diagnostic, codes};
+
+    /// A parsed JSON value, for checking what the emitters write.
+    #[derive(Debug, PartialEq)]
+    pub(crate) enum Value {
+        Null,
+        Bool(bool),
+        Number(u64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+
+    impl Value {
+        /// Parses a document, panicking if it is not valid JSON. Only the
+        /// non-negative integers that the emitters write are supported.
+        pub(crate) fn parse(src: &str) -> Value {
+            let mut chars = src.chars().peekable();
+            let value = parse_value(&mut chars);
+
+            skip_whitespace(&mut chars);
+            assert_eq!(chars.next(), None, "trailing characters");
+            value
+        }
+
+        pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+            let Value::Object(members) = self else {
+                panic!("expected an object, found {self:?}");
+            };
+
+            members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+        }
+
+        pub(crate) fn as_array(&self) -> &[Value] {
+            match self {
+                Value::Array(values) => values,
+                _ => panic!("expected an array, found {self:?}"),
+            }
+        }
+    }
+
+    impl Index<&str> for Value {
+        type Output = Value;
+
+        fn index(&self, key: &str) -> &Value {
+            self.get(key)
+                .unwrap_or_else(|| panic!("no member `{key}` in {self:?}"))
+        }
+    }
+
+    impl Index<usize> for Value {
+        type Output = Value;
+
+        fn index(&self, index: usize) -> &Value {
+            &self.as_array()[index]
+        }
+    }
+
+    impl PartialEq<&str> for Value {
+        fn eq(&self, other: &&str) -> bool {
+            matches!(self, Value::String(s) if s == other)
+        }
+    }
+
+    impl PartialEq<u64> for Value {
+        fn eq(&self, other: &u64) -> bool {
+            matches!(self, Value::Number(n) if n == other)
+        }
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(chars: &mut Peekable<Chars>, expected: &str) {
+        for c in expected.chars() {
+            assert_eq!(chars.next(), Some(c), "expected `{expected}`");
+        }
+    }
+
+    fn parse_value(chars: &mut Peekable<Chars>) -> Value {
+        skip_whitespace(chars);
+
+        match chars.peek() {
+            Some('n') => {
+                expect(chars, "null");
+                Value::Null
+            }
+            Some('t') => {
+                expect(chars, "true");
+                Value::Bool(true)
+            }
+            Some('f') => {
+                expect(chars, "false");
+                Value::Bool(false)
+            }
+            Some('"') => Value::String(parse_string(chars)),
+            Some('[') => {
+                chars.next();
+
+                let mut values = Vec::new();
+                skip_whitespace(chars);
+
+                if chars.next_if_eq(&']').is_none() {
+                    loop {
+                        values.push(parse_value(chars));
+                        skip_whitespace(chars);
+
+                        match chars.next() {
+                            Some(',') => {}
+                            Some(']') => break,
+                            c => panic!("expected `,` or `]`, found {c:?}"),
+                        }
+                    }
+                }
+
+                Value::Array(values)
+            }
+            Some('{') => {
+                chars.next();
+
+                let mut members = Vec::new();
+                skip_whitespace(chars);
+
+                if chars.next_if_eq(&'}').is_none() {
+                    loop {
+                        skip_whitespace(chars);
+                        let name = parse_string(chars);
+                        skip_whitespace(chars);
+                        expect(chars, ":");
+
+                        assert!(
+                            members.iter().all(|(known, _)| known != &name),
+                            "duplicate member `{name}`",
+                        );
+                        members.push((name, parse_value(chars)));
+                        skip_whitespace(chars);
+
+                        match chars.next() {
+                            Some(',') => {}
+                            Some('}') => break,
+                            c => panic!("expected `,` or `}}`, found {c:?}"),
+                        }
+                    }
+                }
+
+                Value::Object(members)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut n = String::new();
+
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    n.push(c);
+                }
+
+                Value::Number(n.parse().unwrap())
+            }
+            c => panic!("unexpected {c:?}"),
+        }
+    }
+
+    fn parse_string(chars: &mut Peekable<Chars>) -> String {
+        expect(chars, "\"");
+
+        let mut s = String::new();
+
+        loop {
+            match chars.next().expect("unterminated string") {
+                '"' => return s,
+                '\\' => match chars.next().expect("unterminated escape") {
+                    '"' => s.push('"'),
+                    '\\' => s.push('\\'),
+                    '/' => s.push('/'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex = (0..4)
+                            .map(|_| chars.next().unwrap())
+                            .collect::<String>();
+                        let c = u32::from_str_radix(&hex, 16).unwrap();
+                        s.push(char::from_u32(c).unwrap());
+                    }
+                    c => panic!("invalid escape `\\{c}`"),
+                },
+                c => {
+                    assert!(!c.is_control(), "unescaped control character");
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostic() {
+        let mut files = Files::new();
+        let file = files.add(
+            "dir\\\"quoted\".adpl",
+            "def f(a: uint[8]) -> uint[8] {\n    b = a;\n}\n",
+        );
+
+        let diagnostic = Diagnostic::error()
+            .with_code(codes::E0023)
+            .with_message("a \"quoted\" \\ message\twith\u{1}controls\n")
+            .with_primary(Span::new(file, 4, 5), "primary")
+            .with_secondary(Span::new(file, 35, 41), "secondary")
+            .with_note("a note")
+            .with_suggestion(
+                Span::new(file, 35, 36),
+                "_b",
+                "a suggestion",
+                Applicability::MachineApplicable,
+            );
+
+        let mut out = Vec::new();
+        emit(&mut out, &files, &diagnostic).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let (line, rest) = out.split_once('\n').unwrap();
+
+        assert_eq!(rest, "");
+        assert!(line.contains("\\u0001"));
+
+        let json = Value::parse(line);
+
+        assert_eq!(json["severity"], "error");
+        assert_eq!(
+            json["message"],
+            "a \"quoted\" \\ message\twith\u{1}controls\n"
+        );
+        assert_eq!(json["code"], "E0023");
+        assert_eq!(
+            json["notes"],
+            Value::Array(vec![Value::String("a note".into())])
+        );
+
+        let labels = json["labels"].as_array();
+        assert_eq!(labels.len(), 2);
+
+        let primary = &labels[0];
+        assert_eq!(primary["style"], "primary");
+        assert_eq!(primary["message"], "primary");
+        assert_eq!(primary["file"], "dir\\\"quoted\".adpl");
+        assert_eq!(primary["byte_start"], 4);
+        assert_eq!(primary["byte_end"], 5);
+        assert_eq!(primary["line_start"], 1);
+        assert_eq!(primary["column_start"], 5);
+        assert_eq!(primary["line_end"], 1);
+        assert_eq!(primary["column_end"], 6);
+
+        let secondary = &labels[1];
+        assert_eq!(secondary["style"], "secondary");
+        assert_eq!(secondary["message"], "secondary");
+        assert_eq!(secondary["line_start"], 2);
+        assert_eq!(secondary["column_start"], 5);
+        assert_eq!(secondary["line_end"], 2);
+        assert_eq!(secondary["column_end"], 11);
+
+        let suggestions = json["suggestions"].as_array();
+        assert_eq!(suggestions.len(), 1);
+
+        let suggestion = &suggestions[0];
+        assert_eq!(suggestion["message"], "a suggestion");
+        assert_eq!(suggestion["replacement"], "_b");
+        assert_eq!(suggestion["applicability"], "machine-applicable");
+        assert_eq!(suggestion["line_start"], 2);
+        assert_eq!(suggestion["column_start"], 5);
+        assert_eq!(suggestion["column_end"], 6);
+    }
+
+    #[test]
+    fn warning_without_code() {
+        let mut files = Files::new();
+        let file = files.add("test.adpl", "");
+
+        let diagnostic = Diagnostic::warning()
+            .with_message("nothing")
+            .with_primary(Span::new(file, 0, 0), "");
+
+        let mut out = Vec::new();
+        emit(&mut out, &files, &diagnostic).unwrap();
+
+        let json = Value::parse(std::str::from_utf8(&out).unwrap());
+
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["code"], Value::Null);
+        assert!(json["notes"].as_array().is_empty());
+        assert!(json["suggestions"].as_array().is_empty());
+        assert_eq!(json["labels"][0]["line_start"], 1);
+        assert_eq!(json["labels"][0]["column_start"], 1);
+    }
+}
@@ -1,5 +1,18 @@
+pub mod codes;
 mod diagnostics;
+mod edit_distance;
+mod files;
+pub mod ice;
+mod json;
+pub mod lints;
+mod sarif;
 mod stack;
+mod suggestion;
 
-pub use diagnostics::{Diagnostic, Reporter};
+pub use codes::Code;
+pub use codespan_reporting::diagnostic::Severity;
+pub use diagnostics::{Diagnostic, ErrorFormat, Reporter};
+pub use edit_distance::{edit_distance, find_best_match};
+pub use files::{FileId, Files, Span};
 pub use stack::with_sufficient_stack;
+pub use suggestion::{Applicability, Suggestion, apply_suggestions};
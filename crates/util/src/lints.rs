@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+
+use crate::diagnostics::Diagnostic;
+use crate::files::Span;
+
+/// The lint group containing every warning.
+pub const WARNINGS: &str = "warnings";
+
+/// How a lint is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+
+    fn flag(self) -> char {
+        match self {
+            Level::Allow => 'A',
+            Level::Warn => 'W',
+            Level::Deny => 'D',
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Level::Allow),
+            "warn" => Ok(Level::Warn),
+            "deny" => Ok(Level::Deny),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A named class of warnings whose level can be controlled.
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default: Level,
+    pub description: &'static str,
+}
+
+pub static UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default: Level::Warn,
+    description: "detects lint levels set for lints that do not exist",
+};
+
+/// Every registered lint.
+pub static LINTS: &[&Lint] = &[&UNKNOWN_LINTS];
+
+/// Returns the lint or lint group called `name`, accepting `-` in place of
+/// `_`.
+pub fn find(name: &str) -> Option<&'static str> {
+    let name = name.replace('-', "_");
+
+    LINTS
+        .iter()
+        .map(|lint| lint.name)
+        .chain([WARNINGS])
+        .find(|&known| known == name)
+}
+
+/// A lint level set by an attribute on the item spanning `span`.
+#[derive(Clone, Copy, Debug)]
+pub struct LintScope {
+    pub span: Span,
+    pub lint: &'static str,
+    pub level: Level,
+    pub attr: Span,
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    Default,
+    CommandLine(&'static str),
+    Attribute(&'static str, Span),
+}
+
+/// The lint levels requested on the command line and in the source.
+#[derive(Default)]
+pub struct LintLevels {
+    command_line: HashMap<&'static str, Level>,
+    scopes: Vec<LintScope>,
+}
+
+impl LintLevels {
+    pub fn new() -> LintLevels {
+        LintLevels::default()
+    }
+
+    /// Sets the level of the lint or lint group `name` everywhere it is not
+    /// overridden by an attribute. Returns `false` if there is no such lint.
+    pub fn set(&mut self, name: &str, level: Level) -> bool {
+        let Some(name) = find(name) else {
+            return false;
+        };
+
+        self.command_line.insert(name, level);
+        true
+    }
+
+    pub fn push_scope(&mut self, scope: LintScope) {
+        self.scopes.push(scope);
+    }
+
+    /// Applies the effective lint level to a warning, returning `None` if it
+    /// is allowed.
+    pub(crate) fn apply(&self, diagnostic: &Diagnostic) -> Option<Diagnostic> {
+        if diagnostic.severity() != Severity::Warning {
+            return Some(diagnostic.clone());
+        }
+
+        let span = diagnostic
+            .inner
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .map(|label| {
+                Span::new(label.file_id, label.range.start, label.range.end)
+            });
+
+        let (level, source) = self.level(diagnostic.lint, span);
+
+        let mut diagnostic = diagnostic.clone();
+
+        match level {
+            Level::Allow => return None,
+            Level::Warn => {}
+            Level::Deny => diagnostic.inner.severity = Severity::Error,
+        }
+
+        let Some(lint) = diagnostic.lint else {
+            return Some(diagnostic);
+        };
+
+        let implied = |group: &str, attribute: bool| {
+            let (name, group) = if attribute {
+                (
+                    format!("#[{}({})]", level, lint.name),
+                    format!("#[{}({})]", level, group),
+                )
+            } else {
+                (
+                    format!("-{} {}", level.flag(), lint.name),
+                    format!("-{} {}", level.flag(), group),
+                )
+            };
+
+            format!("`{}` implied by `{}`", name, group)
+        };
+
+        let diagnostic = match source {
+            // The note is only rendered on the first diagnostic of the lint.
+            Source::Default => Diagnostic {
+                by_default: true,
+                ..diagnostic
+            },
+            Source::CommandLine(name) if name == lint.name => diagnostic
+                .with_note(format!(
+                    "`-{} {}` requested on the command line",
+                    level.flag(),
+                    lint.name,
+                )),
+            Source::CommandLine(group) => {
+                diagnostic.with_note(implied(group, false))
+            }
+            Source::Attribute(name, attr) => {
+                let diagnostic = diagnostic
+                    .with_secondary(attr, "the lint level is defined here");
+
+                if name == lint.name {
+                    diagnostic
+                } else {
+                    diagnostic.with_note(implied(name, true))
+                }
+            }
+        };
+
+        Some(diagnostic)
+    }
+
+    /// Returns the level of a warning of `lint`, or of a warning outside of
+    /// any lint, whose primary label is at `span`. The innermost attribute
+    /// that names the lint or the `warnings` group wins. Otherwise the lint is
+    /// at its level from the command line or its default, and if that is
+    /// `warn`, at the level of `warnings` from the command line.
+    fn level(
+        &self,
+        lint: Option<&'static Lint>,
+        span: Option<Span>,
+    ) -> (Level, Source) {
+        let applies = |name: &str| {
+            name == WARNINGS || lint.is_some_and(|lint| lint.name == name)
+        };
+
+        let scope = span.and_then(|span| {
+            self.scopes
+                .iter()
+                .rev()
+                .filter(|scope| {
+                    applies(scope.lint) && scope.span.contains(span)
+                })
+                .min_by_key(|scope| Range::from(scope.span).len())
+        });
+
+        if let Some(scope) = scope {
+            return (scope.level, Source::Attribute(scope.lint, scope.attr));
+        }
+
+        let (level, source) = lint
+            .and_then(|lint| self.command_line(lint.name))
+            .unwrap_or_else(|| {
+                (
+                    lint.map_or(Level::Warn, |lint| lint.default),
+                    Source::Default,
+                )
+            });
+
+        match self.command_line(WARNINGS) {
+            Some(group) if level == Level::Warn => group,
+            _ => (level, source),
+        }
+    }
+
+    fn command_line(&self, name: &str) -> Option<(Level, Source)> {
+        self.command_line
+            .get_key_value(name)
+            .map(|(&name, &level)| (level, Source::CommandLine(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan_reporting::diagnostic::Severity;
+
+    use super::{Level, LintLevels, LintScope, UNKNOWN_LINTS, WARNINGS};
+    use crate::diagnostics::Diagnostic;
+    use crate::files::{FileId, Span};
+
+    fn span(start: usize, end: usize) -> Span {
+        Span::new(FileId::from_u32(0), start, end)
+    }
+
+    fn lint_at(offset: usize) -> Diagnostic {
+        Diagnostic::warning()
+            .with_lint(&UNKNOWN_LINTS)
+            .with_message("unknown lint `nope`")
+            .with_primary(span(offset, offset + 1), "")
+    }
+
+    fn scope(
+        levels: &mut LintLevels,
+        item: (usize, usize),
+        lint: &'static str,
+        level: Level,
+    ) {
+        levels.push_scope(LintScope {
+            span: span(item.0, item.1),
+            lint,
+            level,
+            attr: span(item.0, item.0 + 1),
+        });
+    }
+
+    /// The severity that a warning at `offset` is reported with, if any.
+    fn severity(
+        levels: &LintLevels,
+        diagnostic: Diagnostic,
+    ) -> Option<Severity> {
+        levels
+            .apply(&diagnostic)
+            .map(|diagnostic| diagnostic.severity())
+    }
+
+    fn notes(levels: &LintLevels, diagnostic: Diagnostic) -> Vec<String> {
+        levels.apply(&diagnostic).unwrap().inner.notes
+    }
+
+    #[test]
+    fn command_line() {
+        let mut levels = LintLevels::new();
+        assert_eq!(severity(&levels, lint_at(0)), Some(Severity::Warning));
+
+        assert!(levels.set("unknown-lints", Level::Allow));
+        assert_eq!(severity(&levels, lint_at(0)), None);
+
+        assert!(!levels.set("nope", Level::Deny));
+
+        // Later flags override earlier ones.
+        assert!(levels.set("unknown_lints", Level::Deny));
+        assert_eq!(severity(&levels, lint_at(0)), Some(Severity::Error));
+        assert_eq!(
+            notes(&levels, lint_at(0)),
+            ["`-D unknown_lints` requested on the command line"],
+        );
+
+        assert!(levels.set("unknown_lints", Level::Warn));
+        assert_eq!(severity(&levels, lint_at(0)), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn group_precedence() {
+        let cases = [
+            (Level::Warn, Level::Deny, Some(Severity::Error)),
+            (Level::Allow, Level::Deny, None),
+            (Level::Deny, Level::Allow, Some(Severity::Error)),
+            (Level::Warn, Level::Allow, None),
+        ];
+
+        for (lint, group, expected) in cases {
+            let mut levels = LintLevels::new();
+            levels.set("unknown_lints", lint);
+            levels.set(WARNINGS, group);
+
+            assert_eq!(
+                severity(&levels, lint_at(0)),
+                expected,
+                "-{lint} unknown_lints -{group} warnings",
+            );
+        }
+
+        let mut levels = LintLevels::new();
+        levels.set(WARNINGS, Level::Deny);
+
+        assert_eq!(
+            notes(&levels, lint_at(0)),
+            ["`-D unknown_lints` implied by `-D warnings`"],
+        );
+
+        // Warnings outside of any lint only follow the group.
+        let plain = || {
+            Diagnostic::warning()
+                .with_message("plain")
+                .with_primary(span(0, 1), "")
+        };
+
+        assert_eq!(severity(&levels, plain()), Some(Severity::Error));
+
+        levels.set(WARNINGS, Level::Allow);
+        assert_eq!(severity(&levels, plain()), None);
+    }
+
+    #[test]
+    fn attributes_override() {
+        let mut levels = LintLevels::new();
+        levels.set("unknown_lints", Level::Deny);
+        scope(&mut levels, (10, 20), WARNINGS, Level::Allow);
+
+        assert_eq!(severity(&levels, lint_at(12)), None);
+        assert_eq!(severity(&levels, lint_at(25)), Some(Severity::Error));
+
+        let mut levels = LintLevels::new();
+        levels.set(WARNINGS, Level::Allow);
+        scope(&mut levels, (10, 20), "unknown_lints", Level::Deny);
+
+        let denied = levels.apply(&lint_at(12)).unwrap();
+        assert_eq!(denied.severity(), Severity::Error);
+        assert_eq!(denied.inner.labels.len(), 2);
+        assert_eq!(
+            denied.inner.labels[1].message,
+            "the lint level is defined here"
+        );
+        assert_eq!(severity(&levels, lint_at(25)), None);
+
+        let mut levels = LintLevels::new();
+        scope(&mut levels, (10, 20), WARNINGS, Level::Deny);
+
+        assert_eq!(
+            notes(&levels, lint_at(12)),
+            ["`#[deny(unknown_lints)]` implied by `#[deny(warnings)]`"],
+        );
+    }
+
+    #[test]
+    fn innermost_attribute_wins() {
+        let mut levels = LintLevels::new();
+        scope(&mut levels, (0, 100), WARNINGS, Level::Allow);
+        scope(&mut levels, (10, 20), "unknown_lints", Level::Deny);
+        scope(&mut levels, (12, 14), WARNINGS, Level::Warn);
+
+        assert_eq!(severity(&levels, lint_at(5)), None);
+        assert_eq!(severity(&levels, lint_at(15)), Some(Severity::Error));
+        assert_eq!(severity(&levels, lint_at(12)), Some(Severity::Warning));
+
+        // Of two attributes on the same item, the later one wins.
+        let mut levels = LintLevels::new();
+        scope(&mut levels, (0, 100), "unknown_lints", Level::Deny);
+        scope(&mut levels, (0, 100), WARNINGS, Level::Allow);
+
+        assert_eq!(severity(&levels, lint_at(5)), None);
+    }
+
+    #[test]
+    fn errors_are_unaffected() {
+        let mut levels = LintLevels::new();
+        levels.set(WARNINGS, Level::Allow);
+        scope(&mut levels, (0, 100), WARNINGS, Level::Allow);
+
+        let error = Diagnostic::error()
+            .with_message("error")
+            .with_primary(span(5, 6), "");
+
+        assert_eq!(severity(&levels, error), Some(Severity::Error));
+    }
+}
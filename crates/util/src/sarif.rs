@@ -0,0 +1,325 @@
+use std::io::{self, Write};
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Label, LabelStyle, Severity};
+use codespan_reporting::files::Files as _;
+
+use crate::files::{FileId, Files};
+use crate::json::write_string;
+use crate::{Diagnostic, Suggestion};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Writes a SARIF 2.1.0 log with a single run containing `diagnostics`.
+pub fn emit<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    diagnostics: &[Diagnostic],
+) -> io::Result<()> {
+    let mut rules = Vec::new();
+
+    for code in diagnostics.iter().filter_map(Diagnostic::code) {
+        if !rules.contains(&code) {
+            rules.push(code);
+        }
+    }
+
+    write!(writer, "{{\"$schema\":")?;
+    write_string(writer, SCHEMA)?;
+    write!(writer, ",\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{")?;
+    write!(writer, "\"driver\":{{\"name\":\"adpl\",\"version\":")?;
+    write_string(writer, env!("CARGO_PKG_VERSION"))?;
+    write!(writer, ",\"rules\":[")?;
+
+    for (i, rule) in rules.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write!(writer, "{{\"id\":")?;
+        write_string(writer, rule)?;
+        write!(writer, "}}")?;
+    }
+
+    write!(writer, "]}}}},\"results\":[")?;
+
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write_result(writer, files, &rules, diagnostic)?;
+    }
+
+    writeln!(writer, "]}}]}}")
+}
+
+fn write_result<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    rules: &[&str],
+    diagnostic: &Diagnostic,
+) -> io::Result<()> {
+    let (diagnostic, suggestions) =
+        (&diagnostic.inner, &diagnostic.suggestions);
+    let level = match diagnostic.severity {
+        Severity::Bug | Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note | Severity::Help => "note",
+    };
+
+    write!(writer, "{{")?;
+
+    if let Some(code) = &diagnostic.code {
+        let index = rules.iter().position(|rule| rule == code).unwrap();
+
+        write!(writer, "\"ruleId\":")?;
+        write_string(writer, code)?;
+        write!(writer, ",\"ruleIndex\":{},", index)?;
+    }
+
+    let mut text = diagnostic.message.clone();
+
+    for note in &diagnostic.notes {
+        text.push_str("\nnote: ");
+        text.push_str(note);
+    }
+
+    write!(writer, "\"level\":")?;
+    write_string(writer, level)?;
+    write!(writer, ",\"message\":{{\"text\":")?;
+    write_string(writer, &text)?;
+    write!(writer, "}},\"locations\":[")?;
+
+    let (primary, secondary): (Vec<_>, Vec<_>) = diagnostic
+        .labels
+        .iter()
+        .partition(|label| label.style == LabelStyle::Primary);
+
+    for (i, label) in primary.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write_location(writer, files, label, None)?;
+    }
+
+    write!(writer, "],\"relatedLocations\":[")?;
+
+    for (i, label) in secondary.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        write_location(writer, files, label, Some(i))?;
+    }
+
+    write!(writer, "]")?;
+
+    if !suggestions.is_empty() {
+        write!(writer, ",\"fixes\":[")?;
+
+        for (i, suggestion) in suggestions.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+
+            write_fix(writer, files, suggestion)?;
+        }
+
+        write!(writer, "]")?;
+    }
+
+    write!(writer, "}}")
+}
+
+fn write_fix<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    suggestion: &Suggestion,
+) -> io::Result<()> {
+    let range = Range::from(suggestion.span);
+
+    write!(writer, "{{\"description\":{{\"text\":")?;
+    write_string(writer, &suggestion.message)?;
+    write!(writer, "}},\"artifactChanges\":[{{\"artifactLocation\":")?;
+    write!(writer, "{{\"uri\":")?;
+    write_string(writer, files.name(suggestion.span.file()))?;
+    write!(writer, "}},\"replacements\":[{{\"deletedRegion\":")?;
+    write!(
+        writer,
+        "{{\"byteOffset\":{},\"byteLength\":{}}}",
+        range.start,
+        range.len(),
+    )?;
+    write!(writer, ",\"insertedContent\":{{\"text\":")?;
+    write_string(writer, &suggestion.replacement)?;
+    write!(writer, "}}}}]}}]}}")
+}
+
+fn write_location<W: Write>(
+    writer: &mut W,
+    files: &Files,
+    label: &Label<FileId>,
+    id: Option<usize>,
+) -> io::Result<()> {
+    let start = files
+        .location(label.file_id, label.range.start)
+        .map_err(io::Error::other)?;
+    let end = files
+        .location(label.file_id, label.range.end)
+        .map_err(io::Error::other)?;
+
+    write!(writer, "{{")?;
+
+    if let Some(id) = id {
+        write!(writer, "\"id\":{},", id)?;
+    }
+
+    write!(
+        writer,
+        "\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":"
+    )?;
+    write_string(writer, files.name(label.file_id))?;
+    write!(
+        writer,
+        "}},\"region\":{{\"startLine\":{},\"startColumn\":{}",
+        start.line_number, start.column_number,
+    )?;
+    write!(
+        writer,
+        ",\"endLine\":{},\"endColumn\":{}",
+        end.line_number, end.column_number,
+    )?;
+    write!(
+        writer,
+        ",\"byteOffset\":{},\"byteLength\":{}}}}}",
+        label.range.start,
+        label.range.len(),
+    )?;
+
+    if !label.message.is_empty() {
+        write!(writer, ",\"message\":{{\"text\":")?;
+        write_string(writer, &label.message)?;
+        write!(writer, "}}")?;
+    }
+
+    write!(writer, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit;
+    use crate::files::{Files, Span};
+    use crate::json::tests::Value;
+    use crate::{Applicability, Diagnostic, codes};
+
+    #[test]
+    fn run() {
+        let mut files = Files::new();
+        let file = files.add(
+            "test.adpl",
+            "def f(a: uint[8]) -> uint[8] {\n    b = a;\n}\n",
+        );
+
+        let diagnostics = [
+            Diagnostic::error()
+                .with_code(codes::E0023)
+                .with_message("definition `f` does not return a value")
+                .with_primary(Span::new(file, 4, 5), "missing `return`")
+                .with_secondary(Span::new(file, 35, 41), "last statement")
+                .with_secondary(Span::new(file, 42, 43), "")
+                .with_note("every definition must return"),
+            Diagnostic::warning()
+                .with_code(codes::E0007)
+                .with_message("unused")
+                .with_primary(Span::new(file, 35, 36), "never read")
+                .with_suggestion(
+                    Span::new(file, 35, 36),
+                    "_b",
+                    "prefix it with an underscore",
+                    Applicability::MaybeIncorrect,
+                ),
+            Diagnostic::error()
+                .with_code(codes::E0023)
+                .with_message("again")
+                .with_primary(Span::new(file, 0, 3), ""),
+        ];
+
+        let mut out = Vec::new();
+        emit(&mut out, &files, &diagnostics).unwrap();
+
+        let sarif = Value::parse(std::str::from_utf8(&out).unwrap());
+
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let run = &sarif["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["id"], "E0023");
+        assert_eq!(rules[1]["id"], "E0007");
+
+        let results = run["results"].as_array();
+        assert_eq!(results.len(), 3);
+
+        let error = &results[0];
+        assert_eq!(error["ruleId"], "E0023");
+        assert_eq!(error["ruleIndex"], 0);
+        assert_eq!(error["level"], "error");
+        assert_eq!(
+            error["message"]["text"],
+            "definition `f` does not return a value\n\
+             note: every definition must return",
+        );
+        assert!(error.get("fixes").is_none());
+
+        let locations = error["locations"].as_array();
+        assert_eq!(locations.len(), 1);
+        assert!(locations[0].get("id").is_none());
+        assert_eq!(locations[0]["message"]["text"], "missing `return`");
+
+        let location = &locations[0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "test.adpl");
+
+        let region = &location["region"];
+        assert_eq!(region["startLine"], 1);
+        assert_eq!(region["startColumn"], 5);
+        assert_eq!(region["endLine"], 1);
+        assert_eq!(region["endColumn"], 6);
+        assert_eq!(region["byteOffset"], 4);
+        assert_eq!(region["byteLength"], 1);
+
+        let related = error["relatedLocations"].as_array();
+        assert_eq!(related.len(), 2);
+        assert_eq!(related[0]["id"], 0);
+        assert_eq!(related[0]["message"]["text"], "last statement");
+        assert_eq!(related[0]["physicalLocation"]["region"]["startLine"], 2);
+        assert_eq!(related[1]["id"], 1);
+        assert!(related[1].get("message").is_none());
+        assert_eq!(related[1]["physicalLocation"]["region"]["startLine"], 3);
+
+        let warning = &results[1];
+        assert_eq!(warning["ruleId"], "E0007");
+        assert_eq!(warning["ruleIndex"], 1);
+        assert_eq!(warning["level"], "warning");
+        assert!(warning["relatedLocations"].as_array().is_empty());
+
+        let fixes = warning["fixes"].as_array();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0]["description"]["text"],
+            "prefix it with an underscore",
+        );
+
+        let change = &fixes[0]["artifactChanges"][0];
+        assert_eq!(change["artifactLocation"]["uri"], "test.adpl");
+
+        let replacement = &change["replacements"][0];
+        assert_eq!(replacement["deletedRegion"]["byteOffset"], 35);
+        assert_eq!(replacement["deletedRegion"]["byteLength"], 1);
+        assert_eq!(replacement["insertedContent"]["text"], "_b");
+
+        assert_eq!(results[2]["ruleIndex"], 0);
+    }
+}
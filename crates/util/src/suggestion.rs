@@ -0,0 +1,102 @@
+use std::io;
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term::termcolor::WriteColor;
+use codespan_reporting::term::{self, Config};
+
+use crate::files::{Files, Span};
+
+/// How confident a [`Suggestion`] is that its replacement is correct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Applicability {
+    /// The replacement is definitely what the user intended and can be
+    /// applied automatically.
+    MachineApplicable,
+    /// The replacement may not be what the user intended.
+    MaybeIncorrect,
+    /// The replacement contains placeholders that the user has to fill in.
+    HasPlaceholders,
+}
+
+impl Applicability {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+            Applicability::HasPlaceholders => "has-placeholders",
+        }
+    }
+}
+
+/// A proposed edit that replaces `span` with `replacement`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    /// Writes `help: <message>` followed by a preview of the edited source.
+    pub(crate) fn emit<W: WriteColor>(
+        &self,
+        writer: &mut W,
+        config: &Config,
+        files: &Files,
+    ) -> io::Result<()> {
+        let file = self.span.file();
+        let range = Range::from(self.span);
+        let source = files.source(file);
+
+        // Removals are shown on the original source, everything else on the
+        // edited one with the inserted text underlined.
+        let (source, label) = if self.replacement.is_empty() {
+            (source.to_owned(), range)
+        } else {
+            let start = range.start;
+            let end = start + self.replacement.len();
+
+            (apply_suggestions(source, [self]), start..end)
+        };
+
+        let preview = SimpleFile::new(files.name(file), source);
+        let help = Diagnostic::help()
+            .with_message(&self.message)
+            .with_labels(vec![Label::primary((), label)]);
+
+        term::emit(writer, config, &preview, &help).map_err(io::Error::other)
+    }
+}
+
+/// Applies `suggestions` to `source`, skipping any suggestion that overlaps
+/// one that starts earlier.
+pub fn apply_suggestions<'a, I>(source: &str, suggestions: I) -> String
+where
+    I: IntoIterator<Item = &'a Suggestion>,
+{
+    let mut ranges = suggestions
+        .into_iter()
+        .map(|suggestion| (Range::from(suggestion.span), suggestion))
+        .collect::<Vec<_>>();
+
+    ranges.sort_by_key(|(range, _)| (range.start, range.end));
+
+    let mut output = String::with_capacity(source.len());
+    let mut pos = 0;
+
+    for (range, suggestion) in ranges {
+        if range.start < pos {
+            continue;
+        }
+
+        output.push_str(&source[pos..range.start]);
+        output.push_str(&suggestion.replacement);
+        pos = range.end;
+    }
+
+    output.push_str(&source[pos..]);
+    output
+}
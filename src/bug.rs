@@ -0,0 +1,50 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic;
+use std::sync::{Mutex, PoisonError};
+
+use adpl::util::ice::{self, Activity};
+
+/// The exit code of a compilation that hit an internal compiler error.
+pub const EXIT_CODE: u8 = 101;
+
+/// A panic caught by the hook installed with [`install_hook`].
+pub struct Panic {
+    pub message: String,
+    pub location: Option<String>,
+    pub activity: Activity,
+    pub backtrace: Option<String>,
+}
+
+static PANIC: Mutex<Option<Panic>> = Mutex::new(None);
+
+/// Replaces the default panic output with a record of the panic, to be
+/// reported as a bug diagnostic once it has been caught.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            String::from("unknown panic payload")
+        };
+
+        let backtrace = Backtrace::capture();
+        let backtrace = (backtrace.status() == BacktraceStatus::Captured)
+            .then(|| backtrace.to_string());
+
+        *PANIC.lock().unwrap_or_else(PoisonError::into_inner) = Some(Panic {
+            message,
+            location: info.location().map(ToString::to_string),
+            activity: ice::current(),
+            backtrace,
+        });
+    }));
+}
+
+/// Takes the last panic recorded by the hook.
+pub fn take() -> Option<Panic> {
+    PANIC.lock().unwrap_or_else(PoisonError::into_inner).take()
+}
@@ -1,11 +1,78 @@
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use adpl::util::ErrorFormat;
 
 /// The ADPL compiler.
 #[derive(argh::FromArgs)]
 pub struct Opts {
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+
     /// input file
     #[argh(positional)]
     pub file: Option<PathBuf>,
+
+    /// print the explanation of a diagnostic code and exit
+    #[argh(option)]
+    pub explain: Option<String>,
+
+    /// generate code for a target (c, rust, mir)
+    #[argh(option)]
+    pub emit: Option<Emit>,
+
+    /// diagnostic output format (human, json, sarif)
+    #[argh(option, default = "ErrorFormat::Human")]
+    pub error_format: ErrorFormat,
+
+    /// stop after reporting this many errors
+    #[argh(option)]
+    pub error_limit: Option<usize>,
+
+    /// write diagnostics to a file instead of stderr
+    #[argh(option)]
+    pub error_output: Option<PathBuf>,
+
+    /// write generated code to a file instead of stdout
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+
+    /// allow a lint, or all warnings with `warnings`
+    #[argh(option, short = 'A')]
+    pub allow: Vec<String>,
+
+    /// warn about a lint
+    #[argh(option, short = 'W')]
+    pub warn: Vec<String>,
+
+    /// deny a lint, reporting it as an error; takes precedence over -A and -W
+    #[argh(option, short = 'D')]
+    pub deny: Vec<String>,
+
+    /// inline every call when emitting MIR, not only those the HIR inliner
+    /// picks
+    #[argh(switch)]
+    pub inline: bool,
+
+    /// disable inlining and simplification of the HIR, so that only --inline
+    /// inlines calls
+    #[argh(switch)]
+    pub no_opt: bool,
+}
+
+#[derive(argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Fix(Fix),
+}
+
+/// Apply machine-applicable suggestions to a source file in place.
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "fix")]
+pub struct Fix {
+    /// file to fix
+    #[argh(positional)]
+    pub file: PathBuf,
 }
 
 impl Opts {
@@ -14,3 +81,27 @@ impl Opts {
         argh::from_env()
     }
 }
+
+/// A code generation target.
+#[derive(Clone, Copy)]
+pub enum Emit {
+    C,
+    Rust,
+    Mir,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c" => Ok(Emit::C),
+            "rust" => Ok(Emit::Rust),
+            "mir" => Ok(Emit::Mir),
+            _ => Err(format!(
+                "unknown target `{}`, expected `c`, `rust` or `mir`",
+                s,
+            )),
+        }
+    }
+}
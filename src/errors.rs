@@ -1,7 +1,54 @@
 use std::io;
 
 use adpl::parse;
-use adpl::util::Diagnostic;
+use adpl::util::{Diagnostic, Span, codes, lints};
+
+use crate::bug;
+
+pub struct InternalError(pub Option<bug::Panic>);
+
+impl From<InternalError> for Diagnostic {
+    fn from(value: InternalError) -> Self {
+        let Some(panic) = value.0 else {
+            return Diagnostic::bug()
+                .with_message("internal compiler error")
+                .with_note(REPORT);
+        };
+
+        let mut diagnostic = Diagnostic::bug().with_message(format!(
+            "internal compiler error: {}",
+            panic.message
+        ));
+
+        if let Some((name, span)) = panic.activity.item {
+            diagnostic = diagnostic
+                .with_primary(span, format!("while processing `{}`", name));
+        }
+
+        if let Some(phase) = panic.activity.phase {
+            diagnostic =
+                diagnostic.with_note(format!("compiler phase: {}", phase));
+        }
+
+        if let Some(location) = panic.location {
+            diagnostic =
+                diagnostic.with_note(format!("panicked at {}", location));
+        }
+
+        diagnostic = diagnostic.with_note(REPORT);
+
+        match panic.backtrace {
+            Some(backtrace) => {
+                diagnostic.with_note(format!("backtrace:\n{}", backtrace))
+            }
+            None => diagnostic.with_note(
+                "run with `RUST_BACKTRACE=1` to include a backtrace",
+            ),
+        }
+    }
+}
+
+const REPORT: &str = "this is a bug in the compiler, please report it";
 
 pub struct IoError(pub io::Error);
 
@@ -11,12 +58,39 @@ impl From<IoError> for Diagnostic {
     }
 }
 
+pub struct UnknownCode<'a>(pub &'a str);
+
+impl From<UnknownCode<'_>> for Diagnostic {
+    fn from(value: UnknownCode) -> Self {
+        Diagnostic::error().with_message(format!(
+            "`{}` is not a known diagnostic code",
+            value.0
+        ))
+    }
+}
+
+pub struct UnknownLint<'a>(pub &'a str);
+
+impl From<UnknownLint<'_>> for Diagnostic {
+    fn from(value: UnknownLint) -> Self {
+        Diagnostic::warning()
+            .with_lint(&lints::UNKNOWN_LINTS)
+            .with_message(format!("unknown lint `{}`", value.0))
+    }
+}
+
 pub struct ParseError(pub parse::Error);
 
 impl From<ParseError> for Diagnostic {
     fn from(value: ParseError) -> Self {
+        let span = value.0.span();
+
         Diagnostic::error()
+            .with_code(codes::E0001)
             .with_message("syntax error")
-            .with_primary(value.0.span().clone(), "syntax error")
+            .with_primary(
+                Span::new(span.context, span.start, span.end),
+                "syntax error",
+            )
     }
 }
@@ -1,7 +1,10 @@
 pub use adpl_arena as arena;
 pub use adpl_ast as ast;
 pub use adpl_ast_lowering as ast_lowering;
+pub use adpl_codegen as codegen;
 pub use adpl_hir as hir;
 pub use adpl_lex as lex;
+pub use adpl_mir as mir;
+pub use adpl_opt as opt;
 pub use adpl_parse as parse;
 pub use adpl_util as util;
@@ -1,16 +1,28 @@
+mod bug;
 mod cli;
 mod errors;
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::{fs, io};
 
+use adpl::ast;
 use adpl::ast_lowering::lower_ast;
+use adpl::codegen::{emit_c, emit_rust};
+use adpl::hir;
+use adpl::mir::{CallMode, lower_hir};
+use adpl::opt::{inline, simplify};
 use adpl::parse::parse;
-use adpl::util::Reporter;
+use adpl::util::lints::{Level, LintLevels};
+use adpl::util::{
+    Applicability, Diagnostic, FileId, Files, Reporter, apply_suggestions,
+    codes, ice,
+};
 
-use cli::Opts;
+use cli::{Command, Emit, Opts};
 
 fn read_input(file: &Option<PathBuf>) -> io::Result<(Cow<'_, str>, String)> {
     if let Some(file) = file {
@@ -26,32 +38,282 @@ fn read_input(file: &Option<PathBuf>) -> io::Result<(Cow<'_, str>, String)> {
     }
 }
 
+fn write_output(file: &Option<PathBuf>, code: &str) -> io::Result<()> {
+    if let Some(file) = file {
+        fs::write(file, code)
+    } else {
+        io::stdout().write_all(code.as_bytes())
+    }
+}
+
+/// Returns the lint levels requested on the command line, and the names of
+/// the lints that do not exist.
+fn lint_levels(opts: &Opts) -> (LintLevels, Vec<&String>) {
+    let mut levels = LintLevels::new();
+    let mut unknown = Vec::new();
+
+    // Later levels override earlier ones, so denying a lint always wins.
+    for (names, level) in [
+        (&opts.allow, Level::Allow),
+        (&opts.warn, Level::Warn),
+        (&opts.deny, Level::Deny),
+    ] {
+        for name in names {
+            if !levels.set(name, level) {
+                unknown.push(name);
+            }
+        }
+    }
+
+    (levels, unknown)
+}
+
+fn new_reporter<'src>(
+    opts: &Opts,
+    output: Option<File>,
+    files: &'src Files,
+) -> Reporter<'src> {
+    let (levels, unknown) = lint_levels(opts);
+
+    let mut reporter = Reporter::new(files)
+        .with_format(opts.error_format)
+        .with_lint_levels(levels);
+
+    if let Some(limit) = opts.error_limit {
+        reporter = reporter.with_error_limit(limit);
+    }
+
+    let mut reporter = match output {
+        Some(file) => reporter.with_output(file),
+        None => reporter,
+    };
+
+    for name in unknown {
+        reporter.emit(errors::UnknownLint(name));
+    }
+
+    reporter
+}
+
+/// Reports a diagnostic that occurred before the source was read.
+fn report_early<D: Into<Diagnostic>>(diagnostic: D) {
+    let mut reporter = Reporter::early();
+    reporter.emit(diagnostic);
+    let _ = reporter.finish();
+}
+
+fn explain(code: &str) -> ExitCode {
+    let Some(explanation) = codes::explain(code) else {
+        report_early(errors::UnknownCode(code));
+
+        return ExitCode::FAILURE;
+    };
+
+    if let Err(err) = io::stdout().write_all(explanation.as_bytes()) {
+        report_early(errors::IoError(err));
+
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
 fn main() -> ExitCode {
     let opts = Opts::parse();
 
-    let (filename, source) = match read_input(&opts.file) {
-        Ok(ok) => ok,
+    if let Some(code) = &opts.explain {
+        return explain(code);
+    }
+
+    let output = match opts.error_output.as_ref().map(File::create) {
+        Some(Ok(file)) => Some(file),
+        Some(Err(err)) => {
+            report_early(errors::IoError(err));
+
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+
+    let input = match &opts.command {
+        Some(Command::Fix(fix)) => Some(fix.file.clone()),
+        None => opts.file.clone(),
+    };
+
+    let mut files = Files::new();
+
+    bug::install_hook();
+
+    let fixed = read_input(&input).and_then(|(filename, source)| {
+        let file = files.add(filename, source);
+
+        match &opts.command {
+            Some(Command::Fix(fix)) => {
+                let fixed = apply_fixes(&opts, &fix.file, &files, file)?;
+                let name = files.name(file).to_string();
+
+                Ok(fixed.map_or(file, |source| files.add(name, source)))
+            }
+            None => Ok(file),
+        }
+    });
+
+    let file = match fixed {
+        Ok(file) => file,
         Err(err) => {
-            Reporter::early().emit(errors::IoError(err));
+            let mut reporter = new_reporter(&opts, output, &files);
+            reporter.emit(errors::IoError(err));
+            let _ = reporter.finish();
 
             return ExitCode::FAILURE;
         }
     };
 
-    let mut reporter = Reporter::new(&filename, &source);
+    let mut reporter = new_reporter(&opts, output, &files);
+
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(|| match &opts.command {
+            Some(Command::Fix(_)) => match lower(&files, file, &mut reporter) {
+                Some(_) => ExitCode::SUCCESS,
+                None => ExitCode::FAILURE,
+            },
+            None => compile(&opts, &files, file, &mut reporter),
+        }));
+
+    let code = result.unwrap_or_else(|_| {
+        reporter.emit(errors::InternalError(bug::take()));
+
+        ExitCode::from(bug::EXIT_CODE)
+    });
+
+    if let Err(err) = reporter.finish() {
+        report_early(errors::IoError(err));
+
+        return ExitCode::FAILURE;
+    }
 
-    let ast = match parse(&source) {
+    code
+}
+
+fn lower(
+    files: &Files,
+    file: FileId,
+    reporter: &mut Reporter,
+) -> Option<hir::Context> {
+    let symbols = ast::Symbols::new();
+
+    let ast = match ice::phase("parsing", || {
+        parse(file, files.source(file), &symbols)
+    }) {
         Ok(ok) => ok,
         Err(errors) => {
             for err in errors {
+                if reporter.limit_reached() {
+                    break;
+                }
+
                 reporter.emit(errors::ParseError(err));
             }
 
-            return ExitCode::FAILURE;
+            return None;
+        }
+    };
+
+    let ctx = ice::phase("lowering", || lower_ast(&ast, &symbols, reporter))?;
+
+    // Denied lints do not stop lowering, but still fail the compilation.
+    if reporter.has_errors() {
+        return None;
+    }
+
+    Some(ctx)
+}
+
+/// Rewrites `path` with every machine-applicable suggestion for `file`
+/// applied, and returns the new source if anything changed.
+///
+/// The diagnostics found on the way are dropped, since the caller checks the
+/// fixed source again to report what is left.
+fn apply_fixes(
+    opts: &Opts,
+    path: &Path,
+    files: &Files,
+    file: FileId,
+) -> io::Result<Option<String>> {
+    let (levels, _) = lint_levels(opts);
+    let mut reporter = Reporter::capture(files).with_lint_levels(levels);
+
+    // A panic is left to be reported by the check of the unchanged source.
+    if panic::catch_unwind(AssertUnwindSafe(|| {
+        lower(files, file, &mut reporter)
+    }))
+    .is_err()
+    {
+        bug::take();
+
+        return Ok(None);
+    }
+
+    let suggestions = reporter
+        .diagnostics()
+        .iter()
+        .flat_map(Diagnostic::suggestions)
+        .filter(|suggestion| {
+            suggestion.applicability == Applicability::MachineApplicable
+                && suggestion.span.file() == file
+        })
+        .collect::<Vec<_>>();
+
+    if suggestions.is_empty() {
+        return Ok(None);
+    }
+
+    let source = apply_suggestions(files.source(file), suggestions);
+    fs::write(path, &source)?;
+
+    Ok(Some(source))
+}
+
+fn compile(
+    opts: &Opts,
+    files: &Files,
+    file: FileId,
+    reporter: &mut Reporter,
+) -> ExitCode {
+    let Some(mut ctx) = lower(files, file, reporter) else {
+        return ExitCode::FAILURE;
+    };
+
+    if !opts.no_opt {
+        ice::phase("inlining", || inline(&mut ctx));
+        ice::phase("simplification", || simplify(&mut ctx));
+    }
+
+    let Some(emit) = opts.emit else {
+        return ExitCode::SUCCESS;
+    };
+
+    let code = ice::phase("code generation", || match emit {
+        Emit::C => emit_c(&ctx, reporter),
+        Emit::Rust => emit_rust(&ctx, reporter),
+        Emit::Mir => {
+            let mode = if opts.inline {
+                CallMode::Inline
+            } else {
+                CallMode::Instance
+            };
+
+            lower_hir(&ctx, mode, reporter).map(|module| module.to_string())
         }
+    });
+
+    let Some(code) = code else {
+        return ExitCode::FAILURE;
     };
 
-    if lower_ast(&ast, &mut reporter).is_none() {
+    if let Err(err) = write_output(&opts.output, &code) {
+        reporter.emit(errors::IoError(err));
+
         return ExitCode::FAILURE;
     }
 
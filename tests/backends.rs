@@ -0,0 +1,251 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use adpl::ast;
+use adpl::ast_lowering::lower_ast;
+use adpl::codegen::{emit_c, emit_rust};
+use adpl::hir;
+use adpl::parse::parse;
+use adpl::util::{Files, Reporter};
+
+const WIDTHS: &[u32] = &[1, 8, 16, 63, 64];
+
+/// Bodies of `def f(a: uint[W], b: uint[W]) -> uint[W]`, with `W` replaced
+/// by the width.
+const BODIES: &[&str] = &[
+    "!a + b",
+    "-a * b",
+    "!(a + b)",
+    "-(a - b)",
+    "(!a) * b",
+    "a - b",
+    "a * b + 1",
+    "a / b",
+    "a ^ b",
+    "a ^ 2 ^ 3",
+    "a << b",
+    "a >> b",
+    "a << 3 >> 1",
+    "(a < b) + (a <= b) + (a > b) + (a >= b) + (a = b) + (a != b)",
+    "!(a = b)",
+    "negate[W](a) + b",
+    "swap[W](Pair[W] { lo = a, hi = b }).lo",
+];
+
+const PRELUDE: &str = "
+struct Pair[W] {
+    lo: uint[W],
+    hi: uint[W],
+}
+
+def negate[W](a: uint[W]) -> uint[W] {
+    return -a;
+}
+
+def swap[W](p: Pair[W]) -> Pair[W] {
+    return Pair[W] { lo = p.hi, hi = p.lo };
+}
+";
+
+/// A let that, numbered, would be spelled like the parameter it reads.
+const SHADOWED: &str = "
+def f(x_1: uint[8]) -> uint[8] {
+    x = x_1 + 1;
+    return x + x_1;
+}
+";
+
+fn inputs(width: u32) -> Vec<u64> {
+    let max = if width == 64 {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    };
+    let mut values = vec![0, 1, 2, 3, 7, max, max - 1, max / 2, max / 3];
+
+    values.retain(|&value| value <= max);
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+fn source() -> String {
+    let mut src = String::from(PRELUDE);
+
+    for &width in WIDTHS {
+        for (i, body) in BODIES.iter().enumerate() {
+            let body = body.replace('W', &width.to_string());
+
+            write!(
+                src,
+                "\ndef f{i}_{width}(a: uint[{width}], b: uint[{width}]) \
+                 -> uint[{width}] {{\n    return {body};\n}}\n",
+            )
+            .unwrap();
+        }
+    }
+
+    src
+}
+
+fn compile(src: &str) -> (String, String) {
+    let mut files = Files::new();
+    let file = files.add("backends.adpl", src);
+    let mut reporter = Reporter::new(&files);
+    let symbols = ast::Symbols::new();
+
+    let ast = parse(file, src, &symbols).expect("test source parses");
+    let ctx = lower_ast(&ast, &symbols, &mut reporter).expect("lowering");
+    let c = emit_c(&ctx, &mut reporter).expect("C code generation");
+    let rust = emit_rust(&ctx, &mut reporter).expect("Rust code generation");
+
+    assert!(!reporter.has_errors());
+
+    (c, rust)
+}
+
+fn run(program: &Path) -> String {
+    let output = Command::new(program).output().unwrap();
+    assert!(output.status.success(), "{} failed", program.display());
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn build(tool: &str, args: &[&str], dir: &Path) {
+    let status = Command::new(tool)
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap_or_else(|err| panic!("cannot run `{tool}`: {err}"));
+
+    assert!(status.success(), "`{tool}` failed");
+}
+
+#[test]
+fn c_and_rust_agree() {
+    let src = source();
+    let (mut c, mut rust) = compile(&src);
+
+    c.push_str("\n#include <stdio.h>\n\nint main(void)\n{\n");
+    rust.push_str("\nfn main() {\n");
+
+    let mut calls = Vec::new();
+
+    for &width in WIDTHS {
+        let inputs = inputs(width);
+
+        for (i, body) in BODIES.iter().enumerate() {
+            for &a in &inputs {
+                for &b in &inputs {
+                    writeln!(
+                        c,
+                        "    printf(\"%llu\\n\", (unsigned long long)\
+                         f{i}_{width}({a}ULL, {b}ULL));",
+                    )
+                    .unwrap();
+                    writeln!(
+                        rust,
+                        "    println!(\"{{}}\", f{i}_{width}(UInt::new({a}), \
+                         UInt::new({b})).get());",
+                    )
+                    .unwrap();
+
+                    calls.push(format!("`{body}` with {a}, {b}"));
+                }
+            }
+        }
+    }
+
+    c.push_str("    return 0;\n}\n");
+    rust.push_str("}\n");
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("backends");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("golden.c"), c).unwrap();
+    std::fs::write(dir.join("golden.rs"), rust).unwrap();
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    build(&cc, &["-O1", "-o", "golden_c", "golden.c"], &dir);
+    build(
+        "rustc",
+        &["--edition=2024", "-o", "golden_rs", "golden.rs"],
+        &dir,
+    );
+
+    let c = run(&dir.join("golden_c"));
+    let rust = run(&dir.join("golden_rs"));
+
+    assert_eq!(c.lines().count(), calls.len());
+    assert_eq!(rust.lines().count(), calls.len());
+
+    for ((c, rust), call) in c.lines().zip(rust.lines()).zip(&calls) {
+        assert_eq!(c, rust, "C and Rust disagree on {call}");
+    }
+}
+
+#[test]
+fn lets_do_not_shadow_params() {
+    let (mut c, mut rust) = compile(SHADOWED);
+    let inputs = inputs(8);
+
+    c.push_str("\n#include <stdio.h>\n\nint main(void)\n{\n");
+    rust.push_str("\nfn main() {\n");
+
+    for &x in &inputs {
+        writeln!(c, "    printf(\"%u\\n\", (unsigned)f({x}));").unwrap();
+        writeln!(rust, "    println!(\"{{}}\", f(UInt::new({x})).get());")
+            .unwrap();
+    }
+
+    c.push_str("    return 0;\n}\n");
+    rust.push_str("}\n");
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("shadowed");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("shadowed.c"), c).unwrap();
+    std::fs::write(dir.join("shadowed.rs"), rust).unwrap();
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    build(&cc, &["-O1", "-o", "shadowed_c", "shadowed.c"], &dir);
+    build(
+        "rustc",
+        &["--edition=2024", "-o", "shadowed_rs", "shadowed.rs"],
+        &dir,
+    );
+
+    let expected = inputs
+        .iter()
+        .map(|x| format!("{}\n", (2 * x + 1) & 0xff))
+        .collect::<String>();
+
+    assert_eq!(run(&dir.join("shadowed_c")), expected);
+    assert_eq!(run(&dir.join("shadowed_rs")), expected);
+}
+
+#[test]
+fn missing_return() {
+    let src = "def g(a: uint[8]) -> uint[8] {\n    b = a;\n}\n";
+    let mut files = Files::new();
+    let file = files.add("missing_return.adpl", src);
+    let symbols = ast::Symbols::new();
+
+    let ast = parse(file, src, &symbols).expect("test source parses");
+    let mut reporter = Reporter::capture(&files);
+    let ctx = lower_ast(&ast, &symbols, &mut reporter).expect("lowering");
+
+    type Backend = fn(&hir::Context, &mut Reporter) -> Option<String>;
+
+    for emit in [emit_c as Backend, emit_rust] {
+        let mut reporter = Reporter::capture(&files);
+        assert!(emit(&ctx, &mut reporter).is_none());
+
+        let codes = reporter
+            .diagnostics()
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code())
+            .collect::<Vec<_>>();
+
+        assert_eq!(codes, ["E0023"]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+const SRC: &str = "
+#[inline]
+def checked(a: uint[8]) -> uint[8] where a > 0 {
+    return a - 1;
+}
+
+def small(a: uint[8]) -> uint[8] {
+    return a + 1;
+}
+
+#[noinline]
+def kept(a: uint[8]) -> uint[8] {
+    return a + 2;
+}
+
+def f(a: uint[8]) -> uint[8] {
+    return checked(a) + small(a) + kept(a);
+}
+";
+
+fn write(name: &str, src: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(name);
+    std::fs::write(&path, src).unwrap();
+    path
+}
+
+/// Emits the MIR of [`SRC`] with `flags` and returns the functions that `f`
+/// instantiates.
+fn instances(flags: &[&str]) -> Vec<String> {
+    let path = write("inline.adpl", SRC);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+        .arg(&path)
+        .args(["--emit", "mir"])
+        .args(flags)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "adpl {flags:?} failed");
+
+    let mir = String::from_utf8(output.stdout).unwrap();
+    let (_, f) = mir.split_once("fn f(").unwrap();
+
+    f.lines()
+        .filter_map(|line| line.split_once("instance @"))
+        .map(|(_, rest)| rest.split('(').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn mir_call_modes() {
+    assert_eq!(instances(&[]), ["checked", "kept"]);
+    assert_eq!(instances(&["--no-opt"]), ["checked", "small", "kept"]);
+    assert!(instances(&["--inline"]).is_empty());
+    assert!(instances(&["--no-opt", "--inline"]).is_empty());
+}
+
+#[test]
+fn inline_requirement_warns() {
+    let path = write("inline_requirement.adpl", SRC);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+        .arg(&path)
+        .args(["--emit", "mir"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(stderr.contains(
+        "warning: `#[inline]` has no effect on a definition with a `where` \
+         clause",
+    ));
+    assert!(stderr.contains("1 warning emitted"), "{stderr}");
+}
+
+#[test]
+fn mir_instance_names() {
+    let path = write(
+        "mangle.adpl",
+        "
+def foo[N](a: uint[8]) -> uint[8] {
+    return a + N;
+}
+
+def foo_8(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+def adpl_3foo_8(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+def f(a: uint[8]) -> uint[8] {
+    return foo[8](a) + foo_8(a) + adpl_3foo_8(a);
+}
+",
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+        .arg(&path)
+        .args(["--emit", "mir", "--no-opt"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let mir = String::from_utf8(output.stdout).unwrap();
+    let mut names = mir
+        .lines()
+        .filter_map(|line| line.strip_prefix("fn "))
+        .map(|line| line.split('(').next().unwrap())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+
+    assert_eq!(names, ["adpl_3foo_8", "adpl_3foo_8_", "f", "foo_8"]);
+}
+
+/// Runs `adpl fix` on `src` and returns whether it succeeded, what it
+/// reported and the fixed source.
+fn fix(name: &str, src: &str) -> (bool, String, String) {
+    let path = write(name, src);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+        .arg("fix")
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let fixed = std::fs::read_to_string(&path).unwrap();
+
+    (output.status.success(), stderr, fixed)
+}
+
+#[test]
+fn fix_reports_what_is_left() {
+    let src = "
+def f(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+def g(a: uint[8]) -> uint[8] {
+    return f(a, a);
+}
+";
+
+    let (success, stderr, fixed) = fix("fixable.adpl", src);
+    assert!(success);
+    assert_eq!(stderr, "");
+    assert_eq!(fixed, src.replace("f(a, a)", "f(a)"));
+
+    let src =
+        format!("{src}\ndef h(a: uint[8]) -> uint[8] {{\n    return b;\n}}\n");
+
+    let (success, stderr, fixed) = fix("unfixable.adpl", &src);
+    assert!(!success);
+    assert!(stderr.contains("undefined name `b`"), "{stderr}");
+    assert!(
+        stderr.contains("aborting due to 1 previous error"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("argument"), "{stderr}");
+    assert_eq!(fixed, src.replace("f(a, a)", "f(a)"));
+}
+
+#[test]
+fn misspelled_field() {
+    let src = "
+struct Vec[N] {
+    length: uint[8],
+    data: uint[N],
+}
+
+def f(y: Vec[4]) -> uint[8] {
+    return y.lenght;
+}
+";
+
+    let path = write("field.adpl", src);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+        .arg(&path)
+        .args(["--emit", "c"])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("no field `lenght`"), "{stderr}");
+    assert!(
+        stderr.contains("a field with a similar name exists: `length`"),
+        "{stderr}",
+    );
+}
+
+#[test]
+fn lint_levels() {
+    let src = "
+#[allow(nope, unknown_lints)]
+def f(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+#[allow(typo)]
+def g(a: uint[8]) -> uint[8] {
+    return a;
+}
+";
+
+    let path = write("lints.adpl", src);
+
+    let run = |flags: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+            .arg(&path)
+            .args(["--emit", "c"])
+            .args(flags)
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (output.status.success(), stderr)
+    };
+
+    let (success, stderr) = run(&[]);
+    assert!(success);
+    assert!(!stderr.contains("`nope`"), "{stderr}");
+    assert!(stderr.contains("warning: unknown lint `typo`"), "{stderr}");
+    assert!(stderr.contains("1 warning emitted"), "{stderr}");
+
+    let (success, stderr) = run(&["-D", "unknown_lints"]);
+    assert!(!success);
+    assert!(stderr.contains("error: unknown lint `typo`"), "{stderr}");
+
+    let (success, stderr) = run(&["-D", "warnings", "-A", "unknown-lints"]);
+    assert!(success);
+    assert_eq!(stderr, "");
+
+    let (success, stderr) = run(&["-A", "nope"]);
+    assert!(success);
+    assert!(stderr.contains("warning: unknown lint `nope`"), "{stderr}");
+    assert!(stderr.contains("2 warnings emitted"), "{stderr}");
+}
+
+#[test]
+fn internal_compiler_error() {
+    let src = "
+def f(a: uint[8]) -> uint[8] {
+    return a;
+}
+
+def g(a: uint[8]) -> uint[8] {
+    return f(a);
+}
+";
+
+    let path = write("ice.adpl", src);
+
+    let run = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_adpl"))
+            .arg(&path)
+            .args(["--emit", "c", "--error-format", format])
+            .env("ADPL_FORCE_ICE", "g")
+            .env("RUST_BACKTRACE", "0")
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(101));
+        assert!(output.stdout.is_empty());
+
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = run("human");
+    assert!(
+        stderr.contains("bug: internal compiler error: forced by"),
+        "{stderr}",
+    );
+    assert!(stderr.contains("ice.adpl:6:5"), "{stderr}");
+    assert!(stderr.contains("while processing `g`"), "{stderr}");
+    assert!(stderr.contains("compiler phase: lowering"), "{stderr}");
+    assert!(!stderr.contains("backtrace:"), "{stderr}");
+
+    let stderr = run("json");
+    let bug = stderr
+        .lines()
+        .find(|line| line.starts_with("{\"severity\":\"bug\""))
+        .unwrap_or_else(|| panic!("no bug diagnostic in {stderr}"));
+
+    assert!(bug.contains("\"line_start\":6,\"column_start\":5"), "{bug}");
+}
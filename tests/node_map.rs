@@ -0,0 +1,152 @@
+use std::ops::Range;
+
+use adpl::ast;
+use adpl::ast_lowering::lower_ast;
+use adpl::hir::{self, Node, NodeMap};
+use adpl::parse::parse;
+use adpl::util::{FileId, Files, Reporter, Span};
+
+const SRC: &str = "
+def g(a: uint[8], b: uint[8]) -> uint[8] where a > b {
+    return a;
+}
+
+def f(x: uint[8]) -> uint[8] {
+    y = x + 1;
+    z = g(x, y * 2);
+    return z;
+}
+";
+
+fn lower() -> (FileId, hir::Context) {
+    let mut files = Files::new();
+    let file = files.add("node_map.adpl", SRC);
+    let mut reporter = Reporter::capture(&files);
+    let symbols = ast::Symbols::new();
+
+    let ast = parse(file, SRC, &symbols).expect("source parses");
+    let ctx = lower_ast(&ast, &symbols, &mut reporter).expect("source lowers");
+
+    (file, ctx)
+}
+
+/// Returns the offset of the `n`th occurrence of `pattern` in the source.
+fn offset(pattern: &str, n: usize) -> usize {
+    SRC.match_indices(pattern).nth(n).unwrap().0
+}
+
+fn span(ctx: &hir::Context, node: Node) -> Option<Span> {
+    match node {
+        Node::Record(record) => Some(ctx[record].span),
+        Node::Field(field) => Some(ctx[field].span),
+        Node::Definition(def) => Some(ctx[def].span),
+        Node::Parameter(param) => Some(ctx[param].span),
+        Node::Statement(_) => None,
+        Node::Expression(expr) => Some(ctx[expr].span),
+        Node::Local(local) => Some(ctx[local].name.span),
+    }
+}
+
+/// Returns the source text of the innermost node at `offset`.
+fn text_at(
+    ctx: &hir::Context,
+    map: &NodeMap,
+    file: FileId,
+    offset: usize,
+) -> &'static str {
+    let node = map.node_at(file, offset).expect("a node at the offset");
+
+    &SRC[Range::from(span(ctx, node).unwrap())]
+}
+
+fn name(ctx: &hir::Context, def: hir::Index<hir::Definition>) -> &str {
+    &ctx.symbols[ctx[def].name.symbol]
+}
+
+#[test]
+fn nested_call_argument() {
+    let (file, ctx) = lower();
+    let map = NodeMap::new(&ctx);
+
+    assert_eq!(text_at(&ctx, &map, file, offset("y * 2", 0)), "y");
+    assert_eq!(text_at(&ctx, &map, file, offset("* 2", 0)), "y * 2");
+    assert_eq!(text_at(&ctx, &map, file, offset(", y", 0)), "g(x, y * 2)");
+
+    let Some(Node::Expression(mul)) = map.node_at(file, offset("* 2", 0))
+    else {
+        panic!("no expression at `*`");
+    };
+
+    let call = map.parent(mul).unwrap();
+    assert!(matches!(call, Node::Expression(call)
+        if matches!(ctx[call].kind, hir::ExprKind::Call(_))));
+    assert!(matches!(map.parent(call), Some(Node::Statement(_))));
+}
+
+#[test]
+fn between_statements() {
+    let (file, ctx) = lower();
+    let map = NodeMap::new(&ctx);
+
+    // Statements have no span of their own, so the gap between two of them
+    // belongs to the definition.
+    let gap = offset("1;", 0) + 1;
+    let Some(Node::Definition(def)) = map.node_at(file, gap) else {
+        panic!("no definition between the statements");
+    };
+
+    assert_eq!(name(&ctx, def), "f");
+    assert_eq!(text_at(&ctx, &map, file, offset("z = g", 0)), "z");
+}
+
+#[test]
+fn outside_items() {
+    let (file, ctx) = lower();
+    let map = NodeMap::new(&ctx);
+
+    assert_eq!(map.node_at(file, 0), None);
+    assert_eq!(map.node_at(file, offset("}\n\ndef f", 0) + 1), None);
+    assert_eq!(map.node_at(file, SRC.len()), None);
+    assert_eq!(map.node_at(file, SRC.len() + 10), None);
+}
+
+#[test]
+fn enclosing_definition() {
+    let (file, ctx) = lower();
+    let map = NodeMap::new(&ctx);
+
+    let two = map.node_at(file, offset("2)", 0)).unwrap();
+    let def = map.enclosing_definition(two).unwrap();
+    assert_eq!(name(&ctx, def), "f");
+
+    // Requirements are children of their definition as well.
+    let b = map.node_at(file, offset("b {", 0)).unwrap();
+    let def = map.enclosing_definition(b).unwrap();
+    assert_eq!(name(&ctx, def), "g");
+
+    assert_eq!(map.enclosing_definition(Node::Definition(def)), Some(def));
+}
+
+#[test]
+fn overlapping_spans() {
+    let (file, mut ctx) = lower();
+
+    // Stretch `x + 1` over the start of the next statement, as inlining can
+    // leave spans that are not nested.
+    let plus = offset("x + 1", 0);
+    let (expr, _) = ctx
+        .exprs
+        .iter()
+        .find(|(_, expr)| Range::from(expr.span) == (plus..plus + 5))
+        .unwrap();
+    let z = offset("z = g", 0);
+    ctx.exprs[expr].span = Span::new(file, plus, z + 1);
+
+    let map = NodeMap::new(&ctx);
+
+    // The span that starts last among those that contain the offset wins.
+    assert_eq!(map.node_at(file, z - 1), Some(Node::Expression(expr)));
+    assert_eq!(text_at(&ctx, &map, file, z), "z");
+    assert_eq!(text_at(&ctx, &map, file, z + 4), "g(x, y * 2)");
+    assert_eq!(text_at(&ctx, &map, file, plus + 4), "1");
+}
@@ -0,0 +1,115 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use adpl::ast;
+use adpl::ast_lowering::lower_ast;
+use adpl::codegen::{emit_c, emit_rust};
+use adpl::hir::Context;
+use adpl::mir::{CallMode, lower_hir};
+use adpl::opt::{inline, simplify};
+use adpl::parse::parse;
+use adpl::util::{Files, Reporter};
+
+const SRC: &str = "
+struct Pair[W] {
+    lo: uint[W],
+    hi: uint[W],
+}
+
+def swap[W](p: Pair[W]) -> Pair[W] {
+    return Pair[W] { lo = p.hi, hi = p.lo };
+}
+
+#[noinline]
+def shift[N](a: uint[N], b: uint[8]) -> uint[N] where N > 2 {
+    return a << b >> 1;
+}
+
+unsafe def mix(a: uint[8], b: uint[8]) -> uint[16] {
+    c = a * b + -a;
+    unsafe {
+        d = !(c ^ 2) / 3;
+        return d - (a = b) + (a != b);
+    }
+}
+
+def f(a: uint[8], b: uint[8]) -> uint[8] {
+    p = swap[8](Pair[8] { lo = a, hi = b * 4 + 0 });
+    return shift[8](p.lo, p.hi) + mix(a, b);
+}
+";
+
+fn lower(src: &str, files: &Files, symbols: &ast::Symbols) -> Context {
+    let mut reporter = Reporter::capture(files);
+    let ast = parse(adpl::util::FileId::from_u32(0), src, symbols).unwrap();
+    let ctx = lower_ast(&ast, symbols, &mut reporter).unwrap();
+
+    assert!(!reporter.has_errors());
+    ctx
+}
+
+fn encode(ctx: &Context) -> Vec<u8> {
+    let mut data = Vec::new();
+    ctx.encode(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn round_trip() {
+    let mut files = Files::new();
+    files.add("serialize.adpl", SRC);
+    let symbols = ast::Symbols::new();
+
+    let mut ctx = lower(SRC, &files, &symbols);
+
+    for optimize in [false, true] {
+        if optimize {
+            inline(&mut ctx);
+            simplify(&mut ctx);
+        }
+
+        let data = encode(&ctx);
+        let decoded = Context::decode(&data[..], &ast::Symbols::new())
+            .unwrap_or_else(|err| panic!("optimize = {optimize}: {err}"));
+
+        assert_eq!(encode(&decoded), data, "optimize = {optimize}");
+    }
+}
+
+/// Decoding arbitrarily damaged input either fails or produces a context
+/// that the rest of the compiler handles without panicking.
+#[test]
+fn corrupted_input() {
+    let mut files = Files::new();
+    files.add("serialize.adpl", SRC);
+    let symbols = ast::Symbols::new();
+
+    let mut ctx = lower(SRC, &files, &symbols);
+    inline(&mut ctx);
+    let data = encode(&ctx);
+
+    for i in 0..data.len() {
+        for mask in [0x01, 0x10, 0xff] {
+            let mut data = data.clone();
+            data[i] ^= mask;
+
+            let Ok(mut ctx) = Context::decode(&data[..], &ast::Symbols::new())
+            else {
+                continue;
+            };
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut reporter = Reporter::capture(&files);
+
+                let _ = emit_c(&ctx, &mut reporter);
+                let _ = emit_rust(&ctx, &mut reporter);
+                let _ = lower_hir(&ctx, CallMode::Instance, &mut reporter);
+                let _ = lower_hir(&ctx, CallMode::Inline, &mut reporter);
+
+                inline(&mut ctx);
+                simplify(&mut ctx);
+            }));
+
+            assert!(result.is_ok(), "byte {i} xor {mask:#04x} caused a panic");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use adpl::ast;
+use adpl::ast_lowering::lower_ast;
+use adpl::hir::{self, ExprKind, Index, MutVisitor, Visitor};
+use adpl::parse::parse;
+use adpl::util::{Files, Reporter};
+
+const SRC: &str = "
+struct Pair[W] {
+    lo: uint[W],
+    hi: uint[W],
+}
+
+def id[W](a: uint[W]) -> uint[W] {
+    return a;
+}
+
+def f(a: uint[8]) -> uint[8] where a > 1 implements a + 2 {
+    return id[3](a) + Pair[4] { lo = a, hi = 5 }.hi;
+}
+";
+
+fn lower() -> hir::Context {
+    let mut files = Files::new();
+    let file = files.add("visit.adpl", SRC);
+    let mut reporter = Reporter::capture(&files);
+    let symbols = ast::Symbols::new();
+
+    let ast = parse(file, SRC, &symbols).expect("source parses");
+    lower_ast(&ast, &symbols, &mut reporter).expect("source lowers")
+}
+
+/// Collects the value of every literal, overriding nothing but
+/// `visit_expression`.
+struct Literals(Vec<u64>);
+
+impl Visitor for Literals {
+    fn visit_expression(
+        &mut self,
+        ctx: &hir::Context,
+        expr: Index<hir::Expression>,
+    ) {
+        if let ExprKind::Lit(lit) = &ctx[expr].kind {
+            self.0.push(lit.value);
+        }
+
+        hir::walk_expression(self, ctx, expr);
+    }
+}
+
+/// Adds 10 to every literal, overriding nothing but `visit_expression`.
+struct Bump;
+
+impl MutVisitor for Bump {
+    fn visit_expression(
+        &mut self,
+        ctx: &mut hir::Context,
+        expr: Index<hir::Expression>,
+    ) {
+        if let ExprKind::Lit(lit) = &mut ctx[expr].kind {
+            lit.value += 10;
+        }
+
+        hir::walk_expression_mut(self, ctx, expr);
+    }
+}
+
+#[test]
+fn clauses_and_generic_args() {
+    let ctx = lower();
+
+    let mut literals = Literals(Vec::new());
+    hir::walk_context(&mut literals, &ctx);
+    literals.0.sort();
+
+    assert_eq!(literals.0, [1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn clauses_and_generic_args_mut() {
+    let mut ctx = lower();
+    hir::walk_context_mut(&mut Bump, &mut ctx);
+
+    let mut literals = Literals(Vec::new());
+    hir::walk_context(&mut literals, &ctx);
+    literals.0.sort();
+
+    assert_eq!(literals.0, [11, 12, 13, 14, 15]);
+}